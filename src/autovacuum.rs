@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+
+use crate::{
+    catalog::{Column, DataType, Schema},
+    common::TransactionID,
+    instance::Instance,
+    value::{integer::IntegerValue, varchar::VarcharValue, Value},
+};
+
+pub const STAT_TABLES_VIEW_NAME: &str = "system_stat_tables";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutovacuumConfig {
+    // a table is vacuumed when its dead tuples exceed this
+    pub threshold: usize,
+    pub naptime: Duration,
+    pub cost_limit: usize,
+    pub cost_delay: Duration,
+}
+impl Default for AutovacuumConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 50,
+            naptime: Duration::from_secs(10),
+            cost_limit: 200,
+            cost_delay: Duration::from_millis(2),
+        }
+    }
+}
+
+// the worker sleeps whenever it has done the limit of work,
+// so that it does not keep the buffer pool busy against the sessions
+pub struct VacuumCost {
+    limit: usize,
+    delay: Duration,
+    balance: usize,
+}
+impl VacuumCost {
+    pub fn new(limit: usize, delay: Duration) -> Self {
+        Self {
+            limit,
+            delay,
+            balance: 0,
+        }
+    }
+    pub fn charge(&mut self, cost: usize) {
+        self.balance += cost;
+        if self.balance >= self.limit {
+            thread::sleep(self.delay);
+            self.balance = 0;
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TableStatistics {
+    pub dead_tuples: usize,
+    pub last_autovacuum: Option<SystemTime>,
+    pub last_autovacuum_removed: usize,
+    pub autovacuum_count: usize,
+}
+
+#[derive(Debug, Default)]
+struct PendingDeadTuples {
+    // the deleted versions are dead if the transaction commits, the inserted ones if it aborts
+    committed: usize,
+    aborted: usize,
+}
+
+// the counts are estimates, a rollback to a savepoint is not reflected until the next vacuum
+#[derive(Debug, Default)]
+pub struct StatisticsCollector {
+    tables: HashMap<String, TableStatistics>,
+    pending: HashMap<TransactionID, HashMap<String, PendingDeadTuples>>,
+}
+impl StatisticsCollector {
+    pub fn count_delete(&mut self, txn_id: TransactionID, table_name: &str, count: usize) {
+        self.pending_of(txn_id, table_name).committed += count;
+    }
+    pub fn count_update(&mut self, txn_id: TransactionID, table_name: &str, count: usize) {
+        let pending = self.pending_of(txn_id, table_name);
+        pending.committed += count;
+        pending.aborted += count;
+    }
+    pub fn end_transaction(&mut self, txn_id: TransactionID, committed: bool) {
+        for (table_name, pending) in self.pending.remove(&txn_id).unwrap_or_default() {
+            let count = if committed {
+                pending.committed
+            } else {
+                pending.aborted
+            };
+            if count > 0 {
+                self.tables.entry(table_name).or_default().dead_tuples += count;
+            }
+        }
+    }
+    pub fn vacuumed(&mut self, table_name: &str, removed: usize, autovacuum: bool) {
+        let statistics = self.tables.entry(table_name.to_string()).or_default();
+        statistics.dead_tuples = statistics.dead_tuples.saturating_sub(removed);
+        if autovacuum {
+            statistics.last_autovacuum = Some(SystemTime::now());
+            statistics.last_autovacuum_removed = removed;
+            statistics.autovacuum_count += 1;
+        }
+    }
+    pub fn forget(&mut self, table_name: &str) {
+        self.tables.remove(table_name);
+    }
+    pub fn get(&self, table_name: &str) -> TableStatistics {
+        self.tables.get(table_name).cloned().unwrap_or_default()
+    }
+    pub fn tables_to_vacuum(&self, threshold: usize) -> Vec<String> {
+        let mut table_names = self
+            .tables
+            .iter()
+            .filter(|(_, statistics)| statistics.dead_tuples > threshold)
+            .map(|(table_name, _)| table_name.clone())
+            .collect::<Vec<_>>();
+        table_names.sort();
+        table_names
+    }
+    pub fn view_schema() -> Schema {
+        Schema {
+            columns: [
+                ("table_name", DataType::Varchar),
+                ("n_dead_tuples", DataType::Integer),
+                ("last_autovacuum", DataType::Integer),
+                ("last_autovacuum_removed", DataType::Integer),
+                ("autovacuum_count", DataType::Integer),
+            ]
+            .into_iter()
+            .map(|(name, data_type)| Column {
+                name: name.to_string(),
+                data_type,
+            })
+            .collect(),
+        }
+    }
+    // last_autovacuum is in seconds since the unix epoch, null if the table was never vacuumed
+    pub fn view_rows(&self, table_names: &[String]) -> Vec<Vec<Value>> {
+        table_names
+            .iter()
+            .map(|table_name| {
+                let statistics = self.get(table_name);
+                vec![
+                    Value::Varchar(VarcharValue(table_name.clone())),
+                    Value::Integer(IntegerValue(statistics.dead_tuples as i64)),
+                    match statistics.last_autovacuum {
+                        Some(time) => Value::Integer(IntegerValue(
+                            time.duration_since(UNIX_EPOCH)
+                                .map(|duration| duration.as_secs() as i64)
+                                .unwrap_or_default(),
+                        )),
+                        None => Value::Null,
+                    },
+                    Value::Integer(IntegerValue(statistics.last_autovacuum_removed as i64)),
+                    Value::Integer(IntegerValue(statistics.autovacuum_count as i64)),
+                ]
+            })
+            .collect()
+    }
+    fn pending_of(&mut self, txn_id: TransactionID, table_name: &str) -> &mut PendingDeadTuples {
+        self.pending
+            .entry(txn_id)
+            .or_default()
+            .entry(table_name.to_string())
+            .or_default()
+    }
+}
+
+pub fn autovacuum_start(instance: Arc<RwLock<Instance>>, config: AutovacuumConfig) {
+    thread::spawn(move || loop {
+        thread::sleep(config.naptime);
+        if let Err(e) = autovacuum(&instance, &config) {
+            println!("autovacuum error: {}", e);
+        }
+    });
+}
+
+pub fn autovacuum(instance: &Arc<RwLock<Instance>>, config: &AutovacuumConfig) -> Result<()> {
+    let instance = instance.read().map_err(|_| anyhow::anyhow!("lock error"))?;
+    let table_names = instance
+        .statistics
+        .lock()
+        .map_err(|_| anyhow::anyhow!("lock error"))?
+        .tables_to_vacuum(config.threshold);
+    for table_name in table_names {
+        let mut cost = VacuumCost::new(config.cost_limit, config.cost_delay);
+        if let Err(e) = instance.autovacuum(&table_name, &mut cost) {
+            // e.g. the table was created by a transaction which aborted, counting starts over
+            instance
+                .statistics
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .forget(&table_name);
+            println!("autovacuum error on {}: {}", table_name, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_statistics_collector() {
+        let mut statistics_collector = StatisticsCollector::default();
+        let txn_id1 = TransactionID(1);
+        let txn_id2 = TransactionID(2);
+        statistics_collector.count_delete(txn_id1, "t1", 10);
+        statistics_collector.count_update(txn_id1, "t2", 20);
+        statistics_collector.count_update(txn_id2, "t1", 30);
+        assert_eq!(statistics_collector.get("t1").dead_tuples, 0);
+
+        // the old versions are dead after the commit, the new versions after the abort
+        statistics_collector.end_transaction(txn_id1, true);
+        statistics_collector.end_transaction(txn_id2, false);
+        assert_eq!(statistics_collector.get("t1").dead_tuples, 40);
+        assert_eq!(statistics_collector.get("t2").dead_tuples, 20);
+        assert_eq!(statistics_collector.tables_to_vacuum(20), vec!["t1"]);
+        assert_eq!(statistics_collector.tables_to_vacuum(10), vec!["t1", "t2"]);
+
+        statistics_collector.vacuumed("t1", 35, true);
+        statistics_collector.vacuumed("t2", 50, false);
+        let t1 = statistics_collector.get("t1");
+        assert_eq!(t1.dead_tuples, 5);
+        assert!(t1.last_autovacuum.is_some());
+        assert_eq!(t1.last_autovacuum_removed, 35);
+        assert_eq!(t1.autovacuum_count, 1);
+        assert_eq!(
+            statistics_collector.get("t2"),
+            TableStatistics {
+                dead_tuples: 0,
+                ..Default::default()
+            }
+        );
+        assert_eq!(statistics_collector.tables_to_vacuum(0), vec!["t1"]);
+    }
+}
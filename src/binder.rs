@@ -3,19 +3,22 @@ use std::sync::{Arc, Mutex};
 use anyhow::Result;
 
 use crate::{
+    autovacuum::{StatisticsCollector, STAT_TABLES_VIEW_NAME},
     catalog::{Catalog, Column, DataType, Schema},
     common::{PageID, TransactionID},
     parser::{
         BaseTableReferenceAST, BinaryExpressionAST, BinaryOperator, DeleteStatementAST,
         ExpressionAST, FunctionCallExpressionAST, InsertStatementAST, JoinTableReferenceAST,
-        JoinType, LiteralExpressionAST, Order, PathExpressionAST, SelectElementAST,
-        SelectStatementAST, StatementAST, SubqueryTableReferenceAST, TableReferenceAST,
-        UnaryExpressionAST, UnaryOperator, UpdateStatementAST, AGGREGATE_FUNCTION_NAMES,
+        JoinType, LiteralExpressionAST, LockingClauseAST, Order, PathExpressionAST,
+        SelectElementAST, SelectStatementAST, StatementAST, SubqueryTableReferenceAST,
+        TableReferenceAST, UnaryExpressionAST, UnaryOperator, UpdateStatementAST,
+        AGGREGATE_FUNCTION_NAMES,
     },
     tuple::Tuple,
     value::Value,
 };
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BoundStatementAST {
     Select(BoundSelectStatementAST),
@@ -33,6 +36,7 @@ pub struct BoundSelectStatementAST {
     pub having: Option<BoundExpressionAST>,
     pub order_by: Option<Vec<BoundOrderByElementAST>>,
     pub limit: Option<BoundLimitAST>,
+    pub locking_clause: Option<LockingClauseAST>,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct BoundSelectElementAST {
@@ -49,11 +53,13 @@ pub struct BoundLimitAST {
     pub count: BoundExpressionAST,
     pub offset: BoundExpressionAST,
 }
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BoundTableReferenceAST {
     Base(BoundBaseTableReferenceAST),
     Join(BoundJoinTableReferenceAST),
     Subquery(BoundSubqueryTableReferenceAST),
+    SystemView(BoundSystemViewReferenceAST),
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct BoundBaseTableReferenceAST {
@@ -62,6 +68,13 @@ pub struct BoundBaseTableReferenceAST {
     pub first_page_id: PageID,
     pub schema: Schema,
 }
+// rows are built from the state of the instance, only SELECT can read them
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BoundSystemViewReferenceAST {
+    pub view_name: String,
+    pub alias: Option<String>,
+    pub schema: Schema,
+}
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct BoundJoinTableReferenceAST {
     pub left: Box<BoundTableReferenceAST>,
@@ -203,6 +216,7 @@ impl Binder {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             });
         }
         let table_reference = match &statement.table_reference {
@@ -228,6 +242,14 @@ impl Binder {
             .map(|function| self.bind_function_call_expression(function))
             .collect::<Result<Vec<_>>>()?;
         let needs_aggregation = !group_by.is_empty() || !bound_aggregate_functions.is_empty();
+        // the locked rows are identified by the rids of a single table
+        if statement.locking_clause.is_some()
+            && (needs_aggregation || !matches!(table_reference, BoundTableReferenceAST::Base(_)))
+        {
+            return Err(anyhow::anyhow!(
+                "FOR UPDATE and FOR SHARE are not allowed with joins, subqueries or aggregation"
+            ));
+        }
         if needs_aggregation {
             self.replace_scope_by_aggregation(&group_by, aggregate_functions);
         }
@@ -283,6 +305,7 @@ impl Binder {
             having,
             order_by,
             limit,
+            locking_clause: statement.locking_clause.clone(),
         })
     }
 
@@ -322,6 +345,13 @@ impl Binder {
         table_reference: &TableReferenceAST,
     ) -> Result<BoundTableReferenceAST> {
         match table_reference {
+            TableReferenceAST::Base(table_reference)
+                if table_reference.table_name == STAT_TABLES_VIEW_NAME =>
+            {
+                Ok(BoundTableReferenceAST::SystemView(
+                    self.bind_system_view_reference(table_reference)?,
+                ))
+            }
             TableReferenceAST::Base(table_reference) => Ok(BoundTableReferenceAST::Base(
                 self.bind_base_table_reference(table_reference)?,
             )),
@@ -371,6 +401,35 @@ impl Binder {
         Ok(table_reference)
     }
 
+    fn bind_system_view_reference(
+        &mut self,
+        table_reference: &BaseTableReferenceAST,
+    ) -> Result<BoundSystemViewReferenceAST> {
+        let table_reference = BoundSystemViewReferenceAST {
+            view_name: table_reference.table_name.clone(),
+            alias: table_reference.alias.clone(),
+            schema: StatisticsCollector::view_schema(),
+        };
+        self.scopes
+            .last_mut()
+            .ok_or_else(|| anyhow::anyhow!("no scope"))?
+            .tables
+            .push(ScopeTable {
+                table_name: table_reference.view_name.clone(),
+                alias: table_reference.alias.clone(),
+                columns: table_reference
+                    .schema
+                    .columns
+                    .iter()
+                    .map(|column| ScopeColumn {
+                        column_name: column.name.clone(),
+                        data_type: Some(column.data_type.clone()),
+                    })
+                    .collect::<Vec<_>>(),
+            });
+        Ok(table_reference)
+    }
+
     fn bind_join_table_reference(
         &mut self,
         table_reference: &JoinTableReferenceAST,
@@ -458,7 +517,11 @@ impl Binder {
                     ));
                 }
                 for column_name in column_names {
-                    if !schema.columns.iter().any(|column| column.name == *column_name) {
+                    if !schema
+                        .columns
+                        .iter()
+                        .any(|column| column.name == *column_name)
+                    {
                         return Err(anyhow::anyhow!("column {} not found", column_name));
                     }
                 }
@@ -996,6 +1059,7 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -1107,6 +1171,7 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -1222,6 +1287,7 @@ mod tests {
                             having: None,
                             order_by: None,
                             limit: None,
+                            locking_clause: None,
                         },
                         alias: "sub1".to_string(),
                         schema: Schema {
@@ -1248,6 +1314,7 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -1290,6 +1357,7 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -6,9 +6,9 @@ use std::{
 use anyhow::{anyhow, Result};
 
 use crate::{
-    common::{PageID, PAGE_SIZE},
+    common::{PageID, INVALID_TRANSACTION_ID, LSN, PAGE_SIZE},
     disk::DiskManager,
-    log::LogManager,
+    log::{FreePage, LogManager, LogRecordBody, ReuseFreePage},
     page::{Page, PageType},
 };
 
@@ -16,6 +16,8 @@ struct Frame {
     page: Arc<RwLock<Page>>,
     pin_count: u32,
     is_dirty: bool,
+    // changes from this lsn may not be on disk, it is the end of the log when a clean page was pinned
+    rec_lsn: LSN,
 }
 impl Frame {
     fn new(page: Arc<RwLock<Page>>) -> Self {
@@ -23,6 +25,7 @@ impl Frame {
             page,
             pin_count: 0,
             is_dirty: false,
+            rec_lsn: LSN(0),
         }
     }
     fn add_pin_count(&mut self) {
@@ -77,8 +80,16 @@ impl BufferPoolManager {
                 self.page_table.insert(page_id, frame_id);
             }
         }
+        let next_lsn = self
+            .log_manager
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .next_lsn;
         if let Some(&frame_id) = self.page_table.get(&page_id) {
             if let Some(frame) = &mut self.frames[frame_id] {
+                if !frame.is_dirty && !frame.is_pinned() {
+                    frame.rec_lsn = next_lsn;
+                }
                 frame.add_pin_count();
                 self.replacer.pin(frame_id);
                 return Ok(frame.page.clone());
@@ -105,14 +116,35 @@ impl BufferPoolManager {
         if self.is_full() {
             self.evict_page()?;
         }
-        let page_id = self.disk_manager.allocate_page()?;
+        let page_id = match self.disk_manager.allocate_free_page() {
+            // the page is not handed out again by the free list recovered from the log
+            Some(page_id) => {
+                self.log_manager
+                    .lock()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .append(
+                        INVALID_TRANSACTION_ID,
+                        LogRecordBody::ReuseFreePage(ReuseFreePage { page_id }),
+                    )?;
+                page_id
+            }
+            None => self.disk_manager.allocate_page()?,
+        };
         let page = Arc::new(RwLock::new(Page::new(page_id, page_type)));
         let frame_id = self.frames.len();
         self.frames.push(Some(Frame::new(page.clone())));
         self.page_table.insert(page_id, frame_id);
 
+        let next_lsn = self
+            .log_manager
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .next_lsn;
         if let Some(&frame_id) = self.page_table.get(&page_id) {
             if let Some(frame) = &mut self.frames[frame_id] {
+                if !frame.is_dirty && !frame.is_pinned() {
+                    frame.rec_lsn = next_lsn;
+                }
                 frame.add_pin_count();
                 self.replacer.pin(frame_id);
                 return Ok(frame.page.clone());
@@ -140,6 +172,66 @@ impl BufferPoolManager {
         }
         Ok(())
     }
+    // the page must not be pinned, its id is reused by a later new_page
+    pub fn delete_page(&mut self, page_id: PageID) -> Result<()> {
+        if let Some(&frame_id) = self.page_table.get(&page_id) {
+            if let Some(frame) = &self.frames[frame_id] {
+                if frame.is_pinned() {
+                    return Err(anyhow!("page {} is pinned", page_id.0));
+                }
+            }
+            self.replacer.remove(frame_id);
+            self.frames[frame_id] = None;
+            self.page_table.remove(&page_id);
+        }
+        self.log_manager
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .append(
+                INVALID_TRANSACTION_ID,
+                LogRecordBody::FreePage(FreePage { page_id }),
+            )?;
+        self.disk_manager.deallocate_page(page_id);
+        Ok(())
+    }
+    // taken into checkpoints, so that recovery replays only the later changes of the free list
+    pub fn free_page_ids(&self) -> Vec<PageID> {
+        self.disk_manager.free_page_ids()
+    }
+    pub fn set_free_page_ids(&mut self, free_page_ids: Vec<PageID>) {
+        self.disk_manager.set_free_page_ids(free_page_ids);
+    }
+    // pinned pages are included, their changes may already be logged before they are unpinned
+    pub fn dirty_page_table(&self) -> Vec<(PageID, LSN)> {
+        let mut dirty_pages = self
+            .page_table
+            .iter()
+            .filter_map(|(&page_id, &frame_id)| match &self.frames[frame_id] {
+                Some(frame) if frame.is_dirty || frame.is_pinned() => {
+                    Some((page_id, frame.rec_lsn))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        dirty_pages.sort();
+        dirty_pages
+    }
+    // writes the pages which have been dirty since before the lsn, so that the log before it can be removed
+    pub fn flush_pages_before(&mut self, lsn: LSN) -> Result<()> {
+        let page_ids = self
+            .page_table
+            .iter()
+            .filter(|(_, &frame_id)| match &self.frames[frame_id] {
+                Some(frame) => frame.is_dirty && !frame.is_pinned() && frame.rec_lsn < lsn,
+                None => false,
+            })
+            .map(|(&page_id, _)| page_id)
+            .collect::<Vec<_>>();
+        for page_id in page_ids {
+            self.flush_page(page_id)?;
+        }
+        Ok(())
+    }
     pub fn shutdown(&mut self) -> Result<()> {
         self.flush_all_pages()?;
         Ok(())
@@ -161,6 +253,8 @@ impl BufferPoolManager {
                         .map_err(|_| anyhow!("lock error"))?
                         .flush()?;
                     self.disk_manager.write_page(page_id, page.data())?;
+                    drop(page);
+                    frame.is_dirty = false;
                 }
             }
         }
@@ -207,6 +301,11 @@ impl Replacer {
             Self::Lru(replacer) => replacer.unpin(frame_id),
         }
     }
+    pub fn remove(&mut self, frame_id: usize) {
+        match self {
+            Self::Lru(replacer) => replacer.remove(frame_id),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -238,11 +337,16 @@ impl LRUReplacer {
         self.frame_map.insert(frame_id, self.counter);
         self.counter = self.counter.wrapping_add(1);
     }
+    fn remove(&mut self, frame_id: usize) {
+        self.frame_map.remove(&frame_id);
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::page::table_page::TABLE_PAGE_PAGE_TYPE;
+    use crate::{
+        common::TransactionID, log::LogRecordBody, page::table_page::TABLE_PAGE_PAGE_TYPE,
+    };
 
     use super::*;
 
@@ -333,4 +437,79 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_dirty_page_table() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data_file_path = dir.path().join("data");
+        let log_file_path = dir.path().join("log");
+        let disk_manager = DiskManager::new(data_file_path.to_str().unwrap())?;
+        let log_manager = Arc::new(Mutex::new(LogManager::new(
+            log_file_path.to_str().unwrap(),
+        )?));
+        let mut buffer_pool_manager = BufferPoolManager::new(disk_manager, log_manager.clone(), 3);
+        let append = || -> Result<LSN> {
+            log_manager
+                .lock()
+                .map_err(|_| anyhow!("lock error"))?
+                .append(TransactionID(1), LogRecordBody::BeginTransaction)
+        };
+
+        append()?;
+        buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+        buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+        append()?;
+        buffer_pool_manager.unpin_page(PageID(1), true)?;
+        buffer_pool_manager.unpin_page(PageID(2), false)?;
+        assert_eq!(
+            buffer_pool_manager.dirty_page_table(),
+            vec![(PageID(1), LSN(2))]
+        );
+
+        // the lsn is kept until the page is written
+        append()?;
+        buffer_pool_manager.fetch_page(PageID(1))?;
+        buffer_pool_manager.fetch_page(PageID(2))?;
+        assert_eq!(
+            buffer_pool_manager.dirty_page_table(),
+            vec![(PageID(1), LSN(2)), (PageID(2), LSN(4))]
+        );
+        buffer_pool_manager.unpin_page(PageID(1), true)?;
+        buffer_pool_manager.unpin_page(PageID(2), false)?;
+        buffer_pool_manager.flush_all_pages()?;
+        assert_eq!(buffer_pool_manager.dirty_page_table(), vec![]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_page() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let data_file_path = dir.path().join("data");
+        let log_file_path = dir.path().join("log");
+        let disk_manager = DiskManager::new(data_file_path.to_str().unwrap())?;
+        let log_manager = Arc::new(Mutex::new(LogManager::new(
+            log_file_path.to_str().unwrap(),
+        )?));
+        let mut buffer_pool_manager = BufferPoolManager::new(disk_manager, log_manager, 3);
+
+        buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+        buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+        assert!(buffer_pool_manager.delete_page(PageID(1)).is_err());
+        buffer_pool_manager.unpin_page(PageID(1), true)?;
+        buffer_pool_manager.delete_page(PageID(1))?;
+
+        let page = buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+        assert_eq!(
+            page.read().map_err(|_| anyhow!("lock error"))?.page_id(),
+            PageID(1)
+        );
+        buffer_pool_manager.unpin_page(PageID(1), true)?;
+        let page = buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+        assert_eq!(
+            page.read().map_err(|_| anyhow!("lock error"))?.page_id(),
+            PageID(3)
+        );
+
+        Ok(())
+    }
 }
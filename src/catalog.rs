@@ -1,19 +1,22 @@
-use std::sync::{Arc, Mutex, RwLock};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, RwLock},
+};
 
 use anyhow::Result;
 
 use crate::{
     buffer::BufferPoolManager,
-    common::{PageID, TransactionID},
-    concurrency::TransactionManager,
-    index::Index,
+    common::{PageID, TransactionID, INVALID_PAGE_ID, RID},
+    concurrency::{DuplicateStatus, IsolationLevel, TransactionManager, TransactionStatus},
+    index::{Index, IndexManager},
     lock::LockManager,
     log::{LogManager, LogRecordBody, NewBPlusTreeLeafPage, NewTablePage},
     page::{
         b_plus_tree_leaf_page::B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE, table_page::TABLE_PAGE_PAGE_TYPE,
     },
     table::TableHeap,
-    value::{integer::IntegerValue, varchar::VarcharValue, Value},
+    value::{boolean::BooleanValue, integer::IntegerValue, varchar::VarcharValue, Value},
 };
 
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -64,6 +67,15 @@ pub struct Catalog {
     log_manager: Arc<Mutex<LogManager>>,
     next_table_id: u32,
     next_index_id: u32,
+    dropped_indexes: Vec<DroppedIndex>,
+}
+
+// the pages of a dropped index are freed once no transaction can see the index
+struct DroppedIndex {
+    txn_id: TransactionID,
+    index: Index,
+    rid: RID,
+    visible_before: Option<TransactionID>,
 }
 
 impl Catalog {
@@ -80,6 +92,7 @@ impl Catalog {
             log_manager,
             next_table_id: 0,
             next_index_id: 0,
+            dropped_indexes: vec![],
         }
     }
     pub fn bootstrap(&mut self, init: bool) -> Result<()> {
@@ -92,7 +105,7 @@ impl Catalog {
             .transaction_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .begin()?;
+            .begin(IsolationLevel::default())?;
         for _ in 0..SYSTEM_TABLE_COUNT {
             self.create_empty_system_table(txn_id)?;
         }
@@ -195,8 +208,62 @@ impl Catalog {
         name: &str,
         table_name: &str,
         column_names: &[String],
+        is_unique: bool,
+        is_primary: bool,
         txn_id: TransactionID,
     ) -> Result<()> {
+        // DROP INDEX finds the index by its name
+        if self.find_index_row(name, txn_id).is_some() {
+            return Err(anyhow::anyhow!("index {} already exists", name));
+        }
+        let table_first_page_id = self.get_first_page_id_by_table_name(table_name, txn_id)?;
+        let table_schema = self.get_schema_by_table_name(table_name, txn_id)?;
+        for column_name in column_names {
+            if table_schema.column_index(column_name).is_none() {
+                return Err(anyhow::anyhow!("column {} not found", column_name));
+            }
+        }
+        let mut index = Index {
+            id: self.next_index_id as i64,
+            name: name.to_string(),
+            table_name: table_name.to_string(),
+            first_page_id: INVALID_PAGE_ID,
+            columns: column_names.to_vec(),
+            schema: Schema { columns: vec![] },
+            is_unique,
+            is_primary,
+        };
+        index.set_schema(table_schema.clone());
+        // the caller locks the table, so no other transaction writes to it during the build.
+        // versions which older snapshots still see are indexed too, readers filter them by
+        // visibility, but only the live ones have to be unique
+        let mut entries = vec![];
+        let mut live_rids = HashSet::new();
+        for tuple in self
+            .system_table_heap(table_first_page_id, txn_id)
+            .iter_not_dead()
+        {
+            let rid = tuple.rid.ok_or_else(|| anyhow::anyhow!("rid is None"))?;
+            let key = index.key(&table_schema, &tuple.values(&table_schema))?;
+            let duplicate_status = self
+                .transaction_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .duplicate_status(txn_id, tuple.xmin(), tuple.xmax());
+            if duplicate_status == DuplicateStatus::Live {
+                if is_primary {
+                    if let Some(i) = key.iter().position(|value| value.is_null_value()) {
+                        return Err(anyhow::anyhow!(
+                            "column {} contains null values",
+                            column_names[i]
+                        ));
+                    }
+                }
+                live_rids.insert(rid);
+            }
+            entries.push((key, rid));
+        }
+
         let page = self
             .buffer_pool_manager
             .lock()
@@ -220,25 +287,35 @@ impl Catalog {
         self.buffer_pool_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .unpin_page(
-                page.read()
-                    .map_err(|_| anyhow::anyhow!("lock error"))?
-                    .page_id(),
-                true,
-            )?;
+            .unpin_page(page_id, true)?;
+        index.first_page_id = page_id;
+
+        // the index is filled before it is registered, so a failed build leaves no trace in the
+        // catalog. the pages of the tree are freed by the build itself, and the root page here
+        let index_manager = IndexManager::new(
+            index,
+            self.buffer_pool_manager.clone(),
+            self.log_manager.clone(),
+            txn_id,
+        );
+        if let Err(e) = index_manager.bulk_load(entries, &live_rids) {
+            self.buffer_pool_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .delete_page(page_id)?;
+            return Err(e);
+        }
+        let index_id = self.next_index_id;
+
         let mut system_indexes_table =
             self.system_table_heap(PageID(SYSTEM_INDEXES_FIRST_PAGE_ID.0), txn_id);
-        let index_id = self.next_index_id;
         let values = vec![
             Value::Integer(IntegerValue(index_id as i64)),
             Value::Varchar(VarcharValue(name.to_string())),
             Value::Varchar(VarcharValue(table_name.to_string())),
-            Value::Integer(IntegerValue(
-                page.read()
-                    .map_err(|_| anyhow::anyhow!("lock error"))?
-                    .page_id()
-                    .0 as i64,
-            )),
+            Value::Integer(IntegerValue(page_id.0 as i64)),
+            Value::Boolean(BooleanValue(is_unique)),
+            Value::Boolean(BooleanValue(is_primary)),
         ];
         system_indexes_table.insert(&values)?;
         self.next_index_id += 1;
@@ -254,6 +331,138 @@ impl Catalog {
         }
         Ok(())
     }
+    pub fn drop_index(&mut self, name: &str, if_exists: bool, txn_id: TransactionID) -> Result<()> {
+        let (rid, values) = match self.find_index_row(name, txn_id) {
+            Some(found) => found,
+            None if if_exists => return Ok(()),
+            None => return Err(anyhow::anyhow!("index {} not found", name)),
+        };
+        let index = Index::from_system_table(values)?;
+        if index.is_primary {
+            return Err(anyhow::anyhow!(
+                "cannot drop index {} because it is the primary key of table {}",
+                name,
+                index.table_name
+            ));
+        }
+        // the row may still be visible to an older snapshot
+        if self.dropped_indexes.iter().any(|dropped_index| {
+            dropped_index.index.id == index.id && dropped_index.txn_id != txn_id
+        }) {
+            return Err(anyhow::anyhow!("index {} is being dropped", name));
+        }
+        self.system_table_heap(PageID(SYSTEM_INDEXES_FIRST_PAGE_ID.0), txn_id)
+            .delete(rid)?;
+        let column_rids = self
+            .system_table_heap(PageID(SYSTEM_INDEX_COLUMNS_FIRST_PAGE_ID.0), txn_id)
+            .iter()
+            .filter(|tuple| {
+                tuple.values(&Self::system_index_columns_schema())[0]
+                    == Value::Integer(IntegerValue(index.id))
+            })
+            .filter_map(|tuple| tuple.rid)
+            .collect::<Vec<_>>();
+        let mut system_index_columns_table =
+            self.system_table_heap(PageID(SYSTEM_INDEX_COLUMNS_FIRST_PAGE_ID.0), txn_id);
+        for rid in column_rids {
+            system_index_columns_table.delete(rid)?;
+        }
+        self.dropped_indexes.push(DroppedIndex {
+            txn_id,
+            index,
+            rid,
+            visible_before: None,
+        });
+        Ok(())
+    }
+    // the row of system_indexes visible to the transaction
+    fn find_index_row(&self, name: &str, txn_id: TransactionID) -> Option<(RID, Vec<Value>)> {
+        let system_indexes_table =
+            self.system_table_heap(PageID(SYSTEM_INDEXES_FIRST_PAGE_ID.0), txn_id);
+        for tuple in system_indexes_table.iter() {
+            let values = tuple.values(&Self::system_indexes_schema());
+            if let Value::Varchar(VarcharValue(index_name)) = &values[1] {
+                if index_name == name {
+                    return tuple.rid.map(|rid| (rid, values));
+                }
+            }
+        }
+        None
+    }
+    // called after a rollback to a savepoint, the index is back if its row is visible again
+    pub fn rollback_dropped_indexes(&mut self, txn_id: TransactionID) -> Result<()> {
+        let system_indexes_table =
+            self.system_table_heap(PageID(SYSTEM_INDEXES_FIRST_PAGE_ID.0), txn_id);
+        let mut restored = vec![];
+        for (i, dropped_index) in self.dropped_indexes.iter().enumerate() {
+            if dropped_index.txn_id == txn_id
+                && system_indexes_table.get(dropped_index.rid)?.is_some()
+            {
+                restored.push(i);
+            }
+        }
+        for i in restored.into_iter().rev() {
+            self.dropped_indexes.remove(i);
+        }
+        Ok(())
+    }
+    // called after a transaction ends
+    pub fn free_dropped_indexes(&mut self) -> Result<()> {
+        let transaction_manager = self
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?;
+        let next_txn_id = transaction_manager.next_txn_id();
+        let oldest_active_txn_id = transaction_manager.oldest_active_txn_id();
+        let mut freed_indexes = vec![];
+        self.dropped_indexes.retain_mut(|dropped_index| {
+            match transaction_manager.status(dropped_index.txn_id) {
+                TransactionStatus::Running => true,
+                TransactionStatus::Aborted => false,
+                TransactionStatus::Committed => {
+                    // transactions that begin after the commit do not see the index
+                    let visible_before = *dropped_index.visible_before.get_or_insert(next_txn_id);
+                    if oldest_active_txn_id.is_none_or(|txn_id| txn_id.0 >= visible_before.0) {
+                        freed_indexes.push((dropped_index.txn_id, dropped_index.index.clone()));
+                        false
+                    } else {
+                        true
+                    }
+                }
+            }
+        });
+        drop(transaction_manager);
+        for (txn_id, index) in freed_indexes {
+            let page_ids = IndexManager::new(
+                index,
+                self.buffer_pool_manager.clone(),
+                self.log_manager.clone(),
+                txn_id,
+            )
+            .page_ids()?;
+            let mut buffer_pool_manager = self
+                .buffer_pool_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+            for page_id in page_ids {
+                buffer_pool_manager.delete_page(page_id)?;
+            }
+        }
+        Ok(())
+    }
+    pub fn get_table_names(&self, txn_id: TransactionID) -> Result<Vec<String>> {
+        let system_tables_table =
+            self.system_table_heap(PageID(SYSTEM_TABLES_FIRST_PAGE_ID.0), txn_id);
+        let mut table_names = vec![];
+        for tuple in system_tables_table.iter() {
+            if let Value::Varchar(VarcharValue(name)) =
+                &tuple.values(&Self::system_tables_schema())[1]
+            {
+                table_names.push(name.clone());
+            }
+        }
+        Ok(table_names)
+    }
     pub fn get_first_page_id_by_table_name(
         &self,
         table_name: &str,
@@ -334,10 +543,33 @@ impl Catalog {
         table_name: &str,
         txn_id: TransactionID,
     ) -> Result<Vec<Index>> {
-        let system_index_columns_table =
-            self.system_table_heap(PageID(SYSTEM_INDEX_COLUMNS_FIRST_PAGE_ID.0), txn_id);
+        self.indexes_by_table_name(table_name, txn_id, false)
+    }
+    // the indexes whose entries have to be added or removed, including the ones created after the
+    // snapshot of the transaction was taken
+    pub fn get_indexes_to_maintain(
+        &self,
+        table_name: &str,
+        txn_id: TransactionID,
+    ) -> Result<Vec<Index>> {
+        self.indexes_by_table_name(table_name, txn_id, true)
+    }
+    fn indexes_by_table_name(
+        &self,
+        table_name: &str,
+        txn_id: TransactionID,
+        latest: bool,
+    ) -> Result<Vec<Index>> {
+        let iter = |first_page_id: PageID| {
+            let table_heap = self.system_table_heap(first_page_id, txn_id);
+            if latest {
+                table_heap.iter_latest()
+            } else {
+                table_heap.iter()
+            }
+        };
         let mut columns = Vec::new();
-        for tuple in system_index_columns_table.iter() {
+        for tuple in iter(PageID(SYSTEM_INDEX_COLUMNS_FIRST_PAGE_ID.0)) {
             let values = tuple.values(&Self::system_index_columns_schema());
             let id = if let Value::Integer(id) = &values[0] {
                 id.0
@@ -352,10 +584,8 @@ impl Catalog {
             columns.push((id, column_name));
         }
 
-        let system_indexes_table =
-            self.system_table_heap(PageID(SYSTEM_INDEXES_FIRST_PAGE_ID.0), txn_id);
         let mut indexes = Vec::new();
-        for tuple in system_indexes_table.iter() {
+        for tuple in iter(PageID(SYSTEM_INDEXES_FIRST_PAGE_ID.0)) {
             let values = tuple.values(&Self::system_indexes_schema());
             if let Value::Varchar(VarcharValue(name)) = &values[2] {
                 if name == table_name {
@@ -455,7 +685,7 @@ impl Catalog {
             .transaction_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .begin()?;
+            .begin(IsolationLevel::default())?;
         let system_tables_table =
             self.system_table_heap(PageID(SYSTEM_TABLES_FIRST_PAGE_ID.0), txn_id);
         for tuple in system_tables_table.iter() {
@@ -479,7 +709,7 @@ impl Catalog {
             .transaction_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .begin()?;
+            .begin(IsolationLevel::default())?;
         let system_indexes_table =
             self.system_table_heap(PageID(SYSTEM_INDEXES_FIRST_PAGE_ID.0), txn_id);
         for tuple in system_indexes_table.iter() {
@@ -490,7 +720,7 @@ impl Catalog {
                 }
             }
         }
-        self.next_table_id = (max_index_id as u32) + 1;
+        self.next_index_id = (max_index_id as u32) + 1;
         self.transaction_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
@@ -572,6 +802,14 @@ impl Catalog {
                     name: "first_page_id".to_string(),
                     data_type: DataType::Integer,
                 },
+                Column {
+                    name: "is_unique".to_string(),
+                    data_type: DataType::Boolean,
+                },
+                Column {
+                    name: "is_primary".to_string(),
+                    data_type: DataType::Boolean,
+                },
             ],
         }
     }
@@ -619,7 +857,6 @@ mod tests {
     fn test_catalog() -> Result<()> {
         let dir = tempdir()?;
         let data_file_path = dir.path().join("data");
-        let txn_log_file_path = dir.path().join("transaction.log");
         let wal_log_file_path = dir.path().join("wal.log");
         let disk_manager = DiskManager::new(data_file_path.to_str().unwrap())?;
         let log_manager = Arc::new(Mutex::new(LogManager::new(
@@ -634,9 +871,7 @@ mod tests {
         let transaction_manager = Arc::new(Mutex::new(TransactionManager::new(
             lock_manager.clone(),
             log_manager.clone(),
-            txn_log_file_path.to_str().unwrap(),
-            IsolationLevel::RepeatableRead,
-        )?));
+        )));
         let mut catalog = Catalog::new(
             buffer_pool_manager.clone(),
             transaction_manager.clone(),
@@ -651,7 +886,7 @@ mod tests {
         let txn_id = transaction_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .begin()?;
+            .begin(IsolationLevel::default())?;
         catalog.create_table(
             "test",
             &Schema {
@@ -696,7 +931,7 @@ mod tests {
         let txn_id = transaction_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .begin()?;
+            .begin(IsolationLevel::default())?;
         let first_page_id = catalog.get_first_page_id_by_table_name("test", txn_id)?;
         let schema = catalog.get_schema_by_table_name("test", txn_id)?;
         let table_heap = TableHeap::new(
@@ -1,3 +1,7 @@
+use std::{fs::File, path::Path};
+
+use anyhow::Result;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PageID(pub u32);
 pub const PAGE_SIZE: usize = 4096;
@@ -12,3 +16,14 @@ pub struct RID(pub PageID, pub u32);
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LSN(pub u64);
+pub const INVALID_LSN: LSN = LSN(0);
+
+// a file renamed into place survives a crash only once its directory is synced
+pub fn sync_parent_dir(path: &str) -> Result<()> {
+    let parent = match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()?;
+    Ok(())
+}
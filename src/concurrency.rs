@@ -1,21 +1,42 @@
 use std::{
     collections::HashMap,
-    fs::{File, OpenOptions},
-    io::{Read, Write},
+    fmt,
     sync::{Arc, Mutex, RwLock},
 };
 
 use anyhow::Result;
 
 use crate::{
-    common::TransactionID,
+    common::{TransactionID, INVALID_TRANSACTION_ID, LSN},
     lock::LockManager,
     log::{LogManager, LogRecordBody},
+    predicate_lock::{PredicateLockManager, PredicateLockTarget},
 };
 
 pub struct Transaction {
+    begin_lsn: LSN,
+    // the commit record is written, the commit is visible once it is on disk
+    commit_lsn: Option<LSN>,
     snapshot: Vec<TransactionID>,
+    isolation_level: IsolationLevel,
+    savepoints: Vec<Savepoint>,
+}
+
+struct Savepoint {
+    name: String,
+    // the last log record of the transaction, a partial rollback undoes the ones after it
+    undo_position: LSN,
+}
+
+// the transaction can not go on after these errors, so it is rolled back as a whole
+#[derive(Debug)]
+pub struct TransactionRollbackError(pub String);
+impl fmt::Display for TransactionRollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
+impl std::error::Error for TransactionRollbackError {}
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum TransactionStatus {
@@ -25,99 +46,130 @@ pub enum TransactionStatus {
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DuplicateStatus {
+    Dead,
+    Live,
+    InProgress,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub enum IsolationLevel {
     ReadCommitted,
+    #[default]
     RepeatableRead,
+    Serializable,
+}
+
+impl TryFrom<&str> for IsolationLevel {
+    type Error = anyhow::Error;
+    fn try_from(s: &str) -> Result<Self> {
+        match &*s.to_lowercase() {
+            "read committed" => Ok(IsolationLevel::ReadCommitted),
+            "repeatable read" => Ok(IsolationLevel::RepeatableRead),
+            "serializable" => Ok(IsolationLevel::Serializable),
+            _ => Err(anyhow::anyhow!("invalid isolation level: {}", s)),
+        }
+    }
 }
 
 pub struct TransactionManager {
     lock_manager: Arc<RwLock<LockManager>>,
-    log_manager: TransactionLogManager,
     wal_log_manager: Arc<Mutex<LogManager>>,
-    isolation_level: IsolationLevel,
     next_txn_id: TransactionID,
     statuses: HashMap<TransactionID, TransactionStatus>,
     active_transactions: HashMap<TransactionID, Transaction>,
+    predicate_lock_manager: PredicateLockManager,
 }
 
 impl TransactionManager {
+    // the statuses of finished transactions are restored from the log at startup
     pub fn new(
         lock_manager: Arc<RwLock<LockManager>>,
         wal_log_manager: Arc<Mutex<LogManager>>,
-        log_file_path: &str,
-        isolation_level: IsolationLevel,
-    ) -> Result<Self> {
-        let mut log_manager = TransactionLogManager::new(log_file_path)?;
-        let logs = log_manager.read()?;
-        let statuses = logs
-            .iter()
-            .map(|log| (log.txn_id, log.status))
-            .collect::<HashMap<_, _>>();
-        let next_txn_id = if statuses.is_empty() {
-            TransactionID(1)
-        } else {
-            TransactionID(statuses.keys().max_by_key(|k| k.0).unwrap().0 + 1)
-        };
-        Ok(TransactionManager {
+    ) -> Self {
+        TransactionManager {
             lock_manager,
-            log_manager,
             wal_log_manager,
-            isolation_level,
-            next_txn_id,
-            statuses,
+            next_txn_id: TransactionID(1),
+            statuses: HashMap::new(),
             active_transactions: HashMap::new(),
-        })
+            predicate_lock_manager: PredicateLockManager::default(),
+        }
+    }
+    pub fn restore(
+        &mut self,
+        statuses: HashMap<TransactionID, TransactionStatus>,
+        next_txn_id: TransactionID,
+    ) {
+        self.statuses = statuses;
+        self.next_txn_id = next_txn_id;
     }
 
-    pub fn begin(&mut self) -> Result<TransactionID> {
+    pub fn begin(&mut self, isolation_level: IsolationLevel) -> Result<TransactionID> {
         let txn_id = self.next_txn_id;
+        let begin_lsn = self
+            .wal_log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .append(txn_id, LogRecordBody::BeginTransaction)?;
         self.next_txn_id.0 += 1;
         self.statuses.insert(txn_id, TransactionStatus::Running);
         self.active_transactions.insert(
             txn_id,
             Transaction {
+                begin_lsn,
+                commit_lsn: None,
                 snapshot: self.active_transactions.keys().cloned().collect(),
+                isolation_level,
+                savepoints: vec![],
             },
         );
-        self.wal_log_manager
-            .lock()
-            .map_err(|_| anyhow::anyhow!("lock error"))
-            .unwrap()
-            .append(txn_id, LogRecordBody::BeginTransaction)?;
+        if isolation_level == IsolationLevel::Serializable {
+            self.predicate_lock_manager.register(txn_id);
+        }
         Ok(txn_id)
     }
 
+    // sessions wait for the commit record to be on disk outside of the lock, between these two
+    pub fn log_commit(&mut self, txn_id: TransactionID) -> Result<LSN> {
+        self.transaction(txn_id)?;
+        let commit_lsn = self
+            .wal_log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .append(txn_id, LogRecordBody::CommitTransaction)?;
+        self.transaction_mut(txn_id)?.commit_lsn = Some(commit_lsn);
+        Ok(commit_lsn)
+    }
+    // a commit which is not logged yet is logged and flushed at once
     pub fn commit(&mut self, txn_id: TransactionID) -> Result<()> {
+        if self.transaction(txn_id)?.commit_lsn.is_none() {
+            self.log_commit(txn_id)?;
+            self.wal_log_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .flush()?;
+        }
         self.lock_manager
-            .write()
+            .read()
             .map_err(|_| anyhow::anyhow!("lock error"))?
             .unlock(txn_id)?;
-        self.log_manager
-            .write(TransactionLog::new(txn_id, TransactionStatus::Committed))?;
         self.statuses.insert(txn_id, TransactionStatus::Committed);
         self.active_transactions.remove(&txn_id);
-        self.wal_log_manager
-            .lock()
-            .map_err(|_| anyhow::anyhow!("lock error"))
-            .unwrap()
-            .append(txn_id, LogRecordBody::CommitTransaction)?;
-        self.wal_log_manager
-            .lock()
-            .map_err(|_| anyhow::anyhow!("lock error"))
-            .unwrap()
-            .flush()?;
+        let oldest_active_txn_id = self.oldest_active_txn_id();
+        self.predicate_lock_manager
+            .commit(txn_id, self.next_txn_id, oldest_active_txn_id);
         Ok(())
     }
 
     pub fn abort(&mut self, txn_id: TransactionID) -> Result<()> {
         self.lock_manager
-            .write()
+            .read()
             .map_err(|_| anyhow::anyhow!("lock error"))?
             .unlock(txn_id)?;
-        self.log_manager
-            .write(TransactionLog::new(txn_id, TransactionStatus::Aborted))?;
         self.statuses.insert(txn_id, TransactionStatus::Aborted);
         self.active_transactions.remove(&txn_id);
+        self.predicate_lock_manager.abort(txn_id);
         self.wal_log_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))
@@ -131,22 +183,213 @@ impl TransactionManager {
         Ok(())
     }
 
+    // transactions without a status were running at a crash
+    pub fn status(&self, txn_id: TransactionID) -> TransactionStatus {
+        self.statuses
+            .get(&txn_id)
+            .copied()
+            .unwrap_or(TransactionStatus::Aborted)
+    }
+    pub fn next_txn_id(&self) -> TransactionID {
+        self.next_txn_id
+    }
+    // the other finished transactions have committed, so checkpoints record only these
+    pub fn aborted_transactions(&self) -> Vec<TransactionID> {
+        let mut aborted_transactions = self
+            .statuses
+            .iter()
+            .filter(|(_, status)| **status == TransactionStatus::Aborted)
+            .map(|(txn_id, _)| *txn_id)
+            .collect::<Vec<_>>();
+        aborted_transactions.sort();
+        aborted_transactions
+    }
+    // with the lsns of their begin records, their changes are undone from there after a crash.
+    // the ones whose commit records are written have committed for recovery
+    pub fn active_transaction_table(&self) -> Vec<(TransactionID, LSN)> {
+        let mut active_transactions = self
+            .active_transactions
+            .iter()
+            .filter(|(_, transaction)| transaction.commit_lsn.is_none())
+            .map(|(txn_id, transaction)| (*txn_id, transaction.begin_lsn))
+            .collect::<Vec<_>>();
+        active_transactions.sort();
+        active_transactions
+    }
+    pub fn oldest_active_txn_id(&self) -> Option<TransactionID> {
+        self.active_transactions.keys().min_by_key(|k| k.0).copied()
+    }
+    // only before the first query of the transaction, its reads are not tracked yet
+    pub fn set_isolation_level(
+        &mut self,
+        txn_id: TransactionID,
+        isolation_level: IsolationLevel,
+    ) -> Result<()> {
+        self.transaction_mut(txn_id)?.isolation_level = isolation_level;
+        if isolation_level == IsolationLevel::Serializable {
+            self.predicate_lock_manager.register(txn_id);
+        } else {
+            self.predicate_lock_manager.abort(txn_id);
+        }
+        Ok(())
+    }
+    pub fn undo_position(&self, txn_id: TransactionID) -> Result<LSN> {
+        self.transaction(txn_id)?;
+        Ok(self
+            .wal_log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .last_lsn(txn_id))
+    }
+    // a name can be reused, the newest savepoint of the name is used
+    pub fn savepoint(&mut self, txn_id: TransactionID, name: &str) -> Result<()> {
+        let undo_position = self.undo_position(txn_id)?;
+        let transaction = self.transaction_mut(txn_id)?;
+        transaction.savepoints.push(Savepoint {
+            name: name.to_string(),
+            undo_position,
+        });
+        Ok(())
+    }
+    // the savepoint is kept, the later ones are destroyed
+    pub fn rollback_to_savepoint(&mut self, txn_id: TransactionID, name: &str) -> Result<LSN> {
+        let transaction = self.transaction_mut(txn_id)?;
+        let i = Self::savepoint_index(transaction, name)?;
+        transaction.savepoints.truncate(i + 1);
+        Ok(transaction.savepoints[i].undo_position)
+    }
+    // the changes after the savepoint are kept, and undone with an outer savepoint
+    pub fn release_savepoint(&mut self, txn_id: TransactionID, name: &str) -> Result<()> {
+        let transaction = self.transaction_mut(txn_id)?;
+        let i = Self::savepoint_index(transaction, name)?;
+        transaction.savepoints.truncate(i);
+        Ok(())
+    }
+    pub fn isolation_level(&self, txn_id: TransactionID) -> IsolationLevel {
+        self.active_transactions
+            .get(&txn_id)
+            .map(|transaction| transaction.isolation_level)
+            .unwrap_or_default()
+    }
+
     pub fn is_visible(
         &self,
         txn_id: TransactionID,
         x_min: TransactionID,
         x_max: TransactionID,
     ) -> bool {
-        match self.isolation_level {
+        // the level of the reading transaction
+        match self.isolation_level(txn_id) {
             IsolationLevel::ReadCommitted => {
                 self.is_visible_with_read_committed(txn_id, x_min, x_max)
             }
-            IsolationLevel::RepeatableRead => {
+            IsolationLevel::RepeatableRead | IsolationLevel::Serializable => {
                 self.is_visible_with_repeatable_read(txn_id, x_min, x_max)
             }
         }
     }
 
+    // what a read committed transaction sees, whatever the level of the transaction. writers find
+    // the indexes to maintain with this, as their snapshots may predate an index
+    pub fn is_visible_latest(
+        &self,
+        txn_id: TransactionID,
+        x_min: TransactionID,
+        x_max: TransactionID,
+    ) -> bool {
+        self.is_visible_with_read_committed(txn_id, x_min, x_max)
+    }
+
+    // SIREAD locks and rw-antidependencies are tracked only for serializable transactions
+    pub fn acquire_predicate_lock(&mut self, txn_id: TransactionID, target: PredicateLockTarget) {
+        self.predicate_lock_manager.acquire(txn_id, target);
+    }
+    pub fn check_predicate_locks(
+        &mut self,
+        txn_id: TransactionID,
+        targets: &[PredicateLockTarget],
+    ) -> Result<()> {
+        self.predicate_lock_manager.check_write(txn_id, targets)
+    }
+    // the version read was inserted or deleted by a transaction which the snapshot can not see
+    pub fn check_read_conflict(
+        &mut self,
+        txn_id: TransactionID,
+        x_min: TransactionID,
+        x_max: TransactionID,
+    ) -> Result<()> {
+        if !self.predicate_lock_manager.is_registered(txn_id) {
+            return Ok(());
+        }
+        if self.is_concurrent_writer(txn_id, x_min) {
+            return self.predicate_lock_manager.add_conflict(txn_id, x_min);
+        }
+        if self.is_concurrent_writer(txn_id, x_max) {
+            return self.predicate_lock_manager.add_conflict(txn_id, x_max);
+        }
+        Ok(())
+    }
+
+    // first-updater-wins: the version to be replaced was already replaced by a committed
+    // transaction, which the snapshot can not see as the scan returned the version. updates do not
+    // link the versions, so read committed can not follow the update to the newest version either
+    pub fn is_write_conflict(&self, txn_id: TransactionID, x_max: TransactionID) -> bool {
+        if x_max == INVALID_TRANSACTION_ID || x_max == txn_id {
+            return false;
+        }
+        self.status(x_max) == TransactionStatus::Committed
+    }
+
+    // visible to every running and future transaction, whatever its isolation level
+    pub fn is_visible_to_all(&self, x_min: TransactionID, x_max: TransactionID) -> bool {
+        if self.status(x_min) != TransactionStatus::Committed {
+            return false;
+        }
+        if x_max != INVALID_TRANSACTION_ID && self.status(x_max) != TransactionStatus::Aborted {
+            return false;
+        }
+        self.active_transactions
+            .iter()
+            .all(|(txn_id, transaction)| x_min < *txn_id && !transaction.snapshot.contains(&x_min))
+    }
+
+    // invisible to every running and future transaction, so that vacuum can remove the version
+    pub fn is_dead_to_all(&self, x_min: TransactionID, x_max: TransactionID) -> bool {
+        if self.status(x_min) == TransactionStatus::Aborted {
+            return true;
+        }
+        if x_max == INVALID_TRANSACTION_ID || self.status(x_max) != TransactionStatus::Committed {
+            return false;
+        }
+        self.active_transactions
+            .iter()
+            .all(|(txn_id, transaction)| x_max < *txn_id && !transaction.snapshot.contains(&x_max))
+    }
+
+    // unlike is_visible, this ignores snapshots: a unique key conflicts with the latest version of a row
+    pub fn duplicate_status(
+        &self,
+        txn_id: TransactionID,
+        x_min: TransactionID,
+        x_max: TransactionID,
+    ) -> DuplicateStatus {
+        if x_min != txn_id {
+            match self.statuses.get(&x_min) {
+                Some(TransactionStatus::Committed) => {}
+                Some(TransactionStatus::Running) => return DuplicateStatus::InProgress,
+                _ => return DuplicateStatus::Dead,
+            }
+        }
+        if x_max == txn_id {
+            return DuplicateStatus::Dead;
+        }
+        match self.statuses.get(&x_max) {
+            Some(TransactionStatus::Committed) => DuplicateStatus::Dead,
+            Some(TransactionStatus::Running) => DuplicateStatus::InProgress,
+            _ => DuplicateStatus::Live,
+        }
+    }
+
     fn is_visible_with_read_committed(
         &self,
         txn_id: TransactionID,
@@ -193,6 +436,39 @@ impl TransactionManager {
         false
     }
 
+    fn transaction(&self, txn_id: TransactionID) -> Result<&Transaction> {
+        self.active_transactions
+            .get(&txn_id)
+            .ok_or_else(|| anyhow::anyhow!("transaction {} not found", txn_id.0))
+    }
+    fn transaction_mut(&mut self, txn_id: TransactionID) -> Result<&mut Transaction> {
+        self.active_transactions
+            .get_mut(&txn_id)
+            .ok_or_else(|| anyhow::anyhow!("transaction {} not found", txn_id.0))
+    }
+    fn savepoint_index(transaction: &Transaction, name: &str) -> Result<usize> {
+        transaction
+            .savepoints
+            .iter()
+            .rposition(|savepoint| savepoint.name == name)
+            .ok_or_else(|| anyhow::anyhow!("savepoint {} does not exist", name))
+    }
+
+    fn is_concurrent_writer(&self, txn_id: TransactionID, writer: TransactionID) -> bool {
+        if writer == INVALID_TRANSACTION_ID || writer == txn_id {
+            return false;
+        }
+        if self.status(writer) == TransactionStatus::Aborted {
+            return false;
+        }
+        match self.active_transactions.get(&txn_id) {
+            Some(transaction) => {
+                !self.is_valid_txn_id_with_snapshot(txn_id, writer, &transaction.snapshot)
+            }
+            None => false,
+        }
+    }
+
     fn is_valid_txn_id_with_snapshot(
         &self,
         txn_id: TransactionID,
@@ -218,71 +494,6 @@ impl TransactionManager {
     }
 }
 
-struct TransactionLog {
-    txn_id: TransactionID,
-    status: TransactionStatus,
-}
-impl TransactionLog {
-    pub fn new(txn_id: TransactionID, status: TransactionStatus) -> Self {
-        Self { txn_id, status }
-    }
-    pub fn serialize(&self) -> Box<[u8]> {
-        let mut buffer = self.txn_id.0.to_le_bytes().to_vec();
-        match self.status {
-            TransactionStatus::Committed => buffer.push(0),
-            TransactionStatus::Aborted => buffer.push(1),
-            _ => unreachable!(),
-        }
-        buffer.into()
-    }
-}
-impl From<&[u8]> for TransactionLog {
-    fn from(bytes: &[u8]) -> Self {
-        assert!(bytes.len() == 5);
-        let mut txn_id_buffer = [0u8; 4];
-        txn_id_buffer.copy_from_slice(&bytes[0..4]);
-        let txn_id = TransactionID(u32::from_le_bytes(txn_id_buffer));
-        let status = match bytes[4] {
-            0 => TransactionStatus::Committed,
-            1 => TransactionStatus::Aborted,
-            _ => unreachable!(),
-        };
-        TransactionLog { txn_id, status }
-    }
-}
-
-struct TransactionLogManager {
-    log_file: File,
-}
-impl TransactionLogManager {
-    pub fn new(log_file_path: &str) -> Result<Self> {
-        let log_file = OpenOptions::new()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(log_file_path)?;
-        Ok(Self { log_file })
-    }
-    pub fn read(&mut self) -> Result<Vec<TransactionLog>> {
-        let mut buffer = vec![];
-        self.log_file.read_to_end(&mut buffer)?;
-        let mut logs = vec![];
-        let mut offset = 0;
-        while offset < buffer.len() {
-            let log = TransactionLog::from(&buffer[offset..(offset + 5)]);
-            logs.push(log);
-            offset += 5;
-        }
-        Ok(logs)
-    }
-    pub fn write(&mut self, txn_log: TransactionLog) -> Result<()> {
-        let buffer = txn_log.serialize();
-        self.log_file.write_all(&buffer)?;
-        self.log_file.sync_all()?;
-        Ok(())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use crate::common::INVALID_TRANSACTION_ID;
@@ -294,19 +505,13 @@ mod tests {
     #[test]
     fn test_transaction_manager_begin() -> Result<()> {
         let dir = tempdir()?;
-        let log_file_path = dir.path().join("transaction.log");
         let wal_log_file_path = dir.path().join("wal.log");
         let lock_manager = Arc::new(RwLock::new(LockManager::default()));
         let wal_log_manager = Arc::new(Mutex::new(LogManager::new(
             wal_log_file_path.to_str().unwrap(),
         )?));
-        let mut transaction_manager = TransactionManager::new(
-            lock_manager,
-            wal_log_manager,
-            log_file_path.to_str().unwrap(),
-            IsolationLevel::ReadCommitted,
-        )?;
-        let txn_id = transaction_manager.begin()?;
+        let mut transaction_manager = TransactionManager::new(lock_manager, wal_log_manager);
+        let txn_id = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
         assert_eq!(txn_id, TransactionID(1));
         assert_eq!(
             transaction_manager.statuses.get(&txn_id),
@@ -314,97 +519,76 @@ mod tests {
         );
         assert!(transaction_manager
             .active_transactions
-            .get(&txn_id)
-            .is_some(),);
+            .contains_key(&txn_id));
         Ok(())
     }
 
     #[test]
     fn test_transaction_manager_commit() -> Result<()> {
         let dir = tempdir()?;
-        let log_file_path = dir.path().join("transaction.log");
         let wal_log_file_path = dir.path().join("wal.log");
         let lock_manager = Arc::new(RwLock::new(LockManager::default()));
         let wal_log_manager = Arc::new(Mutex::new(LogManager::new(
             wal_log_file_path.to_str().unwrap(),
         )?));
-        let mut transaction_manager = TransactionManager::new(
-            lock_manager,
-            wal_log_manager,
-            log_file_path.to_str().unwrap(),
-            IsolationLevel::ReadCommitted,
-        )?;
-        let txn_id = transaction_manager.begin()?;
+        let mut transaction_manager = TransactionManager::new(lock_manager, wal_log_manager);
+        let txn_id = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
         transaction_manager.commit(txn_id)?;
         assert_eq!(
             transaction_manager.statuses.get(&txn_id),
             Some(&TransactionStatus::Committed)
         );
-        assert!(transaction_manager
+        assert!(!transaction_manager
             .active_transactions
-            .get(&txn_id)
-            .is_none());
+            .contains_key(&txn_id));
         Ok(())
     }
 
     #[test]
     fn test_transaction_manager_abort() -> Result<()> {
         let dir = tempdir()?;
-        let log_file_path = dir.path().join("transaction.log");
         let wal_log_file_path = dir.path().join("wal.log");
         let lock_manager = Arc::new(RwLock::new(LockManager::default()));
         let wal_log_manager = Arc::new(Mutex::new(LogManager::new(
             wal_log_file_path.to_str().unwrap(),
         )?));
-        let mut transaction_manager = TransactionManager::new(
-            lock_manager,
-            wal_log_manager,
-            log_file_path.to_str().unwrap(),
-            IsolationLevel::ReadCommitted,
-        )?;
-        let txn_id = transaction_manager.begin()?;
+        let mut transaction_manager = TransactionManager::new(lock_manager, wal_log_manager);
+        let txn_id = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
         transaction_manager.abort(txn_id)?;
         assert_eq!(
             transaction_manager.statuses.get(&txn_id),
             Some(&TransactionStatus::Aborted)
         );
-        assert!(transaction_manager
+        assert!(!transaction_manager
             .active_transactions
-            .get(&txn_id)
-            .is_none());
+            .contains_key(&txn_id));
         Ok(())
     }
 
     #[test]
     fn test_transaction_manager_visible_with_read_committed() -> Result<()> {
         let dir = tempdir()?;
-        let log_file_path = dir.path().join("transaction.log");
         let wal_log_file_path = dir.path().join("wal.log");
         let lock_manager = Arc::new(RwLock::new(LockManager::default()));
         let wal_log_manager = Arc::new(Mutex::new(LogManager::new(
             wal_log_file_path.to_str().unwrap(),
         )?));
-        let mut transaction_manager = TransactionManager::new(
-            lock_manager,
-            wal_log_manager,
-            log_file_path.to_str().unwrap(),
-            IsolationLevel::ReadCommitted,
-        )?;
+        let mut transaction_manager = TransactionManager::new(lock_manager, wal_log_manager);
 
-        let txn_id_1 = transaction_manager.begin()?;
+        let txn_id_1 = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
         // self insert
         assert!(transaction_manager.is_visible(txn_id_1, txn_id_1, INVALID_TRANSACTION_ID));
         // self delete
         assert!(!transaction_manager.is_visible(txn_id_1, txn_id_1, txn_id_1));
 
-        let txn_id_2 = transaction_manager.begin()?;
+        let txn_id_2 = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
         // other insert
         assert!(!transaction_manager.is_visible(txn_id_2, txn_id_1, INVALID_TRANSACTION_ID));
         // other insert after commit
         transaction_manager.commit(txn_id_1)?;
         assert!(transaction_manager.is_visible(txn_id_2, txn_id_1, INVALID_TRANSACTION_ID));
 
-        let txn_id_3 = transaction_manager.begin()?;
+        let txn_id_3 = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
         // other delete
         assert!(transaction_manager.is_visible(txn_id_2, txn_id_1, txn_id_3));
         // other delete after commit
@@ -416,28 +600,22 @@ mod tests {
     #[test]
     fn test_transaction_manager_visible_with_repeatable_read() -> Result<()> {
         let dir = tempdir()?;
-        let log_file_path = dir.path().join("transaction.log");
         let wal_log_file_path = dir.path().join("wal.log");
         let lock_manager = Arc::new(RwLock::new(LockManager::default()));
         let wal_log_manager = Arc::new(Mutex::new(LogManager::new(
             wal_log_file_path.to_str().unwrap(),
         )?));
-        let mut transaction_manager = TransactionManager::new(
-            lock_manager,
-            wal_log_manager,
-            log_file_path.to_str().unwrap(),
-            IsolationLevel::RepeatableRead,
-        )?;
+        let mut transaction_manager = TransactionManager::new(lock_manager, wal_log_manager);
 
-        let before_commit_txn_id = transaction_manager.begin()?;
+        let before_commit_txn_id = transaction_manager.begin(IsolationLevel::RepeatableRead)?;
         transaction_manager.commit(before_commit_txn_id)?;
-        let running_txn_id = transaction_manager.begin()?;
-        let before_abort_txn_id = transaction_manager.begin()?;
+        let running_txn_id = transaction_manager.begin(IsolationLevel::RepeatableRead)?;
+        let before_abort_txn_id = transaction_manager.begin(IsolationLevel::RepeatableRead)?;
         transaction_manager.abort(before_abort_txn_id)?;
 
-        let current_txn_id = transaction_manager.begin()?;
+        let current_txn_id = transaction_manager.begin(IsolationLevel::RepeatableRead)?;
 
-        let after_txn_id = transaction_manager.begin()?;
+        let after_txn_id = transaction_manager.begin(IsolationLevel::RepeatableRead)?;
 
         // self insert
         assert!(transaction_manager.is_visible(
@@ -516,4 +694,46 @@ mod tests {
         ));
         Ok(())
     }
+    #[test]
+    fn test_transaction_manager_isolation_level_per_transaction() -> Result<()> {
+        let dir = tempdir()?;
+        let wal_log_file_path = dir.path().join("wal.log");
+        let lock_manager = Arc::new(RwLock::new(LockManager::default()));
+        let wal_log_manager = Arc::new(Mutex::new(LogManager::new(
+            wal_log_file_path.to_str().unwrap(),
+        )?));
+        let mut transaction_manager = TransactionManager::new(lock_manager, wal_log_manager);
+
+        let read_committed_txn_id = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
+        let repeatable_read_txn_id = transaction_manager.begin(IsolationLevel::RepeatableRead)?;
+        let serializable_txn_id = transaction_manager.begin(IsolationLevel::ReadCommitted)?;
+        transaction_manager
+            .set_isolation_level(serializable_txn_id, IsolationLevel::Serializable)?;
+        assert_eq!(
+            transaction_manager.isolation_level(serializable_txn_id),
+            IsolationLevel::Serializable
+        );
+        let writer_txn_id = transaction_manager.begin(IsolationLevel::RepeatableRead)?;
+        transaction_manager.commit(writer_txn_id)?;
+
+        // each reader sees the committed insert by its own level
+        assert!(transaction_manager.is_visible(
+            read_committed_txn_id,
+            writer_txn_id,
+            INVALID_TRANSACTION_ID
+        ));
+        assert!(!transaction_manager.is_visible(
+            repeatable_read_txn_id,
+            writer_txn_id,
+            INVALID_TRANSACTION_ID
+        ));
+        assert!(!transaction_manager.is_visible(
+            serializable_txn_id,
+            writer_txn_id,
+            INVALID_TRANSACTION_ID
+        ));
+        assert!(transaction_manager.is_write_conflict(read_committed_txn_id, writer_txn_id));
+        assert!(transaction_manager.is_write_conflict(repeatable_read_txn_id, writer_txn_id));
+        Ok(())
+    }
 }
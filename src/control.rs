@@ -0,0 +1,110 @@
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+};
+
+use anyhow::Result;
+
+use crate::common::{sync_parent_dir, LSN};
+
+// bumped whenever the layout of the data or log files changes
+pub const FORMAT_VERSION: u32 = 4;
+
+const CONTROL_FILE_SIZE: usize = 13;
+
+// the control file is replaced as a whole, so that a crash leaves either the old or the new one
+pub struct ControlFile {
+    path: String,
+    pub checkpoint_lsn: Option<LSN>,
+    // cleared while the instance is running, so that it stays unset after a crash
+    pub clean_shutdown: bool,
+}
+impl ControlFile {
+    // a new data directory has nothing to recover
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            checkpoint_lsn: None,
+            clean_shutdown: true,
+        }
+    }
+    pub fn load(path: &str) -> Result<Self> {
+        if fs::metadata(path).is_err() {
+            return Err(anyhow::anyhow!(
+                "control file {} not found, the data directory must be initialized with --init",
+                path
+            ));
+        }
+        let mut buffer = vec![];
+        File::open(path)?.read_to_end(&mut buffer)?;
+        if buffer.len() < 4 {
+            return Err(anyhow::anyhow!("control file {} is broken", path));
+        }
+        let mut format_version = [0u8; 4];
+        format_version.copy_from_slice(&buffer[0..4]);
+        let format_version = u32::from_be_bytes(format_version);
+        if format_version != FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "the data directory has format version {}, but this server supports version {}",
+                format_version,
+                FORMAT_VERSION
+            ));
+        }
+        if buffer.len() < CONTROL_FILE_SIZE {
+            return Err(anyhow::anyhow!("control file {} is broken", path));
+        }
+        let mut lsn = [0u8; 8];
+        lsn.copy_from_slice(&buffer[4..12]);
+        let lsn = u64::from_be_bytes(lsn);
+        Ok(Self {
+            path: path.to_string(),
+            checkpoint_lsn: if lsn == 0 { None } else { Some(LSN(lsn)) },
+            clean_shutdown: buffer[12] == 1,
+        })
+    }
+    pub fn save(&self) -> Result<()> {
+        let mut buffer = vec![];
+        buffer.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        buffer.extend_from_slice(&self.checkpoint_lsn.map_or(0, |lsn| lsn.0).to_be_bytes());
+        buffer.push(self.clean_shutdown as u8);
+        let temp_path = format!("{}.tmp", self.path);
+        let mut temp_file = File::create(&temp_path)?;
+        temp_file.write_all(&buffer)?;
+        temp_file.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+        sync_parent_dir(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_control_file() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("control");
+        let path = path.to_str().unwrap();
+        assert!(ControlFile::load(path).is_err());
+        let mut control_file = ControlFile::new(path);
+        assert_eq!(control_file.checkpoint_lsn, None);
+        assert!(control_file.clean_shutdown);
+        control_file.checkpoint_lsn = Some(LSN(42));
+        control_file.clean_shutdown = false;
+        control_file.save()?;
+        let control_file = ControlFile::load(path)?;
+        assert_eq!(control_file.checkpoint_lsn, Some(LSN(42)));
+        assert!(!control_file.clean_shutdown);
+
+        // written by another version
+        let mut buffer = vec![];
+        File::open(path)?.read_to_end(&mut buffer)?;
+        buffer[0..4].copy_from_slice(&(FORMAT_VERSION + 1).to_be_bytes());
+        fs::write(path, &buffer)?;
+        let e = ControlFile::load(path).err().unwrap();
+        assert!(e.to_string().contains("format version"));
+        Ok(())
+    }
+}
@@ -10,6 +10,8 @@ use crate::common::{PageID, PAGE_SIZE};
 pub struct DiskManager {
     data_file: File,
     next_page_id: PageID,
+    // restored from the log on startup, the buffer pool logs every change of it
+    free_page_ids: Vec<PageID>,
 }
 
 impl DiskManager {
@@ -18,12 +20,14 @@ impl DiskManager {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(data_file_path)?;
         let size = data_file.metadata()?.len();
         let next_page_id = PageID((size / PAGE_SIZE as u64) as u32 + 1);
         Ok(Self {
             data_file,
             next_page_id,
+            free_page_ids: vec![],
         })
     }
     pub fn read_page(&mut self, page_id: PageID, data: &mut [u8]) -> Result<()> {
@@ -41,6 +45,7 @@ impl DiskManager {
         self.data_file.sync_all()?;
         Ok(())
     }
+    // extends the file, free pages are taken by allocate_free_page
     pub fn allocate_page(&mut self) -> Result<PageID> {
         self.data_file.seek(SeekFrom::End(0))?;
         self.data_file.write_all(&[0; PAGE_SIZE])?;
@@ -49,6 +54,19 @@ impl DiskManager {
         self.next_page_id.0 += 1;
         Ok(page_id)
     }
+    // the old contents are kept, so that redo of the records before the reuse still sees a page lsn
+    pub fn allocate_free_page(&mut self) -> Option<PageID> {
+        self.free_page_ids.pop()
+    }
+    pub fn deallocate_page(&mut self, page_id: PageID) {
+        self.free_page_ids.push(page_id);
+    }
+    pub fn free_page_ids(&self) -> Vec<PageID> {
+        self.free_page_ids.clone()
+    }
+    pub fn set_free_page_ids(&mut self, free_page_ids: Vec<PageID>) {
+        self.free_page_ids = free_page_ids;
+    }
 }
 
 #[cfg(test)]
@@ -127,4 +145,28 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_deallocate_page() -> Result<()> {
+        let dir = tempdir()?;
+        let data_file_path = dir.path().join("data");
+        let mut disk_manager = DiskManager::new(data_file_path.to_str().unwrap())?;
+
+        let page_id1 = disk_manager.allocate_page()?;
+        let page_id2 = disk_manager.allocate_page()?;
+        disk_manager.write_page(page_id1, &[1; PAGE_SIZE])?;
+        disk_manager.deallocate_page(page_id1);
+        assert_eq!(disk_manager.free_page_ids(), vec![page_id1]);
+
+        assert_eq!(disk_manager.allocate_free_page(), Some(page_id1));
+        let mut read_data = vec![0; PAGE_SIZE];
+        disk_manager.read_page(page_id1, &mut read_data)?;
+        assert_eq!(read_data, vec![1; PAGE_SIZE]);
+        assert_eq!(disk_manager.allocate_free_page(), None);
+
+        let page_id4 = disk_manager.allocate_page()?;
+        assert_eq!(page_id4.0, page_id2.0 + 1);
+
+        Ok(())
+    }
 }
@@ -3,9 +3,19 @@ use std::sync::{Arc, Mutex, RwLock};
 use anyhow::Result;
 
 use crate::{
-    buffer::BufferPoolManager, catalog::Catalog, common::TransactionID,
-    concurrency::TransactionManager, lock::LockManager, log::LogManager, plan::Plan,
-    table::TableHeap, tuple::Tuple, value::Value,
+    autovacuum::StatisticsCollector,
+    buffer::BufferPoolManager,
+    catalog::{Catalog, Schema},
+    common::{TransactionID, RID},
+    concurrency::{DuplicateStatus, TransactionManager},
+    index::IndexManager,
+    lock::LockManager,
+    log::LogManager,
+    plan::Plan,
+    predicate_lock::PredicateLockTarget,
+    table::TableHeap,
+    tuple::Tuple,
+    value::Value,
 };
 
 use self::{
@@ -13,13 +23,16 @@ use self::{
     delete_executor::DeleteExecutor,
     empty_row_executor::EmptyRowExecutor,
     filter_executor::FilterExecutor,
+    index_only_scan_executor::IndexOnlyScanExecutor,
     index_scan_executor::IndexScanExecutor,
     insert_executor::InsertExecutor,
     limit_executor::LimitExecutor,
+    lock_rows_executor::LockRowsExecutor,
     nested_loop_join_executor::NestedLoopJoinExecutor,
     project_executor::ProjectExecutor,
     seq_scan_executor::SeqScanExecutor,
     sort_executor::SortExecutor,
+    system_view_scan_executor::SystemViewScanExecutor,
     update_executor::UpdateExecutor,
 };
 
@@ -27,13 +40,16 @@ mod aggregate_executor;
 mod delete_executor;
 mod empty_row_executor;
 mod filter_executor;
+mod index_only_scan_executor;
 mod index_scan_executor;
 mod insert_executor;
 mod limit_executor;
+mod lock_rows_executor;
 mod nested_loop_join_executor;
 mod project_executor;
 mod seq_scan_executor;
 mod sort_executor;
+mod system_view_scan_executor;
 mod update_executor;
 
 pub struct ExecutorContext {
@@ -43,6 +59,107 @@ pub struct ExecutorContext {
     pub transaction_manager: Arc<Mutex<TransactionManager>>,
     pub log_manager: Arc<Mutex<LogManager>>,
     pub catalog: Arc<Mutex<Catalog>>,
+    pub statistics: Arc<Mutex<StatisticsCollector>>,
+}
+impl ExecutorContext {
+    // entries of deleted tuples are left in the indexes, readers filter them by visibility
+    pub fn insert_into_indexes(
+        &self,
+        table_heap: &TableHeap,
+        table_name: &str,
+        table_schema: &Schema,
+        values: &[Value],
+        rid: RID,
+    ) -> Result<()> {
+        let mut indexes = self
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
+            .get_indexes_to_maintain(table_name, self.transaction_id)?;
+        for index in indexes.iter_mut() {
+            index.set_schema(table_schema.clone());
+        }
+        for index in indexes {
+            let key = index.key(table_schema, values)?;
+            if index.is_primary {
+                if let Some(i) = key.iter().position(|value| value.is_null_value()) {
+                    return Err(anyhow::anyhow!(
+                        "null value in column {} violates not-null constraint",
+                        index.columns[i]
+                    ));
+                }
+            }
+            let is_unique = index.is_unique;
+            let index_name = index.name.clone();
+            let index_page_id = index.first_page_id;
+            // concurrent index scans did not see the new key
+            self.transaction_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .check_predicate_locks(
+                    self.transaction_id,
+                    &[PredicateLockTarget::Index(index.first_page_id)],
+                )?;
+            let index_manager = IndexManager::new(
+                index,
+                self.buffer_pool_manager.clone(),
+                self.log_manager.clone(),
+                self.transaction_id,
+            );
+            // nulls are distinct from each other
+            if is_unique && !key.iter().any(|value| value.is_null_value()) {
+                // held until the transaction ends, so that no other transaction inserts the same
+                // key between the check and the insert
+                self.lock_manager
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .lock_index_key(
+                        index_page_id,
+                        Tuple::temp_tuple(&key).data.to_vec(),
+                        self.transaction_id,
+                    )?;
+                self.check_unique(table_heap, &index_manager, &index_name, &key)?;
+            }
+            index_manager.insert(&key, rid)?;
+        }
+        Ok(())
+    }
+    fn check_unique(
+        &self,
+        table_heap: &TableHeap,
+        index_manager: &IndexManager,
+        index_name: &str,
+        key: &[Value],
+    ) -> Result<()> {
+        loop {
+            let mut in_progress_rid = None;
+            for rid in index_manager.lookup(key)?.unwrap_or_default() {
+                match table_heap.duplicate_status(rid)? {
+                    DuplicateStatus::Dead => {}
+                    DuplicateStatus::Live => {
+                        return Err(anyhow::anyhow!(
+                            "duplicate key value violates unique constraint {}",
+                            index_name
+                        ));
+                    }
+                    DuplicateStatus::InProgress => {
+                        in_progress_rid = Some(rid);
+                        break;
+                    }
+                }
+            }
+            match in_progress_rid {
+                // the inserting or deleting transaction holds the row lock until it ends. the row
+                // is not locked, this transaction does not change it
+                Some(rid) => self
+                    .lock_manager
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .wait(rid, self.transaction_id)?,
+                None => return Ok(()),
+            }
+        }
+    }
 }
 
 pub struct ExecutorEngine {
@@ -64,7 +181,7 @@ impl ExecutorEngine {
         }
         Ok(result)
     }
-    fn create_executor(&self, plan: &Plan) -> Executor {
+    fn create_executor(&self, plan: &Plan) -> Executor<'_> {
         match plan {
             Plan::SeqScan(seq_scan_plan) => Executor::SeqScan(SeqScanExecutor {
                 plan: seq_scan_plan.clone(),
@@ -79,13 +196,47 @@ impl ExecutorEngine {
                 )
                 .iter(),
             }),
+            Plan::SystemViewScan(system_view_scan_plan) => {
+                Executor::SystemViewScan(SystemViewScanExecutor {
+                    plan: system_view_scan_plan.clone(),
+                    executor_context: &self.context,
+                    rows: vec![],
+                    cursor: 0,
+                })
+            }
             Plan::IndexScan(index_plan) => Executor::IndexScan(IndexScanExecutor {
                 plan: index_plan.clone(),
                 executor_context: &self.context,
-                index_id: index_plan.index_id,
-                rids: None,
-                cursor: 0,
+                table_heap: TableHeap::new(
+                    index_plan.first_page_id,
+                    self.context.buffer_pool_manager.clone(),
+                    self.context.transaction_manager.clone(),
+                    self.context.lock_manager.clone(),
+                    self.context.log_manager.clone(),
+                    self.context.transaction_id,
+                ),
+                index_manager: None,
+                index_iterator: None,
+                range_cursor: 0,
             }),
+            Plan::IndexOnlyScan(index_only_scan_plan) => {
+                Executor::IndexOnlyScan(IndexOnlyScanExecutor {
+                    plan: index_only_scan_plan.clone(),
+                    executor_context: &self.context,
+                    table_heap: TableHeap::new(
+                        index_only_scan_plan.first_page_id,
+                        self.context.buffer_pool_manager.clone(),
+                        self.context.transaction_manager.clone(),
+                        self.context.lock_manager.clone(),
+                        self.context.log_manager.clone(),
+                        self.context.transaction_id,
+                    ),
+                    index_manager: None,
+                    index_iterator: None,
+                    range_cursor: 0,
+                    key_column_indexes: vec![],
+                })
+            }
             Plan::Filter(filter_plan) => Executor::Filter(FilterExecutor {
                 plan: filter_plan.clone(),
                 child: Box::new(self.create_executor(&plan.children()[0])),
@@ -139,6 +290,19 @@ impl ExecutorEngine {
                 result: vec![],
                 cursor: 0,
             }),
+            Plan::LockRows(lock_rows_plan) => Executor::LockRows(LockRowsExecutor {
+                plan: lock_rows_plan.clone(),
+                child: Box::new(self.create_executor(&plan.children()[0])),
+                executor_context: &self.context,
+                table_heap: TableHeap::new(
+                    lock_rows_plan.first_page_id,
+                    self.context.buffer_pool_manager.clone(),
+                    self.context.transaction_manager.clone(),
+                    self.context.lock_manager.clone(),
+                    self.context.log_manager.clone(),
+                    self.context.transaction_id,
+                ),
+            }),
             Plan::Limit(limit_plan) => Executor::Limit(limit_executor::LimitExecutor {
                 plan: limit_plan.clone(),
                 child: Box::new(self.create_executor(&plan.children()[0])),
@@ -202,12 +366,15 @@ impl ExecutorEngine {
 
 pub enum Executor<'a> {
     SeqScan(SeqScanExecutor<'a>),
+    SystemViewScan(SystemViewScanExecutor<'a>),
     IndexScan(IndexScanExecutor<'a>),
+    IndexOnlyScan(IndexOnlyScanExecutor<'a>),
     Filter(FilterExecutor<'a>),
     Project(ProjectExecutor<'a>),
     NestedLoopJoin(NestedLoopJoinExecutor<'a>),
     Aggregate(AggregateExecutor<'a>),
     Sort(SortExecutor<'a>),
+    LockRows(LockRowsExecutor<'a>),
     Limit(LimitExecutor<'a>),
     EmptyRow(EmptyRowExecutor<'a>),
     Insert(InsertExecutor<'a>),
@@ -218,12 +385,15 @@ impl Executor<'_> {
     pub fn init(&mut self) -> Result<()> {
         match self {
             Executor::SeqScan(executor) => executor.init(),
+            Executor::SystemViewScan(executor) => executor.init(),
             Executor::IndexScan(executor) => executor.init(),
+            Executor::IndexOnlyScan(executor) => executor.init(),
             Executor::Filter(executor) => executor.init(),
             Executor::Project(executor) => executor.init(),
             Executor::NestedLoopJoin(executor) => executor.init(),
             Executor::Aggregate(executor) => executor.init(),
             Executor::Sort(executor) => executor.init(),
+            Executor::LockRows(executor) => executor.init(),
             Executor::Limit(executor) => executor.init(),
             Executor::EmptyRow(executor) => executor.init(),
             Executor::Insert(executor) => executor.init(),
@@ -234,12 +404,15 @@ impl Executor<'_> {
     pub(crate) fn next(&mut self) -> Result<Option<Tuple>> {
         match self {
             Executor::SeqScan(executor) => executor.next(),
+            Executor::SystemViewScan(executor) => executor.next(),
             Executor::IndexScan(executor) => executor.next(),
+            Executor::IndexOnlyScan(executor) => executor.next(),
             Executor::Filter(executor) => executor.next(),
             Executor::Project(executor) => executor.next(),
             Executor::NestedLoopJoin(executor) => executor.next(),
             Executor::Aggregate(executor) => executor.next(),
             Executor::Sort(executor) => executor.next(),
+            Executor::LockRows(executor) => executor.next(),
             Executor::Limit(executor) => executor.next(),
             Executor::EmptyRow(executor) => executor.next(),
             Executor::Insert(executor) => executor.next(),
@@ -251,14 +424,28 @@ impl Executor<'_> {
 
 #[cfg(test)]
 mod tests {
+    use std::{
+        sync::{Arc, Barrier, RwLock},
+        thread,
+        time::Duration,
+    };
+
     use anyhow::Result;
 
     use crate::{
+        autovacuum::{autovacuum, AutovacuumConfig},
+        binder::Binder,
         catalog::Schema,
-        common::TransactionID,
+        common::{PageID, TransactionID, INVALID_PAGE_ID, INVALID_TRANSACTION_ID},
+        concurrency::IsolationLevel,
+        index::{IndexManager, IndexRange},
         instance::Instance,
         lexer::tokenize,
-        parser::Parser,
+        optimizer::Optimizer,
+        page::table_page::TABLE_PAGE_PAGE_TYPE,
+        parser::{CreateIndexStatementAST, Parser, StatementAST},
+        plan::{Plan, Planner},
+        table::TableHeap,
         test_helpers::setup_test_database,
         value::{boolean::BooleanValue, integer::IntegerValue, varchar::VarcharValue, Value},
     };
@@ -269,8 +456,22 @@ mod tests {
         txn_id: TransactionID,
     ) -> Result<(Vec<Vec<Value>>, Schema)> {
         let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
-        let statement = parser.parse()?;
-        instance.execute(&statement, txn_id)
+        match parser.parse()? {
+            StatementAST::CreateTable(statement) => {
+                instance.create_table(&statement, txn_id)?;
+                Ok((vec![], Schema { columns: vec![] }))
+            }
+            StatementAST::CreateIndex(statement) => {
+                instance.create_index(&statement, txn_id)?;
+                Ok((vec![], Schema { columns: vec![] }))
+            }
+            StatementAST::DropIndex(statement) => {
+                instance.drop_index(&statement, txn_id)?;
+                Ok((vec![], Schema { columns: vec![] }))
+            }
+            StatementAST::ShowIndexes(statement) => instance.show_indexes(&statement, txn_id),
+            statement => instance.execute(&statement, txn_id),
+        }
     }
 
     #[test]
@@ -541,4 +742,1645 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_index_maintenance() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        instance.create_index(
+            &CreateIndexStatementAST {
+                index_name: "t1_c1_index".to_string(),
+                table_name: "t1".to_string(),
+                column_names: vec!["c1".to_string()],
+                unique: false,
+            },
+            txn_id,
+        )?;
+        execute("INSERT INTO t1 VALUES (1, 'foo')", &instance, txn_id)?;
+        execute("INSERT INTO t1 VALUES (2, 'bar')", &instance, txn_id)?;
+        execute("INSERT INTO t1 VALUES (3, 'baz')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        let old_txn_id = instance.begin(None)?;
+        let txn_id = instance.begin(None)?;
+        execute("UPDATE t1 SET c1 = 10 WHERE c1 = 1", &instance, txn_id)?;
+        execute("UPDATE t1 SET c2 = 'qux' WHERE c1 = 2", &instance, txn_id)?;
+        execute("DELETE FROM t1 WHERE c1 = 3", &instance, txn_id)?;
+
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 1", &instance, txn_id)?;
+        assert!(rows.is_empty());
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 10", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(IntegerValue(10)),
+                Value::Varchar(VarcharValue("foo".to_string()))
+            ]]
+        );
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 2", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(IntegerValue(2)),
+                Value::Varchar(VarcharValue("qux".to_string()))
+            ]]
+        );
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 3", &instance, txn_id)?;
+        assert!(rows.is_empty());
+        instance.commit(txn_id)?;
+
+        // a transaction started before the changes still sees the old versions
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 1", &instance, old_txn_id)?;
+        assert_eq!(rows.len(), 1);
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 10", &instance, old_txn_id)?;
+        assert!(rows.is_empty());
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 2", &instance, old_txn_id)?;
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(IntegerValue(2)),
+                Value::Varchar(VarcharValue("bar".to_string()))
+            ]]
+        );
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 3", &instance, old_txn_id)?;
+        assert_eq!(rows.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_index_on_existing_rows() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        for i in 0..500 {
+            let sql = format!("INSERT INTO t1 VALUES ({}, 'foo')", i % 100);
+            execute(&sql, &instance, txn_id)?;
+        }
+        execute("DELETE FROM t1 WHERE c1 = 1", &instance, txn_id)?;
+        let create_index_statement = CreateIndexStatementAST {
+            index_name: "t1_c1_index".to_string(),
+            table_name: "t1".to_string(),
+            column_names: vec!["c1".to_string()],
+            unique: false,
+        };
+        instance.create_index(&create_index_statement, txn_id)?;
+
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 42", &instance, txn_id)?;
+        assert_eq!(rows.len(), 5);
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 1", &instance, txn_id)?;
+        assert!(rows.is_empty());
+
+        // a failed build does not register the index
+        let create_index_statement = CreateIndexStatementAST {
+            index_name: "t1_c3_index".to_string(),
+            table_name: "t1".to_string(),
+            column_names: vec!["c3".to_string()],
+            unique: false,
+        };
+        assert!(instance
+            .create_index(&create_index_statement, txn_id)
+            .is_err());
+        let indexes = instance
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .get_indexes_by_table_name("t1", txn_id)?;
+        assert_eq!(indexes.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_unique_index_fails_half_way() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        // the duplicate is the largest key, so the build fails after the other leaf pages are built
+        for i in (0..500).chain([499]) {
+            let sql = format!("INSERT INTO t1 VALUES ({}, '{:0>200}')", i, i);
+            execute(&sql, &instance, txn_id)?;
+        }
+        let new_page_id = || -> Result<PageID> {
+            let mut buffer_pool_manager = instance
+                .buffer_pool_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+            let page = buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+            let page_id = page
+                .read()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .page_id();
+            buffer_pool_manager.unpin_page(page_id, false)?;
+            Ok(page_id)
+        };
+        let first_page_id = new_page_id()?;
+        assert!(execute(
+            "CREATE UNIQUE INDEX t1_c2_index ON t1 (c2)",
+            &instance,
+            txn_id
+        )
+        .is_err());
+        let (rows, _) = execute("SHOW INDEXES FROM t1", &instance, txn_id)?;
+        assert!(rows.is_empty());
+
+        // the pages of the failed build are reused, the new tree needs at least as many pages
+        execute("CREATE INDEX t1_c2_index ON t1 (c2)", &instance, txn_id)?;
+        let index = instance
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .get_indexes_by_table_name("t1", txn_id)?
+            .remove(0);
+        let page_ids = IndexManager::new(
+            index,
+            instance.buffer_pool_manager.clone(),
+            instance.log_manager.clone(),
+            txn_id,
+        )
+        .page_ids()?;
+        assert!(page_ids.len() > 2);
+        assert_eq!(
+            new_page_id()?,
+            PageID(first_page_id.0 + page_ids.len() as u32 + 1)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_index_waits_for_writers() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let writer_txn_id = instance.begin(None)?;
+        execute("INSERT INTO t1 VALUES (1, 'foo')", &instance, writer_txn_id)?;
+        // began before the index exists, so its snapshot does not see the index
+        let old_txn_id = instance.begin(None)?;
+        execute("SELECT * FROM t1", &instance, old_txn_id)?;
+
+        let handle = {
+            let instance = instance.clone();
+            thread::spawn(move || -> Result<()> {
+                let txn_id = instance.begin(None)?;
+                execute(
+                    "CREATE UNIQUE INDEX t1_c1_index ON t1 (c1)",
+                    &instance,
+                    txn_id,
+                )?;
+                instance.commit(txn_id)
+            })
+        };
+        thread::sleep(Duration::from_millis(100));
+        assert!(!handle.is_finished());
+        instance.commit(writer_txn_id)?;
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+
+        // the writer which did not wait maintains the index too
+        execute("INSERT INTO t1 VALUES (2, 'bar')", &instance, old_txn_id)?;
+        instance.commit(old_txn_id)?;
+        let txn_id = instance.begin(None)?;
+        assert!(execute("INSERT INTO t1 VALUES (1, 'baz')", &instance, txn_id).is_err());
+        let catalog = instance
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?;
+        let mut index = catalog.get_indexes_by_table_name("t1", txn_id)?.remove(0);
+        index.set_schema(catalog.get_schema_by_table_name("t1", txn_id)?);
+        drop(catalog);
+        let index_manager = IndexManager::new(
+            index,
+            instance.buffer_pool_manager.clone(),
+            instance.log_manager.clone(),
+            txn_id,
+        );
+        for i in [1, 2] {
+            assert_eq!(
+                index_manager
+                    .lookup(&[Value::Integer(IntegerValue(i))])?
+                    .map(|rids| rids.len()),
+                Some(1)
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_range_scan() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        let create_index_statement = CreateIndexStatementAST {
+            index_name: "t1_c1_index".to_string(),
+            table_name: "t1".to_string(),
+            column_names: vec!["c1".to_string()],
+            unique: false,
+        };
+        instance.create_index(&create_index_statement, txn_id)?;
+        for i in [5, 3, 8, 1, 9, 2, 7, 4, 6, 0] {
+            let sql = format!("INSERT INTO t1 VALUES ({}, 'foo')", i);
+            execute(&sql, &instance, txn_id)?;
+        }
+
+        let select = |condition: &str| -> Result<Vec<i64>> {
+            let sql = format!("SELECT c1 FROM t1 WHERE {}", condition);
+            let (rows, _) = execute(&sql, &instance, txn_id)?;
+            Ok(rows
+                .iter()
+                .filter_map(|row| match row[0] {
+                    Value::Integer(IntegerValue(c1)) => Some(c1),
+                    _ => None,
+                })
+                .collect())
+        };
+        assert_eq!(select("c1 < 3")?, vec![0, 1, 2]);
+        assert_eq!(select("c1 <= 3")?, vec![0, 1, 2, 3]);
+        assert_eq!(select("c1 > 7")?, vec![8, 9]);
+        assert_eq!(select("c1 >= 7")?, vec![7, 8, 9]);
+        assert_eq!(select("c1 <> 4")?, vec![0, 1, 2, 3, 5, 6, 7, 8, 9]);
+        assert_eq!(select("c1 >= 3 AND c1 < 6")?, vec![3, 4, 5]);
+        assert_eq!(select("2 < c1 AND 5 >= c1")?, vec![3, 4, 5]);
+        assert_eq!(select("c1 > 6 AND c1 < 3")?, Vec::<i64>::new());
+        assert_eq!(select("c1 > 6 AND c2 = 'foo'")?, vec![7, 8, 9]);
+        assert_eq!(select("c1 > 6 AND c2 = 'bar'")?, Vec::<i64>::new());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_composite_index_scan() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        let create_index_statement = CreateIndexStatementAST {
+            index_name: "t1_c1_c2_index".to_string(),
+            table_name: "t1".to_string(),
+            column_names: vec!["c1".to_string(), "c2".to_string()],
+            unique: false,
+        };
+        instance.create_index(&create_index_statement, txn_id)?;
+        for (c1, c2) in [(2, "b"), (1, "c"), (2, "a"), (1, "a"), (3, "a"), (1, "b")] {
+            let sql = format!("INSERT INTO t1 VALUES ({}, '{}')", c1, c2);
+            execute(&sql, &instance, txn_id)?;
+        }
+
+        let select = |condition: &str| -> Result<Vec<String>> {
+            let sql = format!("SELECT c1, c2 FROM t1 WHERE {}", condition);
+            let (rows, _) = execute(&sql, &instance, txn_id)?;
+            Ok(rows
+                .iter()
+                .map(|row| format!("{}{}", row[0], row[1]))
+                .collect())
+        };
+        assert_eq!(select("c1 = 1 AND c2 = 'b'")?, vec!["1b"]);
+        assert_eq!(select("c1 = 1")?, vec!["1a", "1b", "1c"]);
+        assert_eq!(select("c1 = 1 AND c2 > 'a'")?, vec!["1b", "1c"]);
+        assert_eq!(select("c2 <= 'b' AND c1 = 2")?, vec!["2a", "2b"]);
+        assert_eq!(select("c1 >= 2")?, vec!["2a", "2b", "3a"]);
+        assert_eq!(select("c1 = 1 AND c2 <> 'b'")?, vec!["1a", "1c"]);
+        assert_eq!(select("c2 = 'a'")?, vec!["2a", "1a", "3a"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_constraints() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, email VARCHAR UNIQUE, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        execute(
+            "INSERT INTO users VALUES (1, 'a', 'foo')",
+            &instance,
+            txn_id,
+        )?;
+        execute(
+            "INSERT INTO users VALUES (2, NULL, 'bar')",
+            &instance,
+            txn_id,
+        )?;
+        // nulls do not conflict in a unique index
+        execute(
+            "INSERT INTO users VALUES (3, NULL, 'baz')",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+
+        let assert_error = |sql: &str, message: &str| -> Result<()> {
+            let txn_id = instance.begin(None)?;
+            let result = execute(sql, &instance, txn_id);
+            instance.rollback(txn_id)?;
+            match result {
+                Err(e) => assert!(e.to_string().contains(message), "{}", e),
+                Ok(_) => panic!("{} should fail", sql),
+            }
+            Ok(())
+        };
+        assert_error(
+            "INSERT INTO users VALUES (1, 'b', 'qux')",
+            "violates unique constraint users_pkey",
+        )?;
+        assert_error(
+            "INSERT INTO users VALUES (4, 'a', 'qux')",
+            "violates unique constraint users_email_key",
+        )?;
+        assert_error(
+            "INSERT INTO users VALUES (NULL, 'b', 'qux')",
+            "violates not-null constraint",
+        )?;
+        assert_error(
+            "UPDATE users SET id = 1 WHERE id = 2",
+            "violates unique constraint users_pkey",
+        )?;
+
+        // the old versions of updated or deleted rows do not conflict
+        let txn_id = instance.begin(None)?;
+        execute(
+            "UPDATE users SET name = 'qux' WHERE id = 1",
+            &instance,
+            txn_id,
+        )?;
+        execute("DELETE FROM users WHERE id = 2", &instance, txn_id)?;
+        execute(
+            "INSERT INTO users VALUES (2, 'b', 'bar')",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SELECT id, email, name FROM users", &instance, txn_id)?;
+        let mut rows = rows
+            .iter()
+            .map(|row| format!("{} {} {}", row[0], row[1], row[2]))
+            .collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(rows, vec!["1 a qux", "2 b bar", "3 NULL baz"]);
+        instance.commit(txn_id)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_unique_index() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute("INSERT INTO t1 VALUES (1, 'foo')", &instance, txn_id)?;
+        execute("INSERT INTO t1 VALUES (2, 'foo')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        let txn_id = instance.begin(None)?;
+        assert!(execute(
+            "CREATE UNIQUE INDEX t1_c2_index ON t1 (c2)",
+            &instance,
+            txn_id
+        )
+        .is_err());
+        execute(
+            "CREATE UNIQUE INDEX t1_c1_index ON t1 (c1)",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+
+        let txn_id = instance.begin(None)?;
+        assert!(execute("INSERT INTO t1 VALUES (2, 'bar')", &instance, txn_id).is_err());
+        instance.rollback(txn_id)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_waits_for_uncommitted_insert() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE users (id INT PRIMARY KEY)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        for (id, commit) in [(1, false), (2, true)] {
+            let sql = format!("INSERT INTO users VALUES ({})", id);
+            let txn_id = instance.begin(None)?;
+            execute(&sql, &instance, txn_id)?;
+
+            let instance_clone = instance.clone();
+            let handle = thread::spawn(move || -> Result<()> {
+                let txn_id = instance_clone.begin(None)?;
+                let result = execute(&sql, &instance_clone, txn_id);
+                if result.is_ok() {
+                    instance_clone.commit(txn_id)?;
+                } else {
+                    instance_clone.rollback(txn_id)?;
+                }
+                result.map(|_| ())
+            });
+            thread::sleep(Duration::from_millis(200));
+            assert!(!handle.is_finished());
+
+            if commit {
+                instance.commit(txn_id)?;
+            } else {
+                instance.rollback(txn_id)?;
+            }
+            let result = handle.join().map_err(|_| anyhow::anyhow!("thread error"))?;
+            // the waiting insert fails only if the conflicting one has committed
+            assert_eq!(result.is_err(), commit);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_wait_does_not_lock_the_duplicate() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE users (id INT PRIMARY KEY)", &instance, txn_id)?;
+        execute("INSERT INTO users VALUES (1)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        // the inserts of the same key wait for each other on the key, a delete on the row
+        let txn_id = instance.begin(None)?;
+        execute("DELETE FROM users WHERE id = 1", &instance, txn_id)?;
+        let instance_clone = instance.clone();
+        let handle = thread::spawn(move || -> Result<TransactionID> {
+            let txn_id = instance_clone.begin(None)?;
+            let result = execute("INSERT INTO users VALUES (1)", &instance_clone, txn_id);
+            assert!(result.is_err());
+            Ok(txn_id)
+        });
+        thread::sleep(Duration::from_millis(200));
+        instance.rollback(txn_id)?;
+        let waiter_txn_id = handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+
+        // the waiter is still in progress, but does not block the writers of the row
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute(
+            "SELECT * FROM users WHERE id = 1 FOR UPDATE NOWAIT",
+            &instance,
+            txn_id,
+        )?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(1))]]);
+        instance.commit(txn_id)?;
+        instance.rollback(waiter_txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_sessions_insert_into_index() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+
+        let handles = (0..4)
+            .map(|t| {
+                let instance = instance.clone();
+                thread::spawn(move || -> Result<()> {
+                    for i in (t..400).step_by(4) {
+                        let txn_id = instance.begin(None)?;
+                        let sql = format!("INSERT INTO users VALUES ({}, '{:0>100}')", i, i);
+                        execute(&sql, &instance, txn_id)?;
+                        let sql = format!("SELECT id FROM users WHERE id = {}", i);
+                        let (rows, _) = execute(&sql, &instance, txn_id)?;
+                        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(i))]]);
+                        instance.commit(txn_id)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("thread error"))??;
+        }
+
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SELECT COUNT(id) FROM users", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(400))]]);
+        let (rows, _) = execute("SELECT id FROM users WHERE id >= 390", &instance, txn_id)?;
+        assert_eq!(rows.len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_sessions_insert_same_key() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+
+        // every session inserts every key at the same time, only one of them may succeed
+        let barrier = Arc::new(Barrier::new(8));
+        let handles = (0..8)
+            .map(|t| {
+                let instance = instance.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || -> Result<(usize, Vec<String>)> {
+                    let mut inserted = 0;
+                    let mut errors = vec![];
+                    for i in 0..200 {
+                        barrier.wait();
+                        let txn_id = instance.begin(None)?;
+                        let sql = format!("INSERT INTO users VALUES ({}, 'session{}')", i, t);
+                        match execute(&sql, &instance, txn_id) {
+                            Ok(_) => {
+                                thread::sleep(Duration::from_millis(1));
+                                instance.commit(txn_id)?;
+                                inserted += 1;
+                            }
+                            Err(e) => {
+                                instance.rollback(txn_id)?;
+                                if !e.to_string().contains("duplicate key value") {
+                                    errors.push(e.to_string());
+                                }
+                            }
+                        }
+                    }
+                    Ok((inserted, errors))
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut inserted = 0;
+        for handle in handles {
+            let (count, errors) = handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("thread error"))??;
+            assert_eq!(errors, Vec::<String>::new());
+            inserted += count;
+        }
+        assert_eq!(inserted, 200);
+
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SELECT COUNT(id) FROM users", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(200))]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deadlock() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE t1 (c1 INT, c2 VARCHAR)", &instance, txn_id)?;
+        execute("INSERT INTO t1 VALUES (1, 'a')", &instance, txn_id)?;
+        execute("INSERT INTO t1 VALUES (2, 'b')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        // the transactions update the rows in opposite order
+        let txn_id1 = instance.begin(None)?;
+        let txn_id2 = instance.begin(None)?;
+        execute("UPDATE t1 SET c2 = 'x' WHERE c1 = 1", &instance, txn_id1)?;
+        execute("UPDATE t1 SET c2 = 'y' WHERE c1 = 2", &instance, txn_id2)?;
+        let instance_clone = instance.clone();
+        let handle = thread::spawn(move || -> Result<()> {
+            execute(
+                "UPDATE t1 SET c2 = 'x' WHERE c1 = 2",
+                &instance_clone,
+                txn_id1,
+            )?;
+            instance_clone.commit(txn_id1)
+        });
+        thread::sleep(Duration::from_millis(200));
+        assert!(!handle.is_finished());
+        let result = execute("UPDATE t1 SET c2 = 'y' WHERE c1 = 1", &instance, txn_id2);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("deadlock detected"));
+        instance.rollback(txn_id2)?;
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SELECT c1, c2 FROM t1 ORDER BY c1 ASC", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::Integer(IntegerValue(1)),
+                    Value::Varchar(VarcharValue("x".to_string()))
+                ],
+                vec![
+                    Value::Integer(IntegerValue(2)),
+                    Value::Varchar(VarcharValue("x".to_string()))
+                ],
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_first_updater_wins() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE counters (id INT, count INT)",
+            &instance,
+            txn_id,
+        )?;
+        execute("INSERT INTO counters VALUES (1, 0)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        // the second updater fails if the first one commits, and goes on if it aborts. read
+        // committed can not follow the update to the new version, so it fails too
+        for (isolation_level, first_commits, expected_count) in [
+            (IsolationLevel::RepeatableRead, true, 1),
+            (IsolationLevel::RepeatableRead, false, 11),
+            (IsolationLevel::ReadCommitted, true, 12),
+            (IsolationLevel::ReadCommitted, false, 22),
+        ] {
+            let txn_id1 = instance.begin(None)?;
+            let txn_id2 = instance.begin_with_isolation_level(isolation_level)?;
+            execute(
+                "UPDATE counters SET count = count + 1 WHERE id = 1",
+                &instance,
+                txn_id1,
+            )?;
+            let instance_clone = instance.clone();
+            let handle = thread::spawn(move || -> Result<()> {
+                let sql = "UPDATE counters SET count = count + 10 WHERE id = 1";
+                let result = execute(sql, &instance_clone, txn_id2);
+                if result.is_ok() {
+                    instance_clone.commit(txn_id2)?;
+                } else {
+                    instance_clone.rollback(txn_id2)?;
+                }
+                result.map(|_| ())
+            });
+            thread::sleep(Duration::from_millis(200));
+            assert!(!handle.is_finished());
+            if first_commits {
+                instance.commit(txn_id1)?;
+            } else {
+                instance.rollback(txn_id1)?;
+            }
+            let result = handle.join().map_err(|_| anyhow::anyhow!("thread error"))?;
+            if first_commits {
+                assert!(result
+                    .unwrap_err()
+                    .to_string()
+                    .contains("could not serialize access"));
+            } else {
+                result?;
+            }
+
+            let txn_id = instance.begin(None)?;
+            let (rows, _) = execute("SELECT count FROM counters", &instance, txn_id)?;
+            assert_eq!(
+                rows,
+                vec![vec![Value::Integer(IntegerValue(expected_count))]]
+            );
+            instance.commit(txn_id)?;
+        }
+
+        // a transaction which started after the commit sees the new version and can update it
+        let txn_id = instance.begin(None)?;
+        execute("DELETE FROM counters WHERE id = 1", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_isolation_level_per_transaction() -> Result<()> {
+        let instance = setup_test_database()?;
+        let read_committed_txn_id =
+            instance.begin_with_isolation_level(IsolationLevel::ReadCommitted)?;
+        let repeatable_read_txn_id =
+            instance.begin_with_isolation_level(IsolationLevel::RepeatableRead)?;
+        let txn_id = instance.begin(None)?;
+        execute("INSERT INTO t1 VALUES (1, 'foo')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        // the level of the reading transaction decides the visibility
+        let count = |txn_id| -> Result<usize> {
+            Ok(execute("SELECT * FROM t1", &instance, txn_id)?.0.len())
+        };
+        assert_eq!(count(read_committed_txn_id)?, 1);
+        assert_eq!(count(repeatable_read_txn_id)?, 0);
+        instance.commit(read_committed_txn_id)?;
+        instance.commit(repeatable_read_txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_savepoint() -> Result<()> {
+        let instance = setup_test_database()?;
+        let c1s = |txn_id| -> Result<Vec<Vec<Value>>> {
+            Ok(execute("SELECT c1 FROM t1 ORDER BY c1 ASC", &instance, txn_id)?.0)
+        };
+        let rows = |c1s: &[i64]| -> Vec<Vec<Value>> {
+            c1s.iter()
+                .map(|c1| vec![Value::Integer(IntegerValue(*c1))])
+                .collect()
+        };
+        let txn_id = instance.begin(None)?;
+        execute("INSERT INTO t1 VALUES (1, 'foo')", &instance, txn_id)?;
+        instance.savepoint(txn_id, "sp1")?;
+        execute("UPDATE t1 SET c1 = 2 WHERE c1 = 1", &instance, txn_id)?;
+        execute("INSERT INTO t1 VALUES (3, 'bar')", &instance, txn_id)?;
+        instance.savepoint(txn_id, "sp2")?;
+        execute("DELETE FROM t1", &instance, txn_id)?;
+        assert_eq!(c1s(txn_id)?, rows(&[]));
+        instance.rollback_to_savepoint(txn_id, "sp2")?;
+        assert_eq!(c1s(txn_id)?, rows(&[2, 3]));
+
+        // the savepoint is kept, the later ones are destroyed
+        instance.rollback_to_savepoint(txn_id, "sp1")?;
+        assert_eq!(c1s(txn_id)?, rows(&[1]));
+        assert!(instance.rollback_to_savepoint(txn_id, "sp2").is_err());
+        execute("INSERT INTO t1 VALUES (4, 'baz')", &instance, txn_id)?;
+        instance.rollback_to_savepoint(txn_id, "sp1")?;
+        assert_eq!(c1s(txn_id)?, rows(&[1]));
+
+        // the changes after a released savepoint belong to the outer one
+        instance.savepoint(txn_id, "sp2")?;
+        execute("INSERT INTO t1 VALUES (5, 'qux')", &instance, txn_id)?;
+        instance.release_savepoint(txn_id, "sp2")?;
+        assert!(instance.rollback_to_savepoint(txn_id, "sp2").is_err());
+        assert_eq!(c1s(txn_id)?, rows(&[1, 5]));
+        instance.rollback_to_savepoint(txn_id, "sp1")?;
+        execute("INSERT INTO t1 VALUES (6, 'quux')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        let txn_id = instance.begin(None)?;
+        assert_eq!(c1s(txn_id)?, rows(&[1, 6]));
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_statement_level_rollback() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        for id in [3, 1, 2] {
+            let sql = format!("INSERT INTO users VALUES ({}, 'foo')", id);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let ids = |txn_id| -> Result<Vec<Vec<Value>>> {
+            Ok(execute("SELECT id FROM users ORDER BY id ASC", &instance, txn_id)?.0)
+        };
+        let rows = |ids: &[i64]| -> Vec<Vec<Value>> {
+            ids.iter()
+                .map(|id| vec![Value::Integer(IntegerValue(*id))])
+                .collect()
+        };
+
+        // 3 is updated to 4 before 1 conflicts with 2
+        let txn_id = instance.begin(None)?;
+        execute("DELETE FROM users WHERE id = 3", &instance, txn_id)?;
+        execute("INSERT INTO users VALUES (3, 'bar')", &instance, txn_id)?;
+        let undo_position = instance.undo_position(txn_id)?;
+        assert!(execute("UPDATE users SET id = id + 1", &instance, txn_id).is_err());
+        instance.rollback_to(txn_id, undo_position)?;
+        assert_eq!(ids(txn_id)?, rows(&[1, 2, 3]));
+        execute("INSERT INTO users VALUES (4, 'baz')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        let txn_id = instance.begin(None)?;
+        assert_eq!(ids(txn_id)?, rows(&[1, 2, 3, 4]));
+        let (rows, _) = execute("SELECT name FROM users WHERE id = 3", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![vec![Value::Varchar(VarcharValue("bar".to_string()))]]
+        );
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_serializable_write_skew() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE doctors (id INT, on_call INT)",
+            &instance,
+            txn_id,
+        )?;
+        execute("INSERT INTO doctors VALUES (1, 1)", &instance, txn_id)?;
+        execute("INSERT INTO doctors VALUES (2, 1)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let on_call_count = |txn_id| -> Result<Vec<Vec<Value>>> {
+            let sql = "SELECT COUNT(*) FROM doctors WHERE on_call = 1";
+            Ok(execute(sql, &instance, txn_id)?.0)
+        };
+        let count = |count| vec![vec![Value::Integer(IntegerValue(count))]];
+
+        // each one leaves another doctor on call, but both go off call together
+        for (isolation_level, expected_count) in [
+            (IsolationLevel::RepeatableRead, 0),
+            (IsolationLevel::Serializable, 1),
+        ] {
+            let txn_id = instance.begin(None)?;
+            execute("UPDATE doctors SET on_call = 1", &instance, txn_id)?;
+            instance.commit(txn_id)?;
+
+            let txn_id1 = instance.begin_with_isolation_level(isolation_level)?;
+            let txn_id2 = instance.begin_with_isolation_level(isolation_level)?;
+            assert_eq!(on_call_count(txn_id1)?, count(2));
+            assert_eq!(on_call_count(txn_id2)?, count(2));
+            execute(
+                "UPDATE doctors SET on_call = 0 WHERE id = 1",
+                &instance,
+                txn_id1,
+            )?;
+            instance.commit(txn_id1)?;
+            let result = execute(
+                "UPDATE doctors SET on_call = 0 WHERE id = 2",
+                &instance,
+                txn_id2,
+            );
+            if isolation_level == IsolationLevel::Serializable {
+                assert!(result.unwrap_err().to_string().contains(
+                    "could not serialize access due to read/write dependencies among transactions"
+                ));
+                instance.rollback(txn_id2)?;
+            } else {
+                result?;
+                instance.commit(txn_id2)?;
+            }
+
+            let txn_id = instance.begin(None)?;
+            assert_eq!(on_call_count(txn_id)?, count(expected_count));
+            instance.commit(txn_id)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serializable_index_scan() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE shifts (day INT, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        execute(
+            "CREATE INDEX shifts_day_index ON shifts (day)",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+        let serializable = IsolationLevel::Serializable;
+        let sql = "SELECT name FROM shifts WHERE day = 1";
+        let txn_id = instance.begin(None)?;
+        assert!(matches!(
+            scan_plan(&plan(sql, &instance, txn_id)?),
+            Plan::IndexScan(_)
+        ));
+        instance.commit(txn_id)?;
+
+        // phantom: both find no one for the day, and both take it while running
+        let txn_id1 = instance.begin_with_isolation_level(serializable)?;
+        let txn_id2 = instance.begin(None)?;
+        instance.set_isolation_level(txn_id2, serializable)?;
+        assert!(execute(sql, &instance, txn_id1)?.0.is_empty());
+        assert!(execute(sql, &instance, txn_id2)?.0.is_empty());
+        execute("INSERT INTO shifts VALUES (1, 'alice')", &instance, txn_id1)?;
+        assert!(execute("INSERT INTO shifts VALUES (1, 'bob')", &instance, txn_id2).is_err());
+        instance.rollback(txn_id2)?;
+        instance.commit(txn_id1)?;
+
+        // transactions reading and writing different tables do not conflict
+        let txn_id1 = instance.begin_with_isolation_level(serializable)?;
+        let txn_id2 = instance.begin_with_isolation_level(serializable)?;
+        execute(sql, &instance, txn_id1)?;
+        execute("SELECT * FROM t1", &instance, txn_id2)?;
+        execute("INSERT INTO shifts VALUES (2, 'carol')", &instance, txn_id1)?;
+        execute("INSERT INTO t1 VALUES (1, 'foo')", &instance, txn_id2)?;
+        instance.commit(txn_id1)?;
+        instance.commit(txn_id2)?;
+
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SELECT name FROM shifts", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Varchar(VarcharValue("alice".to_string()))],
+                vec![Value::Varchar(VarcharValue("carol".to_string()))],
+            ]
+        );
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_for_update() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE jobs (id INT, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        for id in 1..=3 {
+            let sql = format!("INSERT INTO jobs VALUES ({}, 'job{}')", id, id);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let ids = |rows: Vec<Vec<Value>>| {
+            rows.into_iter()
+                .map(|row| row[0].clone())
+                .collect::<Vec<_>>()
+        };
+
+        let txn_id1 = instance.begin(None)?;
+        let sql = "SELECT name FROM jobs WHERE id = 1 FOR UPDATE";
+        let (rows, _) = execute(sql, &instance, txn_id1)?;
+        assert_eq!(
+            rows,
+            vec![vec![Value::Varchar(VarcharValue("job1".to_string()))]]
+        );
+
+        let txn_id2 = instance.begin(None)?;
+        let result = execute("SELECT * FROM jobs FOR UPDATE NOWAIT", &instance, txn_id2);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("could not obtain lock"));
+        instance.rollback(txn_id2)?;
+
+        // workers take the first job which is not taken by others
+        let sql = "SELECT id FROM jobs ORDER BY id ASC LIMIT 1 FOR UPDATE SKIP LOCKED";
+        let txn_id3 = instance.begin(None)?;
+        let (rows, _) = execute(sql, &instance, txn_id3)?;
+        assert_eq!(ids(rows), vec![Value::Integer(IntegerValue(2))]);
+        let txn_id4 = instance.begin(None)?;
+        let (rows, _) = execute(sql, &instance, txn_id4)?;
+        assert_eq!(ids(rows), vec![Value::Integer(IntegerValue(3))]);
+        let txn_id5 = instance.begin(None)?;
+        let (rows, _) = execute(sql, &instance, txn_id5)?;
+        assert!(rows.is_empty());
+        instance.rollback(txn_id5)?;
+
+        let txn_id6 = instance.begin(None)?;
+        instance.set_lock_timeout(txn_id6, Some(Duration::from_millis(100)))?;
+        let result = execute(
+            "UPDATE jobs SET name = 'x' WHERE id = 2",
+            &instance,
+            txn_id6,
+        );
+        assert!(result.unwrap_err().to_string().contains("lock timeout"));
+        instance.rollback(txn_id6)?;
+
+        // without a timeout, the lock is waited for until the holder ends
+        let instance_clone = instance.clone();
+        let handle = thread::spawn(move || -> Result<()> {
+            let txn_id = instance_clone.begin(None)?;
+            execute("SELECT * FROM jobs FOR UPDATE", &instance_clone, txn_id)?;
+            instance_clone.commit(txn_id)
+        });
+        thread::sleep(Duration::from_millis(200));
+        assert!(!handle.is_finished());
+        for txn_id in [txn_id1, txn_id3, txn_id4] {
+            instance.commit(txn_id)?;
+        }
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+
+        let txn_id = instance.begin(None)?;
+        let result = execute("SELECT COUNT(id) FROM jobs FOR UPDATE", &instance, txn_id);
+        assert!(result.unwrap_err().to_string().contains("not allowed"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_for_update_after_concurrent_update() -> Result<()> {
+        let instance = Arc::new(setup_test_database()?);
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE jobs (id INT, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        for id in 1..=4 {
+            let sql = format!("INSERT INTO jobs VALUES ({}, 'job{}')", id, id);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let update = |id: i64| -> Result<TransactionID> {
+            let txn_id = instance.begin(None)?;
+            let sql = format!("UPDATE jobs SET name = 'done' WHERE id = {}", id);
+            execute(&sql, &instance, txn_id)?;
+            Ok(txn_id)
+        };
+
+        // the lock is waited for until the writer commits
+        for (id, isolation_level) in [
+            (1, IsolationLevel::RepeatableRead),
+            (2, IsolationLevel::ReadCommitted),
+        ] {
+            let writer_txn_id = update(id)?;
+            let instance_clone = instance.clone();
+            let handle = thread::spawn(move || -> Result<Vec<Vec<Value>>> {
+                let txn_id = instance_clone.begin_with_isolation_level(isolation_level)?;
+                let sql = format!("SELECT * FROM jobs WHERE id = {} FOR UPDATE", id);
+                let result = execute(&sql, &instance_clone, txn_id);
+                instance_clone.rollback(txn_id)?;
+                Ok(result?.0)
+            });
+            thread::sleep(Duration::from_millis(200));
+            assert!(!handle.is_finished());
+            instance.commit(writer_txn_id)?;
+            let result = handle.join().map_err(|_| anyhow::anyhow!("thread error"))?;
+            match isolation_level {
+                // read committed skips the replaced version, the scan reads the new one later
+                IsolationLevel::ReadCommitted => assert_eq!(
+                    result?,
+                    vec![vec![
+                        Value::Integer(IntegerValue(id)),
+                        Value::Varchar(VarcharValue("done".to_string()))
+                    ]]
+                ),
+                _ => assert!(result
+                    .unwrap_err()
+                    .to_string()
+                    .contains("could not serialize access")),
+            }
+        }
+
+        // the version was replaced after the snapshot was taken, without waiting
+        for (id, wait_policy) in [(3, "NOWAIT"), (4, "SKIP LOCKED")] {
+            let txn_id = instance.begin_with_isolation_level(IsolationLevel::RepeatableRead)?;
+            execute("SELECT * FROM jobs", &instance, txn_id)?;
+            instance.commit(update(id)?)?;
+            let sql = format!(
+                "SELECT * FROM jobs WHERE id = {} FOR UPDATE {}",
+                id, wait_policy
+            );
+            let result = execute(&sql, &instance, txn_id);
+            assert!(result
+                .unwrap_err()
+                .to_string()
+                .contains("could not serialize access"));
+            instance.rollback(txn_id)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_for_share() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE accounts (id INT, balance INT)",
+            &instance,
+            txn_id,
+        )?;
+        execute("INSERT INTO accounts VALUES (1, 100)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        let sql = "SELECT balance FROM accounts WHERE id = 1 FOR SHARE";
+        let txn_id1 = instance.begin(None)?;
+        let (rows, _) = execute(sql, &instance, txn_id1)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(100))]]);
+        let txn_id2 = instance.begin(None)?;
+        let (rows, _) = execute(&format!("{} NOWAIT", sql), &instance, txn_id2)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(100))]]);
+
+        // the row is protected from writers until both readers end
+        let txn_id3 = instance.begin(None)?;
+        let sql = "SELECT * FROM accounts FOR UPDATE NOWAIT";
+        assert!(execute(sql, &instance, txn_id3).is_err());
+        instance.rollback(txn_id3)?;
+        let sql = "UPDATE accounts SET balance = 0 WHERE id = 1";
+        for txn_id in [txn_id1, txn_id2] {
+            let writer_txn_id = instance.begin(None)?;
+            instance.set_lock_timeout(writer_txn_id, Some(Duration::from_millis(100)))?;
+            let result = execute(sql, &instance, writer_txn_id);
+            assert!(result.unwrap_err().to_string().contains("lock timeout"));
+            instance.rollback(writer_txn_id)?;
+            instance.commit(txn_id)?;
+        }
+        let txn_id = instance.begin(None)?;
+        execute(sql, &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        let txn_id = instance.begin(None)?;
+        let sql = "SELECT * FROM accounts AS a INNER JOIN accounts AS b ON a.id = b.id FOR SHARE";
+        let result = execute(sql, &instance, txn_id);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("not allowed with joins"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_index() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        execute("CREATE INDEX t1_c1_index ON t1 (c1)", &instance, txn_id)?;
+        // enough rows to split the tree
+        for i in 0..2000 {
+            let sql = format!("INSERT INTO t1 VALUES ({}, 'foo')", i);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let index_page_ids = |txn_id| -> Result<Vec<PageID>> {
+            let catalog = instance
+                .catalog
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+            let index = catalog.get_indexes_by_table_name("t1", txn_id)?.remove(0);
+            IndexManager::new(
+                index,
+                instance.buffer_pool_manager.clone(),
+                instance.log_manager.clone(),
+                txn_id,
+            )
+            .page_ids()
+        };
+
+        // rolled back
+        let txn_id = instance.begin(None)?;
+        let page_ids = index_page_ids(txn_id)?;
+        assert!(page_ids.len() > 1);
+        execute("DROP INDEX t1_c1_index", &instance, txn_id)?;
+        let (rows, _) = execute("SHOW INDEXES FROM t1", &instance, txn_id)?;
+        assert!(rows.is_empty());
+        instance.rollback(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SHOW INDEXES FROM t1", &instance, txn_id)?;
+        assert_eq!(rows.len(), 1);
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 42", &instance, txn_id)?;
+        assert_eq!(rows.len(), 1);
+        instance.commit(txn_id)?;
+
+        // committed, the pages are freed after the older transaction ends
+        let old_txn_id = instance.begin(None)?;
+        let txn_id = instance.begin(None)?;
+        assert!(execute("DROP INDEX users_pkey", &instance, txn_id).is_err());
+        execute("DROP INDEX t1_c1_index", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let (rows, _) = execute("SHOW INDEXES FROM t1", &instance, old_txn_id)?;
+        assert_eq!(rows.len(), 1);
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 >= 1990", &instance, old_txn_id)?;
+        assert_eq!(rows.len(), 10);
+        instance.commit(old_txn_id)?;
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SHOW INDEXES FROM t1", &instance, txn_id)?;
+        assert!(rows.is_empty());
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 = 42", &instance, txn_id)?;
+        assert_eq!(rows.len(), 1);
+        let mut buffer_pool_manager = instance
+            .buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?;
+        let page = buffer_pool_manager.new_page(TABLE_PAGE_PAGE_TYPE)?;
+        let page_id = page
+            .read()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .page_id();
+        assert!(page_ids.contains(&page_id));
+        buffer_pool_manager.unpin_page(page_id, false)?;
+        drop(buffer_pool_manager);
+
+        assert!(execute("DROP INDEX t1_c1_index", &instance, txn_id).is_err());
+        execute("DROP INDEX IF EXISTS t1_c1_index", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+
+        // rolled back to a savepoint, the index is not freed at the commit
+        let txn_id = instance.begin(None)?;
+        execute("CREATE INDEX t1_c1_index ON t1 (c1)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        instance.savepoint(txn_id, "sp1")?;
+        execute("DROP INDEX t1_c1_index", &instance, txn_id)?;
+        instance.rollback_to_savepoint(txn_id, "sp1")?;
+        instance.commit(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SHOW INDEXES FROM t1", &instance, txn_id)?;
+        assert_eq!(rows.len(), 1);
+        assert!(index_page_ids(txn_id)?.len() > 1);
+        let (rows, _) = execute("SELECT * FROM t1 WHERE c1 >= 1990", &instance, txn_id)?;
+        assert_eq!(rows.len(), 10);
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_show_indexes() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, email VARCHAR UNIQUE, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        execute(
+            "CREATE INDEX users_name_id_index ON users (name, id)",
+            &instance,
+            txn_id,
+        )?;
+        let (rows, schema) = execute("SHOW INDEXES FROM users", &instance, txn_id)?;
+        assert_eq!(
+            schema
+                .columns
+                .iter()
+                .map(|column| column.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "index_name",
+                "column_names",
+                "root_page_id",
+                "is_unique",
+                "is_primary"
+            ]
+        );
+        let rows = rows
+            .into_iter()
+            .map(|row| {
+                vec![
+                    row[0].clone(),
+                    row[1].clone(),
+                    row[3].clone(),
+                    row[4].clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    Value::Varchar(VarcharValue("users_pkey".to_string())),
+                    Value::Varchar(VarcharValue("id".to_string())),
+                    Value::Boolean(BooleanValue(true)),
+                    Value::Boolean(BooleanValue(true)),
+                ],
+                vec![
+                    Value::Varchar(VarcharValue("users_email_key".to_string())),
+                    Value::Varchar(VarcharValue("email".to_string())),
+                    Value::Boolean(BooleanValue(true)),
+                    Value::Boolean(BooleanValue(false)),
+                ],
+                vec![
+                    Value::Varchar(VarcharValue("users_name_id_index".to_string())),
+                    Value::Varchar(VarcharValue("name, id".to_string())),
+                    Value::Boolean(BooleanValue(false)),
+                    Value::Boolean(BooleanValue(false)),
+                ],
+            ]
+        );
+        assert!(execute("SHOW INDEXES FROM missing", &instance, txn_id).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_index_with_existing_name() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        execute(
+            "CREATE INDEX users_name_index ON users (name)",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+
+        // the names are unique across tables
+        let txn_id = instance.begin(None)?;
+        for sql in [
+            "CREATE INDEX users_name_index ON users (name)",
+            "CREATE INDEX users_name_index ON t1 (c1)",
+            "CREATE INDEX users_pkey ON users (name)",
+        ] {
+            let result = execute(sql, &instance, txn_id);
+            assert!(result.unwrap_err().to_string().contains("already exists"));
+        }
+        let (rows, _) = execute("SHOW INDEXES FROM users", &instance, txn_id)?;
+        assert_eq!(rows.len(), 2);
+
+        // the name can be taken again once the index is dropped
+        execute("DROP INDEX users_name_index", &instance, txn_id)?;
+        execute(
+            "CREATE INDEX users_name_index ON users (id, name)",
+            &instance,
+            txn_id,
+        )?;
+        let (rows, _) = execute("SHOW INDEXES FROM users", &instance, txn_id)?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[1][1],
+            Value::Varchar(VarcharValue("id, name".to_string()))
+        );
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    fn plan(sql: &str, instance: &Instance, txn_id: TransactionID) -> Result<Plan> {
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+        let mut binder = Binder::new(instance.catalog.clone(), txn_id);
+        let statement = binder.bind_statement(&parser.parse()?)?;
+        Optimizer::new(instance.catalog.clone(), txn_id).optimize(Planner::new(statement).plan())
+    }
+
+    fn scan_plan(plan: &Plan) -> Plan {
+        match plan.children().first() {
+            Some(child) => scan_plan(child),
+            None => plan.clone(),
+        }
+    }
+
+    #[test]
+    fn test_index_only_scan() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute("CREATE INDEX t1_c1_index ON t1 (c1)", &instance, txn_id)?;
+        for i in 0..1000 {
+            let sql = format!("INSERT INTO t1 VALUES ({}, '{:0>100}')", i, i);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+
+        let txn_id = instance.begin(None)?;
+        for sql in [
+            "SELECT c1 FROM t1 WHERE c1 > 10",
+            "SELECT c1, c1 * 2 FROM t1 WHERE c1 > 10 AND c1 <> 20 ORDER BY c1 DESC",
+            "SELECT COUNT(c1) FROM t1 WHERE c1 >= 500",
+            "SELECT COUNT(*) FROM t1 WHERE c1 >= 500",
+        ] {
+            assert!(matches!(
+                scan_plan(&plan(sql, &instance, txn_id)?),
+                Plan::IndexOnlyScan(_)
+            ));
+        }
+        for sql in [
+            "SELECT * FROM t1 WHERE c1 > 10",
+            "SELECT c1 FROM t1 WHERE c1 > 10 AND c2 = 'foo'",
+            "SELECT COUNT(c2) FROM t1 WHERE c1 >= 500",
+        ] {
+            assert!(matches!(
+                scan_plan(&plan(sql, &instance, txn_id)?),
+                Plan::IndexScan(_)
+            ));
+        }
+        let select = |txn_id| -> Result<Vec<Vec<Value>>> {
+            Ok(execute("SELECT c1 FROM t1 WHERE c1 >= 990", &instance, txn_id)?.0)
+        };
+        let values = |range: &mut dyn Iterator<Item = i64>| -> Vec<Vec<Value>> {
+            range
+                .map(|i| vec![Value::Integer(IntegerValue(i))])
+                .collect()
+        };
+        // without the flags, the heap decides the visibility
+        assert_eq!(select(txn_id)?, values(&mut (990..1000)));
+
+        let first_page_id = instance
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .get_first_page_id_by_table_name("t1", txn_id)?;
+        let table_heap = TableHeap::new(
+            first_page_id,
+            instance.buffer_pool_manager.clone(),
+            instance.transaction_manager.clone(),
+            instance.lock_manager.clone(),
+            instance.log_manager.clone(),
+            txn_id,
+        );
+        assert!(table_heap.mark_all_visible_pages()? > 1);
+        assert!(table_heap.is_all_visible(first_page_id)?);
+        assert_eq!(select(txn_id)?, values(&mut (990..1000)));
+        let (rows, _) = execute("SELECT COUNT(*) FROM t1 WHERE c1 >= 500", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(500))]]);
+
+        // modifications of another transaction clear the flags of their pages
+        let other_txn_id = instance.begin(None)?;
+        execute("DELETE FROM t1 WHERE c1 = 995", &instance, other_txn_id)?;
+        execute(
+            "INSERT INTO t1 VALUES (2000, 'bar')",
+            &instance,
+            other_txn_id,
+        )?;
+        assert_eq!(select(txn_id)?, values(&mut (990..1000)));
+        assert_eq!(
+            select(other_txn_id)?,
+            values(&mut (990..1000).filter(|&i| i != 995).chain([2000]))
+        );
+        // the running transaction keeps the pages from being flagged again
+        table_heap.mark_all_visible_pages()?;
+        assert_eq!(select(txn_id)?, values(&mut (990..1000)));
+        instance.commit(other_txn_id)?;
+        assert_eq!(select(txn_id)?, values(&mut (990..1000)));
+        instance.commit(txn_id)?;
+
+        let txn_id = instance.begin(None)?;
+        assert_eq!(
+            select(txn_id)?,
+            values(&mut (990..1000).filter(|&i| i != 995).chain([2000]))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_vacuum() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        for i in 0..200 {
+            let sql = format!("INSERT INTO users VALUES ({}, 'foo')", i);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        let (first_page_id, index) = {
+            let catalog = instance
+                .catalog
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+            let first_page_id = catalog.get_first_page_id_by_table_name("users", txn_id)?;
+            let mut index = catalog
+                .get_indexes_by_table_name("users", txn_id)?
+                .remove(0);
+            index.set_schema(catalog.get_schema_by_table_name("users", txn_id)?);
+            (first_page_id, index)
+        };
+        instance.commit(txn_id)?;
+        let index_entry_count = || -> Result<usize> {
+            IndexManager::new(
+                index.clone(),
+                instance.buffer_pool_manager.clone(),
+                instance.log_manager.clone(),
+                INVALID_TRANSACTION_ID,
+            )
+            .scan(&IndexRange::full())?
+            .try_fold(0, |count, entry| entry.map(|_| count + 1))
+        };
+        let page_count = || -> Result<usize> {
+            let mut count = 0;
+            let mut page_id = first_page_id;
+            while page_id != INVALID_PAGE_ID {
+                let mut buffer_pool_manager = instance
+                    .buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?;
+                let page = buffer_pool_manager.fetch_page(page_id)?;
+                let next_page_id = page
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .with_table_page(|table_page| table_page.next_page_id());
+                buffer_pool_manager.unpin_page(page_id, false)?;
+                count += 1;
+                page_id = next_page_id;
+            }
+            Ok(count)
+        };
+        let update_all = || -> Result<()> {
+            let txn_id = instance.begin(None)?;
+            execute("UPDATE users SET name = 'bar'", &instance, txn_id)?;
+            instance.commit(txn_id)
+        };
+        for _ in 0..3 {
+            update_all()?;
+        }
+        assert_eq!(index_entry_count()?, 800);
+
+        // the deleted rows are still visible to the older transaction
+        let old_txn_id = instance.begin(None)?;
+        let txn_id = instance.begin(None)?;
+        execute("DELETE FROM users WHERE id >= 100", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        assert_eq!(instance.vacuum(Some("users"))?, 600);
+        assert_eq!(index_entry_count()?, 200);
+        let (rows, _) = execute("SELECT COUNT(*) FROM users", &instance, old_txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(200))]]);
+        instance.commit(old_txn_id)?;
+        assert_eq!(instance.vacuum(None)?, 100);
+        assert_eq!(index_entry_count()?, 100);
+
+        // new versions reuse the free space
+        let page_count_after_vacuum = page_count()?;
+        for _ in 0..5 {
+            update_all()?;
+            instance.vacuum(Some("users"))?;
+        }
+        assert_eq!(page_count()?, page_count_after_vacuum);
+        let txn_id = instance.begin(None)?;
+        let (rows, _) = execute("SELECT * FROM users WHERE id = 42", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(IntegerValue(42)),
+                Value::Varchar(VarcharValue("bar".to_string()))
+            ]]
+        );
+        let (rows, _) = execute("SELECT COUNT(*) FROM users", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(100))]]);
+        assert!(execute("INSERT INTO users VALUES (42, 'baz')", &instance, txn_id).is_err());
+        instance.commit(txn_id)?;
+        assert!(instance.vacuum(Some("unknown")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_autovacuum() -> Result<()> {
+        let instance = setup_test_database()?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE users (id INT PRIMARY KEY, name VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        for i in 0..100 {
+            let sql = format!("INSERT INTO users VALUES ({}, 'foo')", i);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let instance = Arc::new(RwLock::new(instance));
+        let config = AutovacuumConfig {
+            threshold: 50,
+            naptime: Duration::from_secs(1),
+            cost_limit: 10,
+            cost_delay: Duration::from_millis(1),
+        };
+        let stat = |table_name: &str| -> Result<Vec<Value>> {
+            let instance = instance.read().map_err(|_| anyhow::anyhow!("lock error"))?;
+            let txn_id = instance.begin(None)?;
+            let sql = format!(
+                "SELECT n_dead_tuples, last_autovacuum IS NULL, last_autovacuum_removed, autovacuum_count FROM system_stat_tables WHERE table_name = '{}'",
+                table_name
+            );
+            let (mut rows, _) = execute(&sql, &instance, txn_id)?;
+            instance.commit(txn_id)?;
+            Ok(rows.remove(0))
+        };
+        let stat_row = |dead_tuples, never_vacuumed, removed, count| {
+            vec![
+                Value::Integer(IntegerValue(dead_tuples)),
+                Value::Boolean(BooleanValue(never_vacuumed)),
+                Value::Integer(IntegerValue(removed)),
+                Value::Integer(IntegerValue(count)),
+            ]
+        };
+        {
+            let instance = instance.read().map_err(|_| anyhow::anyhow!("lock error"))?;
+            // counted when the transactions end, the new versions of the aborted update are dead
+            let txn_id = instance.begin(None)?;
+            execute("DELETE FROM users WHERE id < 30", &instance, txn_id)?;
+            assert_eq!(stat("users")?, stat_row(0, true, 0, 0));
+            instance.commit(txn_id)?;
+            let txn_id = instance.begin(None)?;
+            execute("UPDATE users SET name = 'bar'", &instance, txn_id)?;
+            instance.rollback(txn_id)?;
+        }
+        assert_eq!(stat("users")?, stat_row(100, true, 0, 0));
+        assert_eq!(stat("t1")?, stat_row(0, true, 0, 0));
+
+        autovacuum(&instance, &config)?;
+        assert_eq!(stat("users")?, stat_row(0, false, 100, 1));
+        {
+            let instance = instance.read().map_err(|_| anyhow::anyhow!("lock error"))?;
+            let txn_id = instance.begin(None)?;
+            let (rows, _) = execute("SELECT COUNT(*) FROM users", &instance, txn_id)?;
+            assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(70))]]);
+            execute("INSERT INTO users VALUES (0, 'baz')", &instance, txn_id)?;
+            execute(
+                "UPDATE users SET name = 'baz' WHERE id < 20",
+                &instance,
+                txn_id,
+            )?;
+            instance.commit(txn_id)?;
+            // the view is read only
+            let txn_id = instance.begin(None)?;
+            assert!(execute("DELETE FROM system_stat_tables", &instance, txn_id).is_err());
+            instance.rollback(txn_id)?;
+        }
+
+        // below the threshold
+        autovacuum(&instance, &config)?;
+        assert_eq!(stat("users")?, stat_row(1, false, 100, 1));
+        Ok(())
+    }
 }
@@ -27,6 +27,15 @@ impl DeleteExecutor<'_> {
             self.table_heap.delete(rid)?;
             self.count += 1;
         }
+        self.executor_context
+            .statistics
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .count_delete(
+                self.executor_context.transaction_id,
+                &self.plan.table_name,
+                self.count as usize,
+            );
         Ok(())
     }
     pub fn next(&mut self) -> Result<Option<Tuple>> {
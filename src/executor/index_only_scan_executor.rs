@@ -0,0 +1,112 @@
+use anyhow::Result;
+
+use crate::{
+    index::{IndexIterator, IndexManager},
+    plan::IndexOnlyScanPlan,
+    predicate_lock::PredicateLockTarget,
+    table::TableHeap,
+    tuple::Tuple,
+    value::Value,
+};
+
+use super::ExecutorContext;
+
+pub struct IndexOnlyScanExecutor<'a> {
+    pub plan: IndexOnlyScanPlan,
+    pub executor_context: &'a ExecutorContext,
+    pub table_heap: TableHeap,
+    pub index_manager: Option<IndexManager>,
+    pub index_iterator: Option<IndexIterator>,
+    pub range_cursor: usize,
+    // positions of the key columns in the table schema
+    pub key_column_indexes: Vec<usize>,
+}
+
+impl IndexOnlyScanExecutor<'_> {
+    pub fn init(&mut self) -> Result<()> {
+        let mut index = self
+            .executor_context
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
+            .get_index(self.plan.index_id, self.executor_context.transaction_id)?;
+        index.set_schema(self.plan.schema.clone());
+        self.key_column_indexes = index
+            .columns
+            .iter()
+            .map(|column_name| {
+                self.plan
+                    .schema
+                    .column_index(column_name)
+                    .ok_or_else(|| anyhow::anyhow!("column {} not found", column_name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.executor_context
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .acquire_predicate_lock(
+                self.executor_context.transaction_id,
+                PredicateLockTarget::Index(index.first_page_id),
+            );
+        self.index_manager = Some(IndexManager::new(
+            index,
+            self.executor_context.buffer_pool_manager.clone(),
+            self.executor_context.log_manager.clone(),
+            self.executor_context.transaction_id,
+        ));
+        self.index_iterator = None;
+        self.range_cursor = 0;
+        Ok(())
+    }
+    pub fn next(&mut self) -> Result<Option<Tuple>> {
+        let index_manager = match &self.index_manager {
+            Some(index_manager) => index_manager,
+            None => return Ok(None),
+        };
+        loop {
+            let index_iterator = match &mut self.index_iterator {
+                Some(index_iterator) => index_iterator,
+                None => {
+                    if self.range_cursor >= self.plan.ranges.len() {
+                        return Ok(None);
+                    }
+                    let range = &self.plan.ranges[self.range_cursor];
+                    self.range_cursor += 1;
+                    self.index_iterator.insert(index_manager.scan(range)?)
+                }
+            };
+            let (key, rid) = match index_iterator.next().transpose()? {
+                Some(entry) => entry,
+                None => {
+                    self.index_iterator = None;
+                    continue;
+                }
+            };
+            self.executor_context
+                .transaction_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .acquire_predicate_lock(
+                    self.executor_context.transaction_id,
+                    PredicateLockTarget::Page(rid.0),
+                );
+            // the heap tuple is read only if the flag of its page can not prove the visibility.
+            // the flag is checked for every entry, a tuple inserted after the check clears it
+            // before its index entry is inserted
+            if !self.table_heap.is_all_visible(rid.0)? {
+                match self.table_heap.get(rid)? {
+                    Some(tuple) => return Ok(Some(tuple)),
+                    None => continue,
+                }
+            }
+            let mut values = vec![Value::Null; self.plan.schema.columns.len()];
+            for (value, &i) in key.into_iter().zip(self.key_column_indexes.iter()) {
+                values[i] = value;
+            }
+            let mut tuple = Tuple::temp_tuple(&values);
+            tuple.rid = Some(rid);
+            return Ok(Some(tuple));
+        }
+    }
+}
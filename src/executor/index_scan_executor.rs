@@ -1,15 +1,22 @@
 use anyhow::Result;
 
-use crate::{common::RID, index::IndexManager, plan::IndexScanPlan, tuple::Tuple};
+use crate::{
+    index::{IndexIterator, IndexManager},
+    plan::IndexScanPlan,
+    predicate_lock::PredicateLockTarget,
+    table::TableHeap,
+    tuple::Tuple,
+};
 
 use super::ExecutorContext;
 
 pub struct IndexScanExecutor<'a> {
     pub plan: IndexScanPlan,
     pub executor_context: &'a ExecutorContext,
-    pub index_id: i64,
-    pub rids: Option<Vec<RID>>,
-    pub cursor: usize,
+    pub table_heap: TableHeap,
+    pub index_manager: Option<IndexManager>,
+    pub index_iterator: Option<IndexIterator>,
+    pub range_cursor: usize,
 }
 
 impl IndexScanExecutor<'_> {
@@ -19,43 +26,60 @@ impl IndexScanExecutor<'_> {
             .catalog
             .lock()
             .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
-            .get_index(self.index_id, self.executor_context.transaction_id)?;
-        index.set_schema(self.plan.table_schema.clone());
-        let index_manager = IndexManager::new(
+            .get_index(self.plan.index_id, self.executor_context.transaction_id)?;
+        index.set_schema(self.plan.schema.clone());
+        self.executor_context
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .acquire_predicate_lock(
+                self.executor_context.transaction_id,
+                PredicateLockTarget::Index(index.first_page_id),
+            );
+        self.index_manager = Some(IndexManager::new(
             index,
-            self.executor_context.catalog.clone(),
             self.executor_context.buffer_pool_manager.clone(),
-        );
-        let right_value = self
-            .plan
-            .binary_expression
-            .right
-            .eval(&vec![&Tuple::new(None, &[])], &vec![])
-            .map_err(|_| anyhow::anyhow!("eval error"))?;
-        self.rids = index_manager.lookup(&right_value)?;
+            self.executor_context.log_manager.clone(),
+            self.executor_context.transaction_id,
+        ));
+        self.index_iterator = None;
+        self.range_cursor = 0;
         Ok(())
     }
     pub fn next(&mut self) -> Result<Option<Tuple>> {
-        if let Some(rids) = &self.rids {
-            if self.cursor >= rids.len() {
-                return Ok(None);
+        let index_manager = match &self.index_manager {
+            Some(index_manager) => index_manager,
+            None => return Ok(None),
+        };
+        loop {
+            let index_iterator = match &mut self.index_iterator {
+                Some(index_iterator) => index_iterator,
+                None => {
+                    if self.range_cursor >= self.plan.ranges.len() {
+                        return Ok(None);
+                    }
+                    let range = &self.plan.ranges[self.range_cursor];
+                    self.range_cursor += 1;
+                    self.index_iterator.insert(index_manager.scan(range)?)
+                }
+            };
+            match index_iterator.next().transpose()? {
+                // the index also points to versions which are not visible to this transaction
+                Some((_, rid)) => {
+                    self.executor_context
+                        .transaction_manager
+                        .lock()
+                        .map_err(|_| anyhow::anyhow!("lock error"))?
+                        .acquire_predicate_lock(
+                            self.executor_context.transaction_id,
+                            PredicateLockTarget::Page(rid.0),
+                        );
+                    if let Some(tuple) = self.table_heap.get(rid)? {
+                        return Ok(Some(tuple));
+                    }
+                }
+                None => self.index_iterator = None,
             }
-            let rid = rids[self.cursor];
-            self.cursor += 1;
-            let page = self
-                .executor_context
-                .buffer_pool_manager
-                .lock()
-                .map_err(|_| anyhow::anyhow!("lock error"))?
-                .fetch_page(rid.0)?;
-            let tuple_data = page
-                .read()
-                .map_err(|_| anyhow::anyhow!("read error"))?
-                .with_table_page(|table_page| table_page.get_tuple(rid.1 as usize));
-            let tuple = Tuple::new(None, &tuple_data);
-            Ok(Some(tuple))
-        } else {
-            Ok(None)
         }
     }
 }
@@ -3,7 +3,6 @@ use anyhow::Result;
 use crate::{
     catalog::Schema,
     common::INVALID_TRANSACTION_ID,
-    index::IndexManager,
     plan::InsertPlan,
     table::TableHeap,
     tuple::Tuple,
@@ -36,9 +35,9 @@ impl InsertExecutor<'_> {
                         let position = column_names.iter().position(|x| x == &c.name);
                         match position {
                             Some(pos) => index = pos,
-                            None => return Ok(Value::Null)
+                            None => return Ok(Value::Null),
                         }
-                    },
+                    }
                     None => {
                         index = i;
                     }
@@ -55,34 +54,13 @@ impl InsertExecutor<'_> {
             .collect::<Result<Vec<_>>>()?;
         let rid = self.table_heap.insert(&values)?;
         self.count += 1;
-        let mut indexes = self
-            .executor_context
-            .catalog
-            .lock()
-            .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
-            .get_indexes_by_table_name(
-                &self.plan.table_name,
-                self.executor_context.transaction_id,
-            )?;
-        for index in indexes.iter_mut() {
-            index.set_schema(self.plan.table_schema.clone());
-        }
-        for index in indexes {
-            // TODO: only support single column index
-            let column_name = index.columns[0].clone();
-            let index_manager = IndexManager::new(
-                index,
-                self.executor_context.catalog.clone(),
-                self.executor_context.buffer_pool_manager.clone(),
-            );
-            for (i, column) in self.plan.table_schema.columns.iter().enumerate() {
-                if column.name == column_name {
-                    let right_value = values[i].clone();
-                    index_manager.insert(&right_value, rid)?;
-                    break;
-                }
-            }
-        }
+        self.executor_context.insert_into_indexes(
+            &self.table_heap,
+            &self.plan.table_name,
+            &self.plan.table_schema,
+            &values,
+            rid,
+        )?;
         Ok(())
     }
     pub fn next(&mut self) -> Result<Option<Tuple>> {
@@ -24,17 +24,18 @@ impl LimitExecutor<'_> {
             _ => Err(anyhow::anyhow!("offset count must be integer"))?,
         };
         let mut result = vec![];
-        while let Some(tuple) = self.child.next()? {
+        // no more rows are pulled than needed, the rows below may be locked by pulling them
+        while limit > 0 {
+            let tuple = match self.child.next()? {
+                Some(tuple) => tuple,
+                None => break,
+            };
             if offset > 0 {
                 offset -= 1;
                 continue;
             }
-            if limit > 0 {
-                limit -= 1;
-                result.push(tuple);
-            } else {
-                break;
-            }
+            limit -= 1;
+            result.push(tuple);
         }
         self.result = result;
         Ok(())
@@ -0,0 +1,81 @@
+use anyhow::{anyhow, Result};
+
+use crate::{
+    concurrency::{IsolationLevel, TransactionRollbackError},
+    lock::LockMode,
+    parser::{LockStrength, WaitPolicy},
+    plan::LockRowsPlan,
+    table::TableHeap,
+    tuple::Tuple,
+};
+
+use super::{Executor, ExecutorContext};
+
+pub struct LockRowsExecutor<'a> {
+    pub plan: LockRowsPlan,
+    pub child: Box<Executor<'a>>,
+    pub executor_context: &'a ExecutorContext,
+    pub table_heap: TableHeap,
+}
+
+impl LockRowsExecutor<'_> {
+    pub fn init(&mut self) -> Result<()> {
+        self.child.init()?;
+        Ok(())
+    }
+    // the locks are held until the end of the transaction
+    pub fn next(&mut self) -> Result<Option<Tuple>> {
+        while let Some(tuple) = self.child.next()? {
+            let rid = tuple.rid.ok_or_else(|| anyhow!("rid is None"))?;
+            let lock_manager = self
+                .executor_context
+                .lock_manager
+                .read()
+                .map_err(|_| anyhow!("lock error"))?;
+            let txn_id = self.executor_context.transaction_id;
+            let mode = match self.plan.strength {
+                LockStrength::Update => LockMode::Exclusive,
+                LockStrength::Share => LockMode::Shared,
+            };
+            match self.plan.wait_policy {
+                WaitPolicy::Wait => lock_manager.lock(rid, txn_id, mode)?,
+                WaitPolicy::NoWait => {
+                    if !lock_manager.try_lock(rid, txn_id, mode)? {
+                        return Err(anyhow!(
+                            "could not obtain lock on row in table {}",
+                            self.plan.table_name
+                        ));
+                    }
+                }
+                WaitPolicy::SkipLocked => {
+                    if !lock_manager.try_lock(rid, txn_id, mode)? {
+                        continue;
+                    }
+                }
+            }
+            drop(lock_manager);
+            // the version returned by the scan may have been replaced while the lock was waited
+            // for, or before it was taken. updates do not link the versions, so read committed
+            // transactions skip the row instead of following the update
+            if self.table_heap.is_updated(rid)? {
+                let isolation_level = self
+                    .executor_context
+                    .transaction_manager
+                    .lock()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .isolation_level(txn_id);
+                match isolation_level {
+                    IsolationLevel::ReadCommitted => continue,
+                    IsolationLevel::RepeatableRead | IsolationLevel::Serializable => {
+                        return Err(TransactionRollbackError(
+                            "could not serialize access due to concurrent update".to_string(),
+                        )
+                        .into());
+                    }
+                }
+            }
+            return Ok(Some(tuple));
+        }
+        Ok(None)
+    }
+}
@@ -29,7 +29,10 @@ impl ProjectExecutor<'_> {
                         .eval(&tuples, &vec![&self.plan.child.schema()])
                 })
                 .collect::<Result<Vec<_>>>()?;
-            return Ok(Some(Tuple::temp_tuple(&values)));
+            // the rid is kept for the row locks above the projection
+            let mut tuple = Tuple::temp_tuple(&values);
+            tuple.rid = row.rid;
+            return Ok(Some(tuple));
         }
         Ok(None)
     }
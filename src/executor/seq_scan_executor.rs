@@ -1,6 +1,8 @@
 use anyhow::Result;
 
-use crate::{plan::SeqScanPlan, table::TableIterator, tuple::Tuple};
+use crate::{
+    plan::SeqScanPlan, predicate_lock::PredicateLockTarget, table::TableIterator, tuple::Tuple,
+};
 
 use super::ExecutorContext;
 
@@ -13,9 +15,17 @@ pub struct SeqScanExecutor<'a> {
 impl SeqScanExecutor<'_> {
     pub fn init(&mut self) -> Result<()> {
         self.table_iterator.reset();
+        self.executor_context
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .acquire_predicate_lock(
+                self.executor_context.transaction_id,
+                PredicateLockTarget::Table(self.plan.first_page_id),
+            );
         Ok(())
     }
     pub fn next(&mut self) -> Result<Option<Tuple>> {
-        Ok(self.table_iterator.next())
+        self.table_iterator.next_visible()
     }
 }
@@ -0,0 +1,46 @@
+use anyhow::Result;
+
+use crate::{
+    autovacuum::STAT_TABLES_VIEW_NAME, plan::SystemViewScanPlan, tuple::Tuple, value::Value,
+};
+
+use super::ExecutorContext;
+
+pub struct SystemViewScanExecutor<'a> {
+    pub plan: SystemViewScanPlan,
+    pub executor_context: &'a ExecutorContext,
+    pub rows: Vec<Vec<Value>>,
+    pub cursor: usize,
+}
+
+impl SystemViewScanExecutor<'_> {
+    pub fn init(&mut self) -> Result<()> {
+        self.rows = match self.plan.view_name.as_str() {
+            STAT_TABLES_VIEW_NAME => {
+                let table_names = self
+                    .executor_context
+                    .catalog
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .get_table_names(self.executor_context.transaction_id)?;
+                self.executor_context
+                    .statistics
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .view_rows(&table_names)
+            }
+            view_name => return Err(anyhow::anyhow!("view {} not found", view_name)),
+        };
+        self.cursor = 0;
+        Ok(())
+    }
+    pub fn next(&mut self) -> Result<Option<Tuple>> {
+        match self.rows.get(self.cursor) {
+            Some(values) => {
+                self.cursor += 1;
+                Ok(Some(Tuple::temp_tuple(values)))
+            }
+            None => Ok(None),
+        }
+    }
+}
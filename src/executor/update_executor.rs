@@ -22,7 +22,12 @@ pub struct UpdateExecutor<'a> {
 impl UpdateExecutor<'_> {
     pub fn init(&mut self) -> Result<()> {
         self.child.init()?;
+        // read before writing, otherwise the scan would find the new versions and update them again
+        let mut rows = vec![];
         while let Some(row) = self.child.next()? {
+            rows.push(row);
+        }
+        for row in rows {
             let rid = row.rid.ok_or_else(|| anyhow!("rid is None"))?;
             let mut new_values = row.values(self.plan.child.schema());
             for assignment in self.plan.assignments.iter() {
@@ -31,9 +36,25 @@ impl UpdateExecutor<'_> {
                     .eval(&vec![&row], &vec![&self.plan.child.schema()])?;
             }
             self.table_heap.delete(rid)?;
-            self.table_heap.insert(&new_values)?;
+            let new_rid = self.table_heap.insert(&new_values)?;
+            self.executor_context.insert_into_indexes(
+                &self.table_heap,
+                &self.plan.table_name,
+                self.plan.child.schema(),
+                &new_values,
+                new_rid,
+            )?;
             self.count += 1;
         }
+        self.executor_context
+            .statistics
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .count_update(
+                self.executor_context.transaction_id,
+                &self.plan.table_name,
+                self.count as usize,
+            );
         Ok(())
     }
     pub fn next(&mut self) -> Result<Option<Tuple>> {
@@ -1,15 +1,30 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    ops::{Bound, Range},
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
 
 use anyhow::Result;
 
 use crate::{
     buffer::BufferPoolManager,
-    catalog::{Catalog, Schema},
-    common::{PageID, RID},
+    catalog::Schema,
+    common::{PageID, TransactionID, INVALID_PAGE_ID, LSN, RID},
+    log::{
+        BPlusTreePageEntry, LoadBPlusTreePage, LogManager, LogRecordBody, NewBPlusTreeRootPage,
+        SetBPlusTreeParentPageID, SetBPlusTreePrevPageID, SplitBPlusTreePage,
+    },
+    page::{
+        b_plus_tree_internal_page::{BPlusTreeInternalPage, B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE},
+        b_plus_tree_leaf_page::{BPlusTreeLeafPage, B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE},
+        Page, PageType,
+    },
+    tuple::Tuple,
     value::Value,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Index {
     pub id: i64,
     pub name: String,
@@ -17,6 +32,8 @@ pub struct Index {
     pub first_page_id: PageID,
     pub columns: Vec<String>,
     pub schema: Schema,
+    pub is_unique: bool,
+    pub is_primary: bool,
 }
 
 impl Index {
@@ -41,6 +58,16 @@ impl Index {
         } else {
             return Err(anyhow::anyhow!("Invalid first_page_id"));
         };
+        let is_unique = if let Value::Boolean(is_unique) = &values[4] {
+            is_unique.0
+        } else {
+            return Err(anyhow::anyhow!("Invalid is_unique"));
+        };
+        let is_primary = if let Value::Boolean(is_primary) = &values[5] {
+            is_primary.0
+        } else {
+            return Err(anyhow::anyhow!("Invalid is_primary"));
+        };
         Ok(Self {
             id,
             name,
@@ -50,12 +77,26 @@ impl Index {
             schema: Schema {
                 columns: Vec::new(),
             },
+            is_unique,
+            is_primary,
         })
     }
     // TODO: set by catalog
     pub fn add_columns(&mut self, column: String) {
         self.columns.push(column);
     }
+    // picks the values of the indexed columns from a row of the table
+    pub fn key(&self, table_schema: &Schema, values: &[Value]) -> Result<Vec<Value>> {
+        self.columns
+            .iter()
+            .map(|column| {
+                table_schema
+                    .column_index(column)
+                    .map(|i| values[i].clone())
+                    .ok_or_else(|| anyhow::anyhow!("column {} not found", column))
+            })
+            .collect()
+    }
     // TODO: set by catalog
     pub fn set_schema(&mut self, schema: Schema) {
         let columns = self.columns.iter().map(|column| {
@@ -74,80 +115,921 @@ impl Index {
 
 pub struct IndexManager {
     index: Index,
-    catalog: Arc<Mutex<Catalog>>,
     buffer_pool_manager: Arc<Mutex<BufferPoolManager>>,
+    log_manager: Arc<Mutex<LogManager>>,
+    txn_id: TransactionID,
 }
 impl IndexManager {
     pub fn new(
         index: Index,
-        catalog: Arc<Mutex<Catalog>>,
         buffer_pool_manager: Arc<Mutex<BufferPoolManager>>,
+        log_manager: Arc<Mutex<LogManager>>,
+        txn_id: TransactionID,
     ) -> Self {
         Self {
             index,
-            catalog,
             buffer_pool_manager,
+            log_manager,
+            txn_id,
+        }
+    }
+    pub fn lookup(&self, key: &[Value]) -> Result<Option<Vec<RID>>> {
+        let index_iterator = self.scan(&IndexRange {
+            start: Bound::Included(key.to_vec()),
+            end: Bound::Included(key.to_vec()),
+        })?;
+        let rids = index_iterator
+            .map(|entry| entry.map(|(_, rid)| rid))
+            .collect::<Result<Vec<_>>>()?;
+        if rids.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(rids))
+    }
+    pub fn scan(&self, range: &IndexRange) -> Result<IndexIterator> {
+        let start_key = match &range.start {
+            Bound::Included(key) | Bound::Excluded(key) => Some(key.as_slice()),
+            Bound::Unbounded => None,
+        };
+        // the first leaf is read under the latch of the descent, the root may be split into an
+        // internal page as soon as the latch is released
+        let (entries, next_page_id) = self.find_leaf_page(start_key, true, |leaf_page| {
+            read_leaf_page(leaf_page, &self.index.schema)
+        })?;
+        Ok(IndexIterator {
+            buffer_pool_manager: self.buffer_pool_manager.clone(),
+            schema: self.index.schema.clone(),
+            range: range.clone(),
+            next_page_id,
+            entries,
+            cursor: 0,
+        })
+    }
+    // every page of the tree, the index must no longer be reachable
+    pub fn page_ids(&self) -> Result<Vec<PageID>> {
+        let mut page_ids = vec![];
+        let mut stack = vec![self.index.first_page_id];
+        while let Some(page_id) = stack.pop() {
+            let page = self.fetch_page(page_id)?;
+            let child_page_ids = match &*page.read().map_err(|_| anyhow::anyhow!("read error"))? {
+                Page::BPlusTreeInternal(internal_page) => internal_page.values(),
+                _ => vec![],
+            };
+            self.unpin_page(page_id, false)?;
+            page_ids.push(page_id);
+            stack.extend(child_page_ids);
+        }
+        Ok(page_ids)
+    }
+    // latch crabbing: the path is latched from the root, and the latches of the ancestors are
+    // released as soon as a child is known not to split
+    pub fn insert(&self, key: &[Value], rid: RID) -> Result<()> {
+        if BPlusTreeLeafPage::entry_size(key) > BPlusTreeLeafPage::max_entry_size() {
+            return Err(anyhow::anyhow!("index key is too large"));
+        }
+        let root_page_id = self.index.first_page_id;
+        let root_page = self.fetch_page(root_page_id)?;
+        let root_page_guard = root_page
+            .write()
+            .map_err(|_| anyhow::anyhow!("write error"))?;
+        self.insert_into_subtree(root_page_id, root_page_guard, &mut || {}, key, rid)?;
+        Ok(())
+    }
+    // underfull pages are not merged. the path is latched like a scan, but in exclusive mode,
+    // so that the first leaf is changed under the latch of the descent
+    pub fn delete(&self, key: &[Value], rid: RID) -> Result<bool> {
+        let root_page_id = self.index.first_page_id;
+        let root_page = self.fetch_page(root_page_id)?;
+        let root_page_guard = root_page
+            .write()
+            .map_err(|_| anyhow::anyhow!("write error"))?;
+        self.delete_from_subtree(root_page_id, root_page_guard, key, rid)
+    }
+    fn delete_from_subtree(
+        &self,
+        page_id: PageID,
+        mut page_guard: RwLockWriteGuard<'_, Page>,
+        key: &[Value],
+        rid: RID,
+    ) -> Result<bool> {
+        let child_page_id = match &*page_guard {
+            Page::BPlusTreeLeaf(_) => None,
+            Page::BPlusTreeInternal(internal_page) => {
+                Some(internal_page.value_at(internal_page.key_index_lower(key, &self.index.schema)))
+            }
+            _ => return Err(anyhow::anyhow!("invalid index page")),
+        };
+        if let Some(child_page_id) = child_page_id {
+            let child_page = self.fetch_page(child_page_id)?;
+            let child_page_guard = child_page
+                .write()
+                .map_err(|_| anyhow::anyhow!("write error"))?;
+            drop(page_guard);
+            self.unpin_page(page_id, false)?;
+            return self.delete_from_subtree(child_page_id, child_page_guard, key, rid);
+        }
+
+        // the entries of the key may continue in the next leaf pages, which are never the root
+        let (mut is_deleted, mut next_page_id, mut is_last) =
+            self.delete_from_leaf_page(&mut page_guard, key, rid)?;
+        drop(page_guard);
+        self.unpin_page(page_id, is_deleted)?;
+        while !is_deleted && !is_last && next_page_id != INVALID_PAGE_ID {
+            let page_id = next_page_id;
+            let page = self.fetch_page(page_id)?;
+            let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("write error"))?;
+            (is_deleted, next_page_id, is_last) =
+                self.delete_from_leaf_page(&mut page_guard, key, rid)?;
+            drop(page_guard);
+            self.unpin_page(page_id, is_deleted)?;
+        }
+        Ok(is_deleted)
+    }
+    // returns whether the entry was deleted, the next page id and whether the page holds a
+    // greater key
+    fn delete_from_leaf_page(
+        &self,
+        page: &mut Page,
+        key: &[Value],
+        rid: RID,
+    ) -> Result<(bool, PageID, bool)> {
+        let (index, next_page_id, is_last) = page.with_b_plus_tree_leaf_page(|leaf_page| {
+            let num_entries = leaf_page.num_line_pointers() as usize;
+            let is_last = num_entries > 0
+                && Value::compare_values(
+                    &leaf_page.key_at(num_entries - 1, &self.index.schema),
+                    key,
+                )
+                .map(|order| order.is_gt())
+                .unwrap_or(true);
+            (
+                leaf_page.find(key, rid, &self.index.schema),
+                leaf_page.next_page_id(),
+                is_last,
+            )
+        });
+        let index = match index {
+            Some(index) => index,
+            None => return Ok((false, next_page_id, is_last)),
+        };
+        let entry =
+            page.with_b_plus_tree_leaf_page_mut(|leaf_page| leaf_page.remove_entry_at(index));
+        let lsn = self.append_log(LogRecordBody::DeleteFromBPlusTreeLeafPage(
+            BPlusTreePageEntry {
+                page_id: page.page_id(),
+                index: index as u32,
+                entry: entry.into(),
+            },
+        ))?;
+        page.set_lsn(lsn);
+        Ok((true, next_page_id, is_last))
+    }
+    // builds the tree bottom-up from the entries, the tree must be empty.
+    // only the keys of the live rows have to be unique, the other entries are versions which
+    // some snapshots still see
+    pub fn bulk_load(
+        &self,
+        entries: Vec<(Vec<Value>, RID)>,
+        live_rids: &HashSet<RID>,
+    ) -> Result<()> {
+        let mut page_ids = vec![];
+        let result = self.build(entries, live_rids, &mut page_ids);
+        if result.is_err() {
+            // the root page belongs to the caller
+            for page_id in page_ids {
+                if page_id != self.index.first_page_id {
+                    self.buffer_pool_manager
+                        .lock()
+                        .map_err(|_| anyhow::anyhow!("lock error"))?
+                        .delete_page(page_id)?;
+                }
+            }
+        }
+        result
+    }
+    fn build(
+        &self,
+        mut entries: Vec<(Vec<Value>, RID)>,
+        live_rids: &HashSet<RID>,
+        page_ids: &mut Vec<PageID>,
+    ) -> Result<()> {
+        entries.sort_by(|a, b| {
+            Value::compare_values(&a.0, &b.0)
+                .unwrap_or(Ordering::Equal)
+                .then(a.1.cmp(&b.1))
+        });
+        let mut sizes = vec![];
+        for (key, _) in entries.iter() {
+            let size = BPlusTreeLeafPage::entry_size(key);
+            if size > BPlusTreeLeafPage::max_entry_size() {
+                return Err(anyhow::anyhow!("index key is too large"));
+            }
+            sizes.push(size);
+        }
+        // the entries are sorted, so the duplicates are found group by group while the leaf
+        // pages are built
+        let mut last_live_key: Option<&Vec<Value>> = None;
+        let mut check_unique = |group: &Range<usize>| -> Result<()> {
+            if !self.index.is_unique {
+                return Ok(());
+            }
+            for (key, rid) in entries[group.clone()].iter() {
+                if !live_rids.contains(rid) || key.iter().any(|value| value.is_null_value()) {
+                    continue;
+                }
+                if let Some(last_live_key) = last_live_key {
+                    if Value::compare_values(last_live_key, key).unwrap_or(Ordering::Equal)
+                        == Ordering::Equal
+                    {
+                        return Err(anyhow::anyhow!(
+                            "could not create unique index {}: table {} contains duplicated values",
+                            self.index.name,
+                            self.index.table_name
+                        ));
+                    }
+                }
+                last_live_key = Some(key);
+            }
+            Ok(())
+        };
+        let groups = group_by_size(&sizes, BPlusTreeLeafPage::capacity());
+        if groups.len() <= 1 {
+            check_unique(&(0..entries.len()))?;
+            let root_page_id = self.index.first_page_id;
+            let root_page = self.fetch_page(root_page_id)?;
+            root_page
+                .write()
+                .map_err(|_| anyhow::anyhow!("write error"))?
+                .with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                    for (key, rid) in entries.iter() {
+                        leaf_page.insert(key, *rid, &self.index.schema);
+                    }
+                });
+            self.unpin_page(root_page_id, true)?;
+            return self.log_page_image(root_page_id);
+        }
+
+        // pages are logged as images once the whole tree is built
+        let mut children = vec![];
+        let mut prev_page_id = INVALID_PAGE_ID;
+        for group in groups {
+            check_unique(&group)?;
+            let page = self.new_page(B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE)?;
+            let page_id = page
+                .write()
+                .map_err(|_| anyhow::anyhow!("write error"))?
+                .with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                    for (key, rid) in entries[group.clone()].iter() {
+                        leaf_page.insert(key, *rid, &self.index.schema);
+                    }
+                    leaf_page.set_prev_page_id(prev_page_id);
+                    leaf_page.page_id()
+                });
+            page_ids.push(page_id);
+            self.unpin_page(page_id, true)?;
+            if prev_page_id != INVALID_PAGE_ID {
+                let prev_page = self.fetch_page(prev_page_id)?;
+                prev_page
+                    .write()
+                    .map_err(|_| anyhow::anyhow!("write error"))?
+                    .with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                        leaf_page.set_next_page_id(page_id)
+                    });
+                self.unpin_page(prev_page_id, true)?;
+            }
+            children.push((entries[group.start].0.clone(), page_id));
+            prev_page_id = page_id;
+        }
+
+        loop {
+            let sizes = children
+                .iter()
+                .map(|(key, _)| BPlusTreeInternalPage::entry_size(key))
+                .collect::<Vec<_>>();
+            let groups = group_by_size(&sizes, BPlusTreeInternalPage::capacity());
+            let is_root = groups.len() == 1;
+            let mut parents = vec![];
+            for group in groups {
+                let page = if is_root {
+                    let root_page = self.fetch_page(self.index.first_page_id)?;
+                    *root_page
+                        .write()
+                        .map_err(|_| anyhow::anyhow!("write error"))? = Page::BPlusTreeInternal(
+                        BPlusTreeInternalPage::new_empty(self.index.first_page_id, INVALID_PAGE_ID),
+                    );
+                    root_page
+                } else {
+                    self.new_page(B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE)?
+                };
+                let page_id = page
+                    .write()
+                    .map_err(|_| anyhow::anyhow!("write error"))?
+                    .with_b_plus_tree_internal_page_mut(|internal_page| {
+                        for (key, child_page_id) in children[group.clone()].iter() {
+                            internal_page.push(key, *child_page_id);
+                        }
+                        internal_page.page_id()
+                    });
+                page_ids.push(page_id);
+                self.unpin_page(page_id, true)?;
+                // not logged, the children are not in the log until their images
+                for (_, child_page_id) in children[group.clone()].iter() {
+                    let child_page = self.fetch_page(*child_page_id)?;
+                    match &mut *child_page
+                        .write()
+                        .map_err(|_| anyhow::anyhow!("write error"))?
+                    {
+                        Page::BPlusTreeLeaf(leaf_page) => leaf_page.set_parent_page_id(page_id),
+                        Page::BPlusTreeInternal(internal_page) => {
+                            internal_page.set_parent_page_id(page_id)
+                        }
+                        _ => return Err(anyhow::anyhow!("invalid index page")),
+                    }
+                    self.unpin_page(*child_page_id, true)?;
+                }
+                parents.push((children[group.start].0.clone(), page_id));
+            }
+            if is_root {
+                break;
+            }
+            children = parents;
+        }
+        for page_id in page_ids.iter() {
+            self.log_page_image(*page_id)?;
+        }
+        Ok(())
+    }
+    // returns the risen key and the new right page if the page was split
+    fn insert_into_subtree(
+        &self,
+        page_id: PageID,
+        page_guard: RwLockWriteGuard<'_, Page>,
+        release_ancestors: &mut dyn FnMut(),
+        key: &[Value],
+        rid: RID,
+    ) -> Result<Option<(Vec<Value>, PageID)>> {
+        let mut page_guard = Some(page_guard);
+        let child_page_id = match page_guard.as_deref() {
+            Some(Page::BPlusTreeLeaf(_)) => None,
+            Some(Page::BPlusTreeInternal(internal_page)) => {
+                Some(internal_page.value_at(internal_page.key_index(key, &self.index.schema)))
+            }
+            _ => return Err(anyhow::anyhow!("invalid index page")),
+        };
+        let split = match child_page_id {
+            None => {
+                let page_guard = page_guard
+                    .as_deref_mut()
+                    .ok_or_else(|| anyhow::anyhow!("index page is not latched"))?;
+                self.insert_into_leaf_page(page_guard, key, rid)?
+            }
+            Some(child_page_id) => {
+                let child_page = self.fetch_page(child_page_id)?;
+                let child_page_guard = child_page
+                    .write()
+                    .map_err(|_| anyhow::anyhow!("write error"))?;
+                if self.is_safe(&child_page_guard, key) {
+                    page_guard = None;
+                    release_ancestors();
+                }
+                let child_split = self.insert_into_subtree(
+                    child_page_id,
+                    child_page_guard,
+                    &mut || {
+                        page_guard.take();
+                        release_ancestors();
+                    },
+                    key,
+                    rid,
+                )?;
+                match child_split {
+                    // the latch is still held, because the child was not safe
+                    Some((risen_key, right_page_id)) => {
+                        let page_guard = page_guard
+                            .as_deref_mut()
+                            .ok_or_else(|| anyhow::anyhow!("index page is not latched"))?;
+                        self.insert_into_internal_page(
+                            page_guard,
+                            child_page_id,
+                            &risen_key,
+                            right_page_id,
+                        )?
+                    }
+                    None => {
+                        drop(page_guard);
+                        self.unpin_page(page_id, false)?;
+                        return Ok(None);
+                    }
+                }
+            }
+        };
+        let split = match (split, page_guard.as_deref_mut()) {
+            (Some((risen_key, right_page_id)), Some(root_page))
+                if page_id == self.index.first_page_id =>
+            {
+                self.split_root_page(root_page, &risen_key, right_page_id)?;
+                None
+            }
+            (split, _) => split,
+        };
+        drop(page_guard);
+        self.unpin_page(page_id, true)?;
+        Ok(split)
+    }
+    // a safe page does not split, whatever is inserted into it. keys risen from the leaves
+    // are never larger than the largest leaf entry.
+    fn is_safe(&self, page: &Page, key: &[Value]) -> bool {
+        match page {
+            Page::BPlusTreeLeaf(leaf_page) => leaf_page.has_space_for(key),
+            Page::BPlusTreeInternal(internal_page) => {
+                internal_page.free_space() >= BPlusTreeLeafPage::max_entry_size()
+            }
+            _ => false,
+        }
+    }
+    fn insert_into_leaf_page(
+        &self,
+        page: &mut Page,
+        key: &[Value],
+        rid: RID,
+    ) -> Result<Option<(Vec<Value>, PageID)>> {
+        let index = page.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+            if leaf_page.has_space_for(key) {
+                Some(leaf_page.insert(key, rid, &self.index.schema))
+            } else {
+                None
+            }
+        });
+        match index {
+            Some(index) => {
+                self.log_leaf_page_insert(page, index)?;
+                Ok(None)
+            }
+            None => Ok(Some(self.split_leaf_page(page, key, rid)?)),
+        }
+    }
+    fn split_leaf_page(
+        &self,
+        page: &mut Page,
+        key: &[Value],
+        rid: RID,
+    ) -> Result<(Vec<Value>, PageID)> {
+        let page_id = page.page_id();
+        let new_page = self.new_page(B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE)?;
+        let mut new_page_guard = new_page
+            .write()
+            .map_err(|_| anyhow::anyhow!("write error"))?;
+        let new_page_id = new_page_guard.page_id();
+        let (risen_key, next_page_id) = page.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+            new_page_guard.with_b_plus_tree_leaf_page_mut(|new_leaf_page| {
+                let risen_key = leaf_page.split(new_leaf_page, &self.index.schema);
+                let next_page_id = leaf_page.next_page_id();
+                new_leaf_page.set_parent_page_id(leaf_page.parent_page_id());
+                new_leaf_page.set_prev_page_id(page_id);
+                new_leaf_page.set_next_page_id(next_page_id);
+                leaf_page.set_next_page_id(new_page_id);
+                (risen_key, next_page_id)
+            })
+        });
+        self.log_split(page, &mut new_page_guard)?;
+        let target_page = if Value::compare_values(key, &risen_key)
+            .map(|order| order.is_ge())
+            .unwrap_or(false)
+        {
+            &mut *new_page_guard
+        } else {
+            page
+        };
+        let index = target_page.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+            leaf_page.insert(key, rid, &self.index.schema)
+        });
+        self.log_leaf_page_insert(target_page, index)?;
+        // leaf pages are latched from left to right, as scans do
+        if next_page_id != INVALID_PAGE_ID {
+            self.set_prev_page_id(next_page_id, new_page_id)?;
+        }
+        drop(new_page_guard);
+        self.unpin_page(new_page_id, true)?;
+        Ok((risen_key, new_page_id))
+    }
+    fn insert_into_internal_page(
+        &self,
+        page: &mut Page,
+        left_page_id: PageID,
+        key: &[Value],
+        right_page_id: PageID,
+    ) -> Result<Option<(Vec<Value>, PageID)>> {
+        let index = page.with_b_plus_tree_internal_page_mut(|internal_page| {
+            if internal_page.has_space_for(key) {
+                Some(internal_page.insert_after(left_page_id, key, right_page_id))
+            } else {
+                None
+            }
+        });
+        match index {
+            Some(index) => {
+                self.log_internal_page_insert(page, index)?;
+                self.set_parent_page_id(right_page_id, page.page_id())?;
+                Ok(None)
+            }
+            None => Ok(Some(self.split_internal_page(
+                page,
+                left_page_id,
+                key,
+                right_page_id,
+            )?)),
         }
     }
-    pub fn lookup(&self, key: &Value) -> Result<Option<Vec<RID>>> {
-        let leaf_page_id = self.find_leaf_page(key)?;
-        let page = self
-            .buffer_pool_manager
+    fn split_internal_page(
+        &self,
+        page: &mut Page,
+        left_page_id: PageID,
+        key: &[Value],
+        right_page_id: PageID,
+    ) -> Result<(Vec<Value>, PageID)> {
+        let page_id = page.page_id();
+        let new_page = self.new_page(B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE)?;
+        let mut new_page_guard = new_page
+            .write()
+            .map_err(|_| anyhow::anyhow!("write error"))?;
+        let new_page_id = new_page_guard.page_id();
+        let risen_key = page.with_b_plus_tree_internal_page_mut(|internal_page| {
+            new_page_guard.with_b_plus_tree_internal_page_mut(|new_internal_page| {
+                let risen_key = internal_page.split(new_internal_page, &self.index.schema);
+                new_internal_page.set_parent_page_id(internal_page.parent_page_id());
+                risen_key
+            })
+        });
+        self.log_split(page, &mut new_page_guard)?;
+        let target_page = if new_page_guard.with_b_plus_tree_internal_page(|internal_page| {
+            internal_page.contains_value(left_page_id)
+        }) {
+            &mut *new_page_guard
+        } else {
+            page
+        };
+        let index = target_page.with_b_plus_tree_internal_page_mut(|internal_page| {
+            internal_page.insert_after(left_page_id, key, right_page_id)
+        });
+        self.log_internal_page_insert(target_page, index)?;
+        let moved_children =
+            new_page_guard.with_b_plus_tree_internal_page(|internal_page| internal_page.values());
+
+        // children are latched after their parents, as in the descent
+        self.set_parent_page_id(right_page_id, page_id)?;
+        for child_page_id in moved_children {
+            self.set_parent_page_id(child_page_id, new_page_id)?;
+        }
+        drop(new_page_guard);
+        self.unpin_page(new_page_id, true)?;
+        Ok((risen_key, new_page_id))
+    }
+    // the root keeps its page id so that the catalog never has to be updated.
+    // its entries are moved to a new page, which becomes the left child of the new root.
+    fn split_root_page(
+        &self,
+        root_page: &mut Page,
+        key: &[Value],
+        right_page_id: PageID,
+    ) -> Result<()> {
+        let root_page_id = root_page.page_id();
+        let (left_page, moved_children) = match root_page {
+            Page::BPlusTreeLeaf(root_leaf_page) => {
+                let left_page = self.new_page(B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE)?;
+                left_page
+                    .write()
+                    .map_err(|_| anyhow::anyhow!("write error"))?
+                    .with_b_plus_tree_leaf_page_mut(|left_leaf_page| {
+                        root_leaf_page.move_all_to(left_leaf_page);
+                        left_leaf_page.set_parent_page_id(root_page_id);
+                        left_leaf_page.set_next_page_id(right_page_id);
+                    });
+                (left_page, vec![])
+            }
+            Page::BPlusTreeInternal(root_internal_page) => {
+                let left_page = self.new_page(B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE)?;
+                let moved_children = left_page
+                    .write()
+                    .map_err(|_| anyhow::anyhow!("write error"))?
+                    .with_b_plus_tree_internal_page_mut(|left_internal_page| {
+                        root_internal_page.move_all_to(left_internal_page);
+                        left_internal_page.set_parent_page_id(root_page_id);
+                        left_internal_page.values()
+                    });
+                (left_page, moved_children)
+            }
+            _ => return Err(anyhow::anyhow!("invalid index page")),
+        };
+        let mut left_page_guard = left_page
+            .write()
+            .map_err(|_| anyhow::anyhow!("write error"))?;
+        let left_page_id = left_page_guard.page_id();
+        let is_leaf = left_page_guard.is_b_plus_tree_leaf();
+        *root_page = Page::BPlusTreeInternal(BPlusTreeInternalPage::new(
+            root_page_id,
+            INVALID_PAGE_ID,
+            left_page_id,
+            right_page_id,
+            Tuple::temp_tuple(key),
+        ));
+        let lsn = self.append_log(LogRecordBody::NewBPlusTreeRootPage(NewBPlusTreeRootPage {
+            page_id: root_page_id,
+            data: root_page.data().into(),
+            left_page_id,
+            left_data: left_page_guard.data().into(),
+        }))?;
+        root_page.set_lsn(lsn);
+        left_page_guard.set_lsn(lsn);
+
+        if is_leaf {
+            self.set_prev_page_id(right_page_id, left_page_id)?;
+        }
+        self.set_parent_page_id(right_page_id, root_page_id)?;
+        for child_page_id in moved_children {
+            self.set_parent_page_id(child_page_id, left_page_id)?;
+        }
+        drop(left_page_guard);
+        self.unpin_page(left_page_id, true)
+    }
+    fn set_parent_page_id(&self, page_id: PageID, parent_page_id: PageID) -> Result<()> {
+        let page = self.fetch_page(page_id)?;
+        let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("write error"))?;
+        match &mut *page_guard {
+            Page::BPlusTreeLeaf(leaf_page) => leaf_page.set_parent_page_id(parent_page_id),
+            Page::BPlusTreeInternal(internal_page) => {
+                internal_page.set_parent_page_id(parent_page_id)
+            }
+            _ => return Err(anyhow::anyhow!("invalid index page")),
+        }
+        let lsn = self.append_log(LogRecordBody::SetBPlusTreeParentPageID(
+            SetBPlusTreeParentPageID {
+                page_id,
+                parent_page_id,
+            },
+        ))?;
+        page_guard.set_lsn(lsn);
+        drop(page_guard);
+        self.unpin_page(page_id, true)
+    }
+    fn set_prev_page_id(&self, page_id: PageID, prev_page_id: PageID) -> Result<()> {
+        let page = self.fetch_page(page_id)?;
+        let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("write error"))?;
+        page_guard
+            .with_b_plus_tree_leaf_page_mut(|leaf_page| leaf_page.set_prev_page_id(prev_page_id));
+        let lsn = self.append_log(LogRecordBody::SetBPlusTreePrevPageID(
+            SetBPlusTreePrevPageID {
+                page_id,
+                prev_page_id,
+            },
+        ))?;
+        page_guard.set_lsn(lsn);
+        drop(page_guard);
+        self.unpin_page(page_id, true)
+    }
+    fn log_leaf_page_insert(&self, page: &mut Page, index: usize) -> Result<()> {
+        let entry = page.with_b_plus_tree_leaf_page(|leaf_page| leaf_page.entry_at(index));
+        let lsn = self.append_log(LogRecordBody::InsertToBPlusTreeLeafPage(
+            BPlusTreePageEntry {
+                page_id: page.page_id(),
+                index: index as u32,
+                entry: entry.into(),
+            },
+        ))?;
+        page.set_lsn(lsn);
+        Ok(())
+    }
+    fn log_internal_page_insert(&self, page: &mut Page, index: usize) -> Result<()> {
+        let entry =
+            page.with_b_plus_tree_internal_page(|internal_page| internal_page.entry_at(index));
+        let lsn = self.append_log(LogRecordBody::InsertToBPlusTreeInternalPage(
+            BPlusTreePageEntry {
+                page_id: page.page_id(),
+                index: index as u32,
+                entry: entry.into(),
+            },
+        ))?;
+        page.set_lsn(lsn);
+        Ok(())
+    }
+    fn log_split(&self, page: &mut Page, new_page: &mut Page) -> Result<()> {
+        let lsn = self.append_log(LogRecordBody::SplitBPlusTreePage(SplitBPlusTreePage {
+            page_id: page.page_id(),
+            data: page.data().into(),
+            new_page_id: new_page.page_id(),
+            new_data: new_page.data().into(),
+        }))?;
+        page.set_lsn(lsn);
+        new_page.set_lsn(lsn);
+        Ok(())
+    }
+    fn log_page_image(&self, page_id: PageID) -> Result<()> {
+        let page = self.fetch_page(page_id)?;
+        let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("write error"))?;
+        let lsn = self.append_log(LogRecordBody::LoadBPlusTreePage(LoadBPlusTreePage {
+            page_id,
+            data: page_guard.data().into(),
+        }))?;
+        page_guard.set_lsn(lsn);
+        drop(page_guard);
+        self.unpin_page(page_id, true)
+    }
+    fn append_log(&self, body: LogRecordBody) -> Result<LSN> {
+        self.log_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .fetch_page(leaf_page_id)?;
-        let rid = page
+            .append(self.txn_id, body)
+    }
+    // for scans, descends to the leftmost leaf page that may contain the key and reads it with f.
+    // the latch of a page is released only after the latch of its child is acquired, and the
+    // latch of the leaf only after f returns
+    fn find_leaf_page<F, T>(&self, key: Option<&[Value]>, leftmost: bool, f: F) -> Result<T>
+    where
+        F: FnOnce(&BPlusTreeLeafPage) -> T,
+    {
+        let root_page_id = self.index.first_page_id;
+        let root_page = self.fetch_page(root_page_id)?;
+        let root_page_guard = root_page
             .read()
-            .map_err(|_| anyhow::anyhow!("read error"))?
-            .with_b_plus_tree_leaf_page(|leaf_page| {
-                leaf_page.lookup(&[key.clone()], &self.index.schema)
-            });
-        Ok(rid)
+            .map_err(|_| anyhow::anyhow!("read error"))?;
+        self.find_leaf_page_in_subtree(root_page_id, root_page_guard, key, leftmost, f)
     }
-    pub fn insert(&self, key: &Value, rid: RID) -> Result<()> {
-        self.insert_into_leaf_page(key, rid)?;
-        Ok(())
+    fn find_leaf_page_in_subtree<F, T>(
+        &self,
+        page_id: PageID,
+        page_guard: RwLockReadGuard<'_, Page>,
+        key: Option<&[Value]>,
+        leftmost: bool,
+        f: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(&BPlusTreeLeafPage) -> T,
+    {
+        let child_page_id = match &*page_guard {
+            Page::BPlusTreeLeaf(_) => None,
+            Page::BPlusTreeInternal(internal_page) => {
+                let index = match key {
+                    Some(key) if leftmost => internal_page.key_index_lower(key, &self.index.schema),
+                    Some(key) => internal_page.key_index(key, &self.index.schema),
+                    None => 0,
+                };
+                Some(internal_page.value_at(index))
+            }
+            _ => return Err(anyhow::anyhow!("invalid index page")),
+        };
+        let child_page_id = match child_page_id {
+            Some(child_page_id) => child_page_id,
+            None => {
+                let result = page_guard.with_b_plus_tree_leaf_page(f);
+                drop(page_guard);
+                self.unpin_page(page_id, false)?;
+                return Ok(result);
+            }
+        };
+        let child_page = self.fetch_page(child_page_id)?;
+        let child_page_guard = child_page
+            .read()
+            .map_err(|_| anyhow::anyhow!("read error"))?;
+        drop(page_guard);
+        self.unpin_page(page_id, false)?;
+        self.find_leaf_page_in_subtree(child_page_id, child_page_guard, key, leftmost, f)
     }
-    fn insert_into_leaf_page(&self, key: &Value, rid: RID) -> Result<()> {
-        let leaf_page_id = self.find_leaf_page(key)?;
-        let page = self
-            .buffer_pool_manager
+    fn fetch_page(&self, page_id: PageID) -> Result<Arc<RwLock<Page>>> {
+        self.buffer_pool_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .fetch_page(leaf_page_id)?;
-        // TODO: write WAL
-        page.write()
-            .map_err(|_| anyhow::anyhow!("write error"))?
-            .with_b_plus_tree_leaf_page_mut(|leaf_page| {
-                // TODO: full check
-                leaf_page.insert(&[key.clone()], rid, &self.index.schema)
-            });
+            .fetch_page(page_id)
+    }
+    fn new_page(&self, page_type: PageType) -> Result<Arc<RwLock<Page>>> {
         self.buffer_pool_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .unpin_page(leaf_page_id, true)?;
-        Ok(())
+            .new_page(page_type)
+    }
+    fn unpin_page(&self, page_id: PageID, is_dirty: bool) -> Result<()> {
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(page_id, is_dirty)
+    }
+}
+
+// bounds are compared with the leading columns of the keys, so a bound can be a prefix of the key
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IndexRange {
+    pub start: Bound<Vec<Value>>,
+    pub end: Bound<Vec<Value>>,
+}
+impl IndexRange {
+    pub fn full() -> Self {
+        Self {
+            start: Bound::Unbounded,
+            end: Bound::Unbounded,
+        }
+    }
+    pub fn is_before_start(&self, key: &[Value]) -> Result<bool> {
+        match &self.start {
+            Bound::Included(start) => Ok(Value::compare_values(key, start)?.is_lt()),
+            Bound::Excluded(start) => Ok(Value::compare_values(key, start)?.is_le()),
+            Bound::Unbounded => Ok(false),
+        }
+    }
+    pub fn is_after_end(&self, key: &[Value]) -> Result<bool> {
+        match &self.end {
+            Bound::Included(end) => Ok(Value::compare_values(key, end)?.is_gt()),
+            Bound::Excluded(end) => Ok(Value::compare_values(key, end)?.is_ge()),
+            Bound::Unbounded => Ok(false),
+        }
+    }
+    // returns None if the ranges do not overlap
+    pub fn intersect(&self, other: &IndexRange) -> Result<Option<IndexRange>> {
+        let start = match (&self.start, &other.start) {
+            (Bound::Unbounded, start) | (start, Bound::Unbounded) => start.clone(),
+            (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => {
+                match Value::compare_values(a, b)? {
+                    Ordering::Less => other.start.clone(),
+                    Ordering::Greater => self.start.clone(),
+                    Ordering::Equal if matches!(self.start, Bound::Excluded(_)) => {
+                        self.start.clone()
+                    }
+                    Ordering::Equal => other.start.clone(),
+                }
+            }
+        };
+        let end = match (&self.end, &other.end) {
+            (Bound::Unbounded, end) | (end, Bound::Unbounded) => end.clone(),
+            (Bound::Included(a) | Bound::Excluded(a), Bound::Included(b) | Bound::Excluded(b)) => {
+                match Value::compare_values(a, b)? {
+                    Ordering::Less => self.end.clone(),
+                    Ordering::Greater => other.end.clone(),
+                    Ordering::Equal if matches!(self.end, Bound::Excluded(_)) => self.end.clone(),
+                    Ordering::Equal => other.end.clone(),
+                }
+            }
+        };
+        let is_empty = match (&start, &end) {
+            (Bound::Included(start), Bound::Included(end)) => {
+                Value::compare_values(start, end)?.is_gt()
+            }
+            (
+                Bound::Included(start) | Bound::Excluded(start),
+                Bound::Included(end) | Bound::Excluded(end),
+            ) => Value::compare_values(start, end)?.is_ge(),
+            _ => false,
+        };
+        if is_empty {
+            return Ok(None);
+        }
+        Ok(Some(IndexRange { start, end }))
     }
-    fn find_leaf_page(&self, key: &Value) -> Result<PageID> {
-        let page_id = self.index.first_page_id;
+}
+
+// iterates the entries in the range in key order by following the leaf pages
+pub struct IndexIterator {
+    buffer_pool_manager: Arc<Mutex<BufferPoolManager>>,
+    schema: Schema,
+    range: IndexRange,
+    next_page_id: PageID,
+    entries: Vec<(Vec<Value>, RID)>,
+    cursor: usize,
+}
+impl Iterator for IndexIterator {
+    type Item = Result<(Vec<Value>, RID)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+impl IndexIterator {
+    fn next_entry(&mut self) -> Result<Option<(Vec<Value>, RID)>> {
         loop {
+            if self.cursor < self.entries.len() {
+                let (key, rid) = &self.entries[self.cursor];
+                self.cursor += 1;
+                if self.range.is_before_start(key)? {
+                    continue;
+                }
+                if self.range.is_after_end(key)? {
+                    self.entries.clear();
+                    self.next_page_id = INVALID_PAGE_ID;
+                    return Ok(None);
+                }
+                return Ok(Some((key.clone(), *rid)));
+            }
+            if self.next_page_id == INVALID_PAGE_ID {
+                return Ok(None);
+            }
+            let page_id = self.next_page_id;
             let page = self
                 .buffer_pool_manager
                 .lock()
                 .map_err(|_| anyhow::anyhow!("lock error"))?
                 .fetch_page(page_id)?;
-            if page
+            // the pages after the first leaf are never the root, so they stay leaf pages
+            (self.entries, self.next_page_id) = page
                 .read()
                 .map_err(|_| anyhow::anyhow!("read error"))?
-                .is_b_plus_tree_leaf()
-            {
-                self.buffer_pool_manager
-                    .lock()
-                    .map_err(|_| anyhow::anyhow!("lock error"))?
-                    .unpin_page(page_id, false)?;
-                return Ok(page_id);
-            }
-            // TODO: lookup internal page
+                .with_b_plus_tree_leaf_page(|leaf_page| read_leaf_page(leaf_page, &self.schema));
+            self.cursor = 0;
             self.buffer_pool_manager
                 .lock()
                 .map_err(|_| anyhow::anyhow!("lock error"))?
@@ -155,3 +1037,436 @@ impl IndexManager {
         }
     }
 }
+
+// the entries and the next page id of a leaf page
+fn read_leaf_page(
+    leaf_page: &BPlusTreeLeafPage,
+    schema: &Schema,
+) -> (Vec<(Vec<Value>, RID)>, PageID) {
+    let entries = (0..leaf_page.num_line_pointers() as usize)
+        .map(|i| (leaf_page.key_at(i, schema), leaf_page.value_at(i)))
+        .collect::<Vec<_>>();
+    (entries, leaf_page.next_page_id())
+}
+
+// splits the entries into consecutive groups that each fit in a page
+fn group_by_size(sizes: &[usize], capacity: usize) -> Vec<Range<usize>> {
+    let mut groups = vec![];
+    let mut start = 0;
+    let mut size = 0;
+    for (i, entry_size) in sizes.iter().enumerate() {
+        if size + entry_size > capacity {
+            groups.push(start..i);
+            start = i;
+            size = 0;
+        }
+        size += entry_size;
+    }
+    if start < sizes.len() {
+        groups.push(start..sizes.len());
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use tempfile::tempdir;
+
+    use crate::{
+        catalog::{Column, DataType},
+        common::PAGE_SIZE,
+        disk::DiskManager,
+        log::LogManager,
+        value::{integer::IntegerValue, varchar::VarcharValue},
+    };
+
+    use super::*;
+
+    fn setup_index_manager(dir: &std::path::Path, data_type: DataType) -> Result<IndexManager> {
+        setup_index_manager_with_pool_size(dir, data_type, 32)
+    }
+    fn setup_index_manager_with_pool_size(
+        dir: &std::path::Path,
+        data_type: DataType,
+        pool_size: usize,
+    ) -> Result<IndexManager> {
+        let disk_manager = DiskManager::new(dir.join("data").to_str().unwrap())?;
+        let log_manager = Arc::new(Mutex::new(LogManager::new(
+            dir.join("log").to_str().unwrap(),
+        )?));
+        let buffer_pool_manager = Arc::new(Mutex::new(BufferPoolManager::new(
+            disk_manager,
+            log_manager.clone(),
+            pool_size,
+        )));
+        let root_page = buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .new_page(B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE)?;
+        let root_page_id = root_page
+            .read()
+            .map_err(|_| anyhow::anyhow!("read error"))?
+            .page_id();
+        buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(root_page_id, true)?;
+        let index = Index {
+            id: 1,
+            name: "idx".to_string(),
+            table_name: "t".to_string(),
+            first_page_id: root_page_id,
+            columns: vec!["c".to_string()],
+            schema: Schema {
+                columns: vec![Column {
+                    name: "c".to_string(),
+                    data_type,
+                }],
+            },
+            is_unique: false,
+            is_primary: false,
+        };
+        Ok(IndexManager::new(
+            index,
+            buffer_pool_manager,
+            log_manager,
+            TransactionID(1),
+        ))
+    }
+
+    // collects all keys by following the leaf chain from the leftmost leaf page
+    fn scan_keys(index_manager: &IndexManager) -> Result<Vec<Vec<Value>>> {
+        let mut page_id = index_manager.index.first_page_id;
+        loop {
+            let page = index_manager.fetch_page(page_id)?;
+            let child_page_id = match &*page.read().map_err(|_| anyhow::anyhow!("read error"))? {
+                Page::BPlusTreeInternal(internal_page) => Some(internal_page.value_at(0)),
+                _ => None,
+            };
+            index_manager.unpin_page(page_id, false)?;
+            match child_page_id {
+                Some(child_page_id) => page_id = child_page_id,
+                None => break,
+            }
+        }
+        let mut keys = vec![];
+        while page_id != INVALID_PAGE_ID {
+            let page = index_manager.fetch_page(page_id)?;
+            let next_page_id = page
+                .read()
+                .map_err(|_| anyhow::anyhow!("read error"))?
+                .with_b_plus_tree_leaf_page(|leaf_page| {
+                    for i in 0..leaf_page.num_line_pointers() as usize {
+                        keys.push(leaf_page.key_at(i, &index_manager.index.schema));
+                    }
+                    leaf_page.next_page_id()
+                });
+            index_manager.unpin_page(page_id, false)?;
+            page_id = next_page_id;
+        }
+        Ok(keys)
+    }
+
+    #[test]
+    fn test_insert_and_lookup_with_splits() -> Result<()> {
+        let dir = tempdir()?;
+        let index_manager = setup_index_manager(dir.path(), DataType::Integer)?;
+        let n = 5000;
+        for i in 0..n {
+            // inserts keys in a scattered order
+            let key = (i * 7919) % n;
+            index_manager.insert(
+                &[Value::Integer(IntegerValue(key))],
+                RID(PageID(1), key as u32),
+            )?;
+        }
+        for key in 0..n {
+            let rids = index_manager.lookup(&[Value::Integer(IntegerValue(key))])?;
+            assert_eq!(rids, Some(vec![RID(PageID(1), key as u32)]));
+        }
+        assert_eq!(
+            index_manager.lookup(&[Value::Integer(IntegerValue(n))])?,
+            None
+        );
+        assert_eq!(
+            index_manager.lookup(&[Value::Integer(IntegerValue(-1))])?,
+            None
+        );
+
+        let keys = scan_keys(&index_manager)?;
+        let expected = (0..n)
+            .map(|key| vec![Value::Integer(IntegerValue(key))])
+            .collect::<Vec<_>>();
+        assert_eq!(keys, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_duplicate_keys_across_leaf_pages() -> Result<()> {
+        let dir = tempdir()?;
+        let index_manager = setup_index_manager(dir.path(), DataType::Integer)?;
+        for i in 0..1000u32 {
+            index_manager.insert(
+                &[Value::Integer(IntegerValue((i % 3) as i64))],
+                RID(PageID(1), i),
+            )?;
+        }
+        for key in 0..3 {
+            let mut rids = index_manager
+                .lookup(&[Value::Integer(IntegerValue(key))])?
+                .unwrap();
+            rids.sort();
+            let expected = (0..1000)
+                .filter(|i| (i % 3) as i64 == key)
+                .map(|i| RID(PageID(1), i))
+                .collect::<Vec<_>>();
+            assert_eq!(rids, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_internal_page_splits() -> Result<()> {
+        let dir = tempdir()?;
+        let index_manager = setup_index_manager(dir.path(), DataType::Varchar)?;
+        // long keys make internal pages split as well
+        let key = |i: u32| Value::Varchar(VarcharValue(format!("{:0>200}", (i * 31) % 1000)));
+        for i in 0..1000 {
+            index_manager.insert(&[key(i)], RID(PageID(1), i))?;
+        }
+        for i in 0..1000 {
+            assert_eq!(
+                index_manager.lookup(&[key(i)])?,
+                Some(vec![RID(PageID(1), i)])
+            );
+        }
+        let keys = scan_keys(&index_manager)?;
+        assert_eq!(keys.len(), 1000);
+        assert!(keys
+            .windows(2)
+            .all(|w| Value::compare_values(&w[0], &w[1]).unwrap().is_lt()));
+
+        let too_large_key = Value::Varchar(VarcharValue("a".repeat(PAGE_SIZE)));
+        assert!(index_manager
+            .insert(&[too_large_key], RID(PageID(1), 0))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_insert_and_lookup() -> Result<()> {
+        let dir = tempdir()?;
+        let index_manager = Arc::new(setup_index_manager_with_pool_size(
+            dir.path(),
+            DataType::Varchar,
+            128,
+        )?);
+        // long keys make internal pages split as well
+        let key = |i: u32| Value::Varchar(VarcharValue(format!("{:0>200}", i)));
+        let thread_count = 8;
+        let handles = (0..thread_count)
+            .map(|t| {
+                let index_manager = index_manager.clone();
+                thread::spawn(move || -> Result<()> {
+                    // keys of the threads are interleaved, so that they contend for the same pages
+                    for i in (t..2000).step_by(thread_count as usize) {
+                        index_manager.insert(&[key(i)], RID(PageID(1), i))?;
+                        assert_eq!(
+                            index_manager.lookup(&[key(i)])?,
+                            Some(vec![RID(PageID(1), i)])
+                        );
+                        let prev_key = key(i.saturating_sub(thread_count));
+                        assert!(index_manager.lookup(&[prev_key])?.is_some());
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle
+                .join()
+                .map_err(|_| anyhow::anyhow!("thread error"))??;
+        }
+
+        let keys = scan_keys(&index_manager)?;
+        assert_eq!(keys, (0..2000).map(|i| vec![key(i)]).collect::<Vec<_>>());
+        check_parent_page_ids(&index_manager, index_manager.index.first_page_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_scan_and_delete_during_root_split() -> Result<()> {
+        let key = |i: u32| Value::Varchar(VarcharValue(format!("{:0>200}", i)));
+        for _ in 0..20 {
+            let dir = tempdir()?;
+            let index_manager = Arc::new(setup_index_manager_with_pool_size(
+                dir.path(),
+                DataType::Varchar,
+                128,
+            )?);
+            let reader_count = 4;
+            for i in 0..reader_count {
+                index_manager.insert(&[key(i)], RID(PageID(1), i))?;
+            }
+            // the root is still a leaf page when the readers start
+            let writer = {
+                let index_manager = index_manager.clone();
+                thread::spawn(move || -> Result<()> {
+                    for i in reader_count..200 {
+                        index_manager.insert(&[key(i)], RID(PageID(1), i))?;
+                    }
+                    Ok(())
+                })
+            };
+            let readers = (0..reader_count)
+                .map(|t| {
+                    let index_manager = index_manager.clone();
+                    thread::spawn(move || -> Result<()> {
+                        for _ in 0..50 {
+                            assert_eq!(
+                                index_manager.lookup(&[key(t)])?,
+                                Some(vec![RID(PageID(1), t)])
+                            );
+                            assert!(index_manager.delete(&[key(t)], RID(PageID(1), t))?);
+                            index_manager.insert(&[key(t)], RID(PageID(1), t))?;
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in readers.into_iter().chain([writer]) {
+                handle
+                    .join()
+                    .map_err(|_| anyhow::anyhow!("thread error"))??;
+            }
+
+            let keys = scan_keys(&index_manager)?;
+            assert_eq!(keys, (0..200).map(|i| vec![key(i)]).collect::<Vec<_>>());
+        }
+        Ok(())
+    }
+
+    fn check_parent_page_ids(index_manager: &IndexManager, page_id: PageID) -> Result<()> {
+        let page = index_manager.fetch_page(page_id)?;
+        let children = match &*page.read().map_err(|_| anyhow::anyhow!("read error"))? {
+            Page::BPlusTreeInternal(internal_page) => internal_page.values(),
+            _ => vec![],
+        };
+        index_manager.unpin_page(page_id, false)?;
+        for child_page_id in children {
+            let child_page = index_manager.fetch_page(child_page_id)?;
+            let parent_page_id = match &*child_page
+                .read()
+                .map_err(|_| anyhow::anyhow!("read error"))?
+            {
+                Page::BPlusTreeLeaf(leaf_page) => leaf_page.parent_page_id(),
+                Page::BPlusTreeInternal(internal_page) => internal_page.parent_page_id(),
+                _ => INVALID_PAGE_ID,
+            };
+            index_manager.unpin_page(child_page_id, false)?;
+            assert_eq!(parent_page_id, page_id);
+            check_parent_page_ids(index_manager, child_page_id)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bulk_load() -> Result<()> {
+        let dir = tempdir()?;
+        let index_manager = setup_index_manager(dir.path(), DataType::Varchar)?;
+        let key = |i: u32| Value::Varchar(VarcharValue(format!("{:0>200}", i)));
+        let entries = (0..1000)
+            .rev()
+            .map(|i| (vec![key(i / 2)], RID(PageID(1), i)))
+            .collect::<Vec<_>>();
+        index_manager.bulk_load(entries, &HashSet::new())?;
+        for i in 0..500 {
+            assert_eq!(
+                index_manager.lookup(&[key(i)])?,
+                Some(vec![RID(PageID(1), i * 2), RID(PageID(1), i * 2 + 1)])
+            );
+        }
+        index_manager.insert(&[key(1000)], RID(PageID(1), 1000))?;
+        index_manager.insert(&[key(0)], RID(PageID(1), 1001))?;
+        let keys = scan_keys(&index_manager)?;
+        assert_eq!(keys.len(), 1002);
+        assert!(keys
+            .windows(2)
+            .all(|w| Value::compare_values(&w[0], &w[1]).unwrap().is_le()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_scan() -> Result<()> {
+        let dir = tempdir()?;
+        let index_manager = setup_index_manager(dir.path(), DataType::Integer)?;
+        for i in (0..3000).rev() {
+            index_manager.insert(
+                &[Value::Integer(IntegerValue(i / 3))],
+                RID(PageID(1), i as u32),
+            )?;
+        }
+        let key = |i: i64| vec![Value::Integer(IntegerValue(i))];
+        let scan = |start, end| -> Result<Vec<i64>> {
+            let mut keys = vec![];
+            for entry in index_manager.scan(&IndexRange { start, end })? {
+                if let Value::Integer(IntegerValue(key)) = entry?.0[0] {
+                    keys.push(key);
+                }
+            }
+            Ok(keys)
+        };
+        let expected = |range: Range<i64>| range.flat_map(|i| [i, i, i]).collect::<Vec<_>>();
+
+        assert_eq!(scan(Bound::Unbounded, Bound::Unbounded)?, expected(0..1000));
+        assert_eq!(
+            scan(Bound::Included(key(100)), Bound::Excluded(key(200)))?,
+            expected(100..200)
+        );
+        assert_eq!(
+            scan(Bound::Excluded(key(100)), Bound::Included(key(200)))?,
+            expected(101..201)
+        );
+        assert_eq!(
+            scan(Bound::Unbounded, Bound::Excluded(key(500)))?,
+            expected(0..500)
+        );
+        assert_eq!(
+            scan(Bound::Excluded(key(998)), Bound::Unbounded)?,
+            expected(999..1000)
+        );
+        assert_eq!(
+            scan(Bound::Included(key(2000)), Bound::Unbounded)?,
+            expected(0..0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_range_intersect() -> Result<()> {
+        let key = |i: i64| vec![Value::Integer(IntegerValue(i))];
+        let range = |start, end| IndexRange { start, end };
+
+        let intersected = range(Bound::Included(key(1)), Bound::Unbounded)
+            .intersect(&range(Bound::Unbounded, Bound::Excluded(key(5))))?;
+        assert_eq!(
+            intersected,
+            Some(range(Bound::Included(key(1)), Bound::Excluded(key(5))))
+        );
+        let intersected = range(Bound::Included(key(1)), Bound::Included(key(5)))
+            .intersect(&range(Bound::Excluded(key(1)), Bound::Included(key(8))))?;
+        assert_eq!(
+            intersected,
+            Some(range(Bound::Excluded(key(1)), Bound::Included(key(5))))
+        );
+        let intersected = range(Bound::Included(key(5)), Bound::Included(key(5)))
+            .intersect(&range(Bound::Unbounded, Bound::Excluded(key(5))))?;
+        assert_eq!(intersected, None);
+        let intersected = range(Bound::Included(key(6)), Bound::Unbounded)
+            .intersect(&range(Bound::Unbounded, Bound::Included(key(5))))?;
+        assert_eq!(intersected, None);
+        Ok(())
+    }
+}
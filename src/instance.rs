@@ -1,25 +1,34 @@
 use std::{
     fs,
     sync::{Arc, Mutex, RwLock},
+    time::Duration,
 };
 
 use anyhow::Result;
 
 use crate::{
+    autovacuum::{StatisticsCollector, VacuumCost},
     binder::Binder,
     buffer::BufferPoolManager,
-    catalog::{Catalog, Column, Schema},
-    common::TransactionID,
+    catalog::{Catalog, Column, DataType, Schema},
+    common::{TransactionID, INVALID_LSN, INVALID_PAGE_ID, INVALID_TRANSACTION_ID, LSN},
     concurrency::{IsolationLevel, TransactionManager},
+    control::ControlFile,
     disk::DiskManager,
     executor::{ExecutorContext, ExecutorEngine},
-    lock::LockManager,
-    log::LogManager,
+    index::IndexManager,
+    lock::{LockManager, LockMode},
+    log::{Checkpoint, LogManager, LogRecordBody},
+    log_flusher::LogFlusher,
     optimizer::Optimizer,
-    parser::{CreateIndexStatementAST, CreateTableStatementAST, StatementAST},
+    parser::{
+        ConstraintTypeAST, CreateIndexStatementAST, CreateTableStatementAST, DropIndexStatementAST,
+        ShowIndexesStatementAST, StatementAST,
+    },
     plan::Planner,
-    recovery::RecoveryManager,
-    value::Value,
+    recovery::{RecoveryManager, UndoManager},
+    table::TableHeap,
+    value::{boolean::BooleanValue, integer::IntegerValue, varchar::VarcharValue, Value},
 };
 
 pub struct Instance {
@@ -28,6 +37,9 @@ pub struct Instance {
     pub transaction_manager: Arc<Mutex<TransactionManager>>,
     pub lock_manager: Arc<RwLock<LockManager>>,
     pub log_manager: Arc<Mutex<LogManager>>,
+    pub statistics: Arc<Mutex<StatisticsCollector>>,
+    pub control_file: Arc<Mutex<ControlFile>>,
+    pub log_flusher: LogFlusher,
 }
 
 impl Instance {
@@ -40,8 +52,13 @@ impl Instance {
         }
 
         let data_file = format!("{}/data.db", dir);
-        let txn_log_file = format!("{}/txn.log", dir);
         let wal_log_file = format!("{}/wal.log", dir);
+        let control_file_path = format!("{}/control", dir);
+        let mut control_file = if init {
+            ControlFile::new(&control_file_path)
+        } else {
+            ControlFile::load(&control_file_path)?
+        };
 
         let disk_manager = DiskManager::new(&data_file)?;
         let log_manager = Arc::new(Mutex::new(LogManager::new(&wal_log_file)?));
@@ -54,9 +71,36 @@ impl Instance {
         let transaction_manager = Arc::new(Mutex::new(TransactionManager::new(
             lock_manager.clone(),
             log_manager.clone(),
-            &txn_log_file,
-            IsolationLevel::RepeatableRead,
-        )?));
+        )));
+        let log_records = log_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .read()?;
+        let mut recovery_manager = RecoveryManager::new(
+            buffer_pool_manager.clone(),
+            log_manager.clone(),
+            log_records,
+            control_file.checkpoint_lsn,
+        );
+        // system tables are read by bootstrap, so they must be recovered first.
+        // the previous run did not shut down cleanly, so the data file may miss logged changes
+        if recover || !control_file.clean_shutdown {
+            recovery_manager.recover()?;
+        } else {
+            recovery_manager.analyze()?;
+        }
+        // the log is the only record of which transactions have committed, and of the free pages
+        transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .restore(recovery_manager.statuses(), recovery_manager.next_txn_id());
+        buffer_pool_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .set_free_page_ids(recovery_manager.free_page_ids());
+        control_file.clean_shutdown = false;
+        control_file.save()?;
+
         let mut catalog = Catalog::new(
             buffer_pool_manager.clone(),
             transaction_manager.clone(),
@@ -66,22 +110,15 @@ impl Instance {
         catalog.bootstrap(init)?;
         let catalog = Arc::new(Mutex::new(catalog));
 
-        if recover {
-            let log_records = log_manager
-                .lock()
-                .map_err(|e| anyhow::anyhow!("{}", e))?
-                .read()?;
-            let mut recovery_manager =
-                RecoveryManager::new(buffer_pool_manager.clone(), log_records);
-            recovery_manager.recover()?;
-        }
-
         Ok(Self {
             buffer_pool_manager,
             catalog,
             transaction_manager,
             lock_manager,
-            log_manager,
+            log_manager: log_manager.clone(),
+            statistics: Arc::new(Mutex::new(StatisticsCollector::default())),
+            control_file: Arc::new(Mutex::new(control_file)),
+            log_flusher: LogFlusher::start(log_manager),
         })
     }
 
@@ -101,16 +138,76 @@ impl Instance {
                 })
                 .collect(),
         };
-        self.catalog
-            .lock()
-            .map_err(|e| anyhow::anyhow!("{}", e))?
-            .create_table(&statement.table_name, &schema, txn_id)
+        let constraints = statement
+            .elements
+            .iter()
+            .flat_map(|e| {
+                e.constraints
+                    .iter()
+                    .map(|c| (c.clone(), vec![e.column_name.clone()]))
+            })
+            .chain(
+                statement
+                    .constraints
+                    .iter()
+                    .map(|c| (c.constraint_type.clone(), c.column_names.clone())),
+            )
+            .collect::<Vec<_>>();
+        let primary_key_count = constraints
+            .iter()
+            .filter(|(c, _)| *c == ConstraintTypeAST::PrimaryKey)
+            .count();
+        if primary_key_count > 1 {
+            return Err(anyhow::anyhow!(
+                "multiple primary keys for table {} are not allowed",
+                statement.table_name
+            ));
+        }
+        for (_, column_names) in constraints.iter() {
+            for column_name in column_names {
+                if schema.column_index(column_name).is_none() {
+                    return Err(anyhow::anyhow!("column {} not found", column_name));
+                }
+            }
+        }
+
+        let mut catalog = self.catalog.lock().map_err(|e| anyhow::anyhow!("{}", e))?;
+        catalog.create_table(&statement.table_name, &schema, txn_id)?;
+        for (constraint_type, column_names) in constraints {
+            let (index_name, is_primary) = match constraint_type {
+                ConstraintTypeAST::PrimaryKey => (format!("{}_pkey", statement.table_name), true),
+                ConstraintTypeAST::Unique => (
+                    format!("{}_{}_key", statement.table_name, column_names.join("_")),
+                    false,
+                ),
+            };
+            catalog.create_index(
+                &index_name,
+                &statement.table_name,
+                &column_names,
+                true,
+                is_primary,
+                txn_id,
+            )?;
+        }
+        Ok(())
     }
     pub fn create_index(
         &self,
         statement: &CreateIndexStatementAST,
         txn_id: TransactionID,
     ) -> Result<()> {
+        let first_page_id = self
+            .catalog
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .get_first_page_id_by_table_name(&statement.table_name, txn_id)?;
+        // writers hold the table in shared mode until they end, so the build waits for them and
+        // the new index is visible to every writer after it. the catalog is not held while waiting
+        self.lock_manager
+            .read()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .lock_table(first_page_id, txn_id, LockMode::Exclusive)?;
         self.catalog
             .lock()
             .map_err(|e| anyhow::anyhow!("{}", e))?
@@ -118,10 +215,60 @@ impl Instance {
                 &statement.index_name,
                 &statement.table_name,
                 &statement.column_names,
+                statement.unique,
+                false,
                 txn_id,
             )
     }
 
+    pub fn drop_index(
+        &self,
+        statement: &DropIndexStatementAST,
+        txn_id: TransactionID,
+    ) -> Result<()> {
+        self.catalog
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .drop_index(&statement.index_name, statement.if_exists, txn_id)
+    }
+    pub fn show_indexes(
+        &self,
+        statement: &ShowIndexesStatementAST,
+        txn_id: TransactionID,
+    ) -> Result<(Vec<Vec<Value>>, Schema)> {
+        let catalog = self.catalog.lock().map_err(|e| anyhow::anyhow!("{}", e))?;
+        catalog.get_first_page_id_by_table_name(&statement.table_name, txn_id)?;
+        let rows = catalog
+            .get_indexes_by_table_name(&statement.table_name, txn_id)?
+            .into_iter()
+            .map(|index| {
+                vec![
+                    Value::Varchar(VarcharValue(index.name)),
+                    Value::Varchar(VarcharValue(index.columns.join(", "))),
+                    Value::Integer(IntegerValue(index.first_page_id.0 as i64)),
+                    Value::Boolean(BooleanValue(index.is_unique)),
+                    Value::Boolean(BooleanValue(index.is_primary)),
+                ]
+            })
+            .collect();
+        let schema = Schema {
+            columns: [
+                ("index_name", DataType::Varchar),
+                ("column_names", DataType::Varchar),
+                ("root_page_id", DataType::Integer),
+                ("is_unique", DataType::Boolean),
+                ("is_primary", DataType::Boolean),
+            ]
+            .into_iter()
+            .map(|(name, data_type)| Column {
+                name: name.to_string(),
+                data_type,
+            })
+            .collect(),
+        };
+        Ok((rows, schema))
+    }
+
     // DDL
     pub fn execute(
         &self,
@@ -142,33 +289,323 @@ impl Instance {
             transaction_manager: self.transaction_manager.clone(),
             log_manager: self.log_manager.clone(),
             catalog: self.catalog.clone(),
+            statistics: self.statistics.clone(),
         };
         let mut executor_engine = ExecutorEngine::new(plan, executor_context);
         let rows = executor_engine.execute()?;
         Ok((rows, schema))
     }
 
+    // removes the dead tuples of the table, or of all tables, and returns the number of them
+    pub fn vacuum(&self, table_name: Option<&str>) -> Result<usize> {
+        let removed = self.vacuum_with(table_name, None)?;
+        let mut statistics = self
+            .statistics
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        for (table_name, count) in removed.iter() {
+            statistics.vacuumed(table_name, *count, false);
+        }
+        Ok(removed.iter().map(|(_, count)| count).sum())
+    }
+    pub fn autovacuum(&self, table_name: &str, cost: &mut VacuumCost) -> Result<usize> {
+        let removed = self.vacuum_with(Some(table_name), Some(cost))?;
+        let mut statistics = self
+            .statistics
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        for (table_name, count) in removed.iter() {
+            statistics.vacuumed(table_name, *count, true);
+        }
+        Ok(removed.iter().map(|(_, count)| count).sum())
+    }
+    fn vacuum_with(
+        &self,
+        table_name: Option<&str>,
+        cost: Option<&mut VacuumCost>,
+    ) -> Result<Vec<(String, usize)>> {
+        // a transaction only to read the catalog
+        let txn_id = self.begin(None)?;
+        match self.vacuum_tables(table_name, cost, txn_id) {
+            Ok(removed) => {
+                self.commit(txn_id)?;
+                Ok(removed)
+            }
+            Err(e) => {
+                self.rollback(txn_id)?;
+                Err(e)
+            }
+        }
+    }
+    // goes page by page, the cost of each page is charged before the next one
+    fn vacuum_tables(
+        &self,
+        table_name: Option<&str>,
+        mut cost: Option<&mut VacuumCost>,
+        txn_id: TransactionID,
+    ) -> Result<Vec<(String, usize)>> {
+        let mut tables = vec![];
+        {
+            let catalog = self.catalog.lock().map_err(|e| anyhow::anyhow!("{}", e))?;
+            let table_names = match table_name {
+                Some(table_name) => vec![table_name.to_string()],
+                None => catalog.get_table_names(txn_id)?,
+            };
+            for table_name in table_names {
+                let first_page_id = catalog.get_first_page_id_by_table_name(&table_name, txn_id)?;
+                // an index being built may already hold entries of the dead tuples
+                if !self
+                    .lock_manager
+                    .read()
+                    .map_err(|e| anyhow::anyhow!("{}", e))?
+                    .try_lock_table(first_page_id, txn_id, LockMode::Shared)?
+                {
+                    continue;
+                }
+                let schema = catalog.get_schema_by_table_name(&table_name, txn_id)?;
+                let mut indexes = catalog.get_indexes_to_maintain(&table_name, txn_id)?;
+                for index in indexes.iter_mut() {
+                    index.set_schema(schema.clone());
+                }
+                tables.push((table_name, first_page_id, schema, indexes));
+            }
+        }
+        let mut removed = vec![];
+        for (table_name, first_page_id, schema, indexes) in tables {
+            let table_heap = TableHeap::new(
+                first_page_id,
+                self.buffer_pool_manager.clone(),
+                self.transaction_manager.clone(),
+                self.lock_manager.clone(),
+                self.log_manager.clone(),
+                txn_id,
+            );
+            let index_managers = indexes
+                .into_iter()
+                .map(|index| {
+                    (
+                        index.clone(),
+                        IndexManager::new(
+                            index,
+                            self.buffer_pool_manager.clone(),
+                            self.log_manager.clone(),
+                            INVALID_TRANSACTION_ID,
+                        ),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let mut count = 0;
+            let mut page_id = first_page_id;
+            while page_id != INVALID_PAGE_ID {
+                let (dead_tuples, next_page_id) = table_heap.dead_tuples(page_id)?;
+                // index entries first, so that no entry points to a removed tuple
+                for (index, index_manager) in index_managers.iter() {
+                    for tuple in dead_tuples.iter() {
+                        if let Some(rid) = tuple.rid {
+                            index_manager
+                                .delete(&index.key(&schema, &tuple.values(&schema))?, rid)?;
+                        }
+                    }
+                }
+                let rids = dead_tuples
+                    .iter()
+                    .filter_map(|tuple| tuple.rid)
+                    .collect::<Vec<_>>();
+                table_heap.prune(&rids)?;
+                table_heap.mark_all_visible(page_id)?;
+                count += rids.len();
+                if let Some(cost) = cost.as_deref_mut() {
+                    cost.charge(1 + rids.len() * (1 + index_managers.len()));
+                }
+                page_id = next_page_id;
+            }
+            removed.push((table_name, count));
+        }
+        Ok(removed)
+    }
+
     // DCL
     pub fn begin(&self, txn_id: Option<TransactionID>) -> Result<TransactionID> {
         if let Some(txn_id) = txn_id {
             return Ok(txn_id);
         }
+        self.begin_with_isolation_level(IsolationLevel::default())
+    }
+    pub fn begin_with_isolation_level(
+        &self,
+        isolation_level: IsolationLevel,
+    ) -> Result<TransactionID> {
+        self.transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .begin(isolation_level)
+    }
+    pub fn set_isolation_level(
+        &self,
+        txn_id: TransactionID,
+        isolation_level: IsolationLevel,
+    ) -> Result<()> {
         self.transaction_manager
             .lock()
             .map_err(|e| anyhow::anyhow!("{}", e))?
-            .begin()
+            .set_isolation_level(txn_id, isolation_level)
     }
     pub fn commit(&self, txn_id: TransactionID) -> Result<()> {
+        self.commit_with_durability_window(txn_id, None)
+    }
+    // with a window, the commit is visible before it is on disk, and is lost by a crash within the window
+    pub fn commit_with_durability_window(
+        &self,
+        txn_id: TransactionID,
+        durability_window: Option<Duration>,
+    ) -> Result<()> {
+        let commit_lsn = self
+            .transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .log_commit(txn_id)?;
+        match durability_window {
+            Some(durability_window) => self.log_flusher.request(durability_window)?,
+            None => self.log_flusher.wait_for(commit_lsn)?,
+        }
         self.transaction_manager
             .lock()
             .map_err(|e| anyhow::anyhow!("{}", e))?
-            .commit(txn_id)
+            .commit(txn_id)?;
+        self.statistics
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .end_transaction(txn_id, true);
+        self.catalog
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .free_dropped_indexes()
     }
+    // the changes are undone while the locks are still held
     pub fn rollback(&self, txn_id: TransactionID) -> Result<()> {
+        UndoManager::new(self.buffer_pool_manager.clone(), self.log_manager.clone())
+            .rollback(txn_id, INVALID_LSN)?;
+        self.transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .abort(txn_id)?;
+        self.statistics
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .end_transaction(txn_id, false);
+        self.catalog
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .free_dropped_indexes()
+    }
+    pub fn savepoint(&self, txn_id: TransactionID, name: &str) -> Result<()> {
+        self.transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .savepoint(txn_id, name)
+    }
+    pub fn rollback_to_savepoint(&self, txn_id: TransactionID, name: &str) -> Result<()> {
+        let undo_position = self
+            .transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .rollback_to_savepoint(txn_id, name)?;
+        self.rollback_to(txn_id, undo_position)
+    }
+    pub fn release_savepoint(&self, txn_id: TransactionID, name: &str) -> Result<()> {
+        self.transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .release_savepoint(txn_id, name)
+    }
+    pub fn undo_position(&self, txn_id: TransactionID) -> Result<LSN> {
         self.transaction_manager
             .lock()
             .map_err(|e| anyhow::anyhow!("{}", e))?
-            .abort(txn_id)
+            .undo_position(txn_id)
+    }
+    // undo the changes after the position, locks acquired after it are kept
+    pub fn rollback_to(&self, txn_id: TransactionID, undo_position: LSN) -> Result<()> {
+        UndoManager::new(self.buffer_pool_manager.clone(), self.log_manager.clone())
+            .rollback(txn_id, undo_position)?;
+        self.catalog
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .rollback_dropped_indexes(txn_id)
+    }
+
+    pub fn set_lock_timeout(
+        &self,
+        txn_id: TransactionID,
+        lock_timeout: Option<Duration>,
+    ) -> Result<()> {
+        self.lock_manager
+            .read()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .set_lock_timeout(txn_id, lock_timeout)
+    }
+
+    // fuzzy, dirty pages are left in the buffer pool and their oldest changes are redone after a crash
+    pub fn checkpoint(&self) -> Result<()> {
+        let previous_checkpoint_lsn = self
+            .control_file
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .checkpoint_lsn;
+        // pages left dirty through a whole checkpoint interval are written,
+        // otherwise they would keep the log from being truncated
+        if let Some(previous_checkpoint_lsn) = previous_checkpoint_lsn {
+            self.buffer_pool_manager
+                .lock()
+                .map_err(|e| anyhow::anyhow!("{}", e))?
+                .flush_pages_before(previous_checkpoint_lsn)?;
+        }
+        let (checkpoint_lsn, checkpoint) = {
+            // the log is locked last, so that no change is logged between the tables and the record
+            let transaction_manager = self
+                .transaction_manager
+                .lock()
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            let buffer_pool_manager = self
+                .buffer_pool_manager
+                .lock()
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            let mut log_manager = self
+                .log_manager
+                .lock()
+                .map_err(|e| anyhow::anyhow!("{}", e))?;
+            let checkpoint = Checkpoint {
+                active_transactions: transaction_manager.active_transaction_table(),
+                dirty_pages: buffer_pool_manager.dirty_page_table(),
+                next_txn_id: transaction_manager.next_txn_id(),
+                aborted_transactions: transaction_manager.aborted_transactions(),
+                free_page_ids: buffer_pool_manager.free_page_ids(),
+            };
+            let checkpoint_lsn = log_manager.append(
+                INVALID_TRANSACTION_ID,
+                LogRecordBody::Checkpoint(checkpoint.clone()),
+            )?;
+            log_manager.flush()?;
+            (checkpoint_lsn, checkpoint)
+        };
+        let mut control_file = self
+            .control_file
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        control_file.checkpoint_lsn = Some(checkpoint_lsn);
+        control_file.save()?;
+        // recovery needs neither the changes before the redo start
+        // nor the ones before the begin of the oldest running transaction
+        let oldest_lsn = checkpoint
+            .dirty_pages
+            .iter()
+            .map(|(_, lsn)| *lsn)
+            .chain(checkpoint.active_transactions.iter().map(|(_, lsn)| *lsn))
+            .fold(checkpoint_lsn, LSN::min);
+        self.log_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .truncate(oldest_lsn)
     }
 
     pub fn shutdown(&self) -> Result<()> {
@@ -176,7 +613,20 @@ impl Instance {
             .lock()
             .map_err(|e| anyhow::anyhow!("{}", e))?
             .shutdown()?;
-        Ok(())
+        self.checkpoint()?;
+        // running transactions are rolled back by the recovery at the next start
+        let clean_shutdown = self
+            .transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?
+            .active_transaction_table()
+            .is_empty();
+        let mut control_file = self
+            .control_file
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        control_file.clean_shutdown = clean_shutdown;
+        control_file.save()
     }
 }
 
@@ -194,7 +644,7 @@ mod tests {
         Instance::new(dir.to_str().unwrap(), true, false)?;
         assert!(dir.exists());
         assert!(dir.join("data.db").exists());
-        assert!(dir.join("txn.log").exists());
+        assert!(dir.join("wal.log").exists());
         Ok(())
     }
 
@@ -212,7 +662,7 @@ mod tests {
         Instance::new(dir.to_str().unwrap(), true, false)?;
         assert!(created_at < fs::metadata(&dir)?.created()?);
         assert!(dir.join("data.db").exists());
-        assert!(dir.join("txn.log").exists());
+        assert!(dir.join("wal.log").exists());
         Ok(())
     }
 
@@ -229,7 +679,44 @@ mod tests {
         Instance::new(dir.to_str().unwrap(), false, false)?;
         assert_eq!(created_at, fs::metadata(&dir)?.created()?);
         assert!(dir.join("data.db").exists());
-        assert!(dir.join("txn.log").exists());
+        assert!(dir.join("wal.log").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_commit() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let instance = Arc::new(Instance::new(dir.to_str().unwrap(), true, false)?);
+        let handles = (0..8)
+            .map(|_| {
+                let instance = instance.clone();
+                thread::spawn(move || -> Result<()> {
+                    for _ in 0..10 {
+                        let txn_id = instance.begin(None)?;
+                        instance.commit(txn_id)?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        let transaction_manager = instance
+            .transaction_manager
+            .lock()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        assert!(transaction_manager.active_transaction_table().is_empty());
+        assert!(transaction_manager.aborted_transactions().is_empty());
+        drop(transaction_manager);
+        // every commit is on disk
+        let commits = LogManager::new(dir.join("wal.log").to_str().unwrap())?
+            .read()?
+            .into_iter()
+            .filter(|log_record| log_record.body == LogRecordBody::CommitTransaction)
+            .count();
+        assert!(commits >= 80);
         Ok(())
     }
 }
@@ -67,6 +67,30 @@ pub enum Keyword {
     Or,
     Not,
     Is,
+    Primary,
+    Key,
+    Unique,
+    Drop,
+    If,
+    Exists,
+    Show,
+    Indexes,
+    For,
+    Nowait,
+    Skip,
+    Locked,
+    Share,
+    Transaction,
+    Isolation,
+    Level,
+    Read,
+    Committed,
+    Repeatable,
+    Serializable,
+    Savepoint,
+    Release,
+    To,
+    Vacuum,
 }
 impl TryFrom<&str> for Keyword {
     type Error = anyhow::Error;
@@ -108,6 +132,30 @@ impl TryFrom<&str> for Keyword {
             "OR" => Ok(Keyword::Or),
             "NOT" => Ok(Keyword::Not),
             "IS" => Ok(Keyword::Is),
+            "PRIMARY" => Ok(Keyword::Primary),
+            "KEY" => Ok(Keyword::Key),
+            "UNIQUE" => Ok(Keyword::Unique),
+            "DROP" => Ok(Keyword::Drop),
+            "IF" => Ok(Keyword::If),
+            "EXISTS" => Ok(Keyword::Exists),
+            "SHOW" => Ok(Keyword::Show),
+            "INDEXES" => Ok(Keyword::Indexes),
+            "FOR" => Ok(Keyword::For),
+            "NOWAIT" => Ok(Keyword::Nowait),
+            "SKIP" => Ok(Keyword::Skip),
+            "LOCKED" => Ok(Keyword::Locked),
+            "SHARE" => Ok(Keyword::Share),
+            "TRANSACTION" => Ok(Keyword::Transaction),
+            "ISOLATION" => Ok(Keyword::Isolation),
+            "LEVEL" => Ok(Keyword::Level),
+            "READ" => Ok(Keyword::Read),
+            "COMMITTED" => Ok(Keyword::Committed),
+            "REPEATABLE" => Ok(Keyword::Repeatable),
+            "SERIALIZABLE" => Ok(Keyword::Serializable),
+            "SAVEPOINT" => Ok(Keyword::Savepoint),
+            "RELEASE" => Ok(Keyword::Release),
+            "TO" => Ok(Keyword::To),
+            "VACUUM" => Ok(Keyword::Vacuum),
             _ => Err(anyhow!("invalid keyword: {}", s)),
         }
     }
@@ -311,7 +359,10 @@ mod tests {
             CREATE table Index INSERT INTO VALUES DELETE FROM WHERE UPDATE SET
             SELECT INNER LEFT JOIN ON GROUP BY HAVING ORDER ASC
             DESC LIMIT OFFSET INT INTEGER VARCHAR BOOLEAN BEGIN
-            COMMIT ROLLBACK AS AND OR NOT IS
+            COMMIT ROLLBACK AS AND OR NOT IS PRIMARY KEY UNIQUE
+            DROP IF EXISTS SHOW INDEXES FOR NOWAIT SKIP LOCKED SHARE
+            TRANSACTION ISOLATION LEVEL READ COMMITTED REPEATABLE SERIALIZABLE
+            SAVEPOINT RELEASE TO VACUUM
         "#;
         let mut iter = text.chars().peekable();
         let tokens = tokenize(&mut iter)?;
@@ -354,6 +405,30 @@ mod tests {
                 Token::Keyword(Keyword::Or),
                 Token::Keyword(Keyword::Not),
                 Token::Keyword(Keyword::Is),
+                Token::Keyword(Keyword::Primary),
+                Token::Keyword(Keyword::Key),
+                Token::Keyword(Keyword::Unique),
+                Token::Keyword(Keyword::Drop),
+                Token::Keyword(Keyword::If),
+                Token::Keyword(Keyword::Exists),
+                Token::Keyword(Keyword::Show),
+                Token::Keyword(Keyword::Indexes),
+                Token::Keyword(Keyword::For),
+                Token::Keyword(Keyword::Nowait),
+                Token::Keyword(Keyword::Skip),
+                Token::Keyword(Keyword::Locked),
+                Token::Keyword(Keyword::Share),
+                Token::Keyword(Keyword::Transaction),
+                Token::Keyword(Keyword::Isolation),
+                Token::Keyword(Keyword::Level),
+                Token::Keyword(Keyword::Read),
+                Token::Keyword(Keyword::Committed),
+                Token::Keyword(Keyword::Repeatable),
+                Token::Keyword(Keyword::Serializable),
+                Token::Keyword(Keyword::Savepoint),
+                Token::Keyword(Keyword::Release),
+                Token::Keyword(Keyword::To),
+                Token::Keyword(Keyword::Vacuum),
                 Token::EOF,
             ]
         );
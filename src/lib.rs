@@ -1,9 +1,11 @@
+pub mod autovacuum;
 pub mod binder;
 pub mod buffer;
 pub mod catalog;
 pub mod client;
 pub mod common;
 pub mod concurrency;
+pub mod control;
 pub mod disk;
 pub mod executor;
 pub mod index;
@@ -11,10 +13,12 @@ pub mod instance;
 pub mod lexer;
 pub mod lock;
 pub mod log;
+pub mod log_flusher;
 pub mod optimizer;
 pub mod page;
 pub mod parser;
 pub mod plan;
+pub mod predicate_lock;
 pub mod recovery;
 pub mod server;
 pub mod table;
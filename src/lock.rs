@@ -1,100 +1,300 @@
 use std::{
-    collections::HashMap,
-    sync::{Condvar, Mutex, RwLock},
+    collections::{HashMap, HashSet},
+    sync::{Arc, Condvar, Mutex, MutexGuard, RwLock},
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Result};
 
-use crate::common::{TransactionID, RID};
+use crate::{
+    common::{PageID, TransactionID, RID},
+    concurrency::TransactionRollbackError,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
 
+// a row, a key of a unique index which is locked while its uniqueness is checked, or a table
+// which writers lock in shared mode and index builds in exclusive mode
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LockTarget {
+    Row(RID),
+    IndexKey(PageID, Vec<u8>),
+    Table(PageID),
+}
+
+#[derive(Default)]
 pub struct LockRequest {
-    pub txn_id: Mutex<Option<TransactionID>>,
+    pub holders: Mutex<HashMap<TransactionID, LockMode>>,
     pub condvar: Condvar,
 }
-impl LockRequest {
-    pub fn new(txn_id: TransactionID) -> Self {
-        Self {
-            txn_id: Mutex::new(Some(txn_id)),
-            condvar: Condvar::new(),
-        }
-    }
-}
 
 pub struct LockManager {
-    lock_requests_by_rid: RwLock<HashMap<RID, LockRequest>>,
-    rids_by_txn_id: RwLock<HashMap<TransactionID, Vec<RID>>>,
+    lock_requests: RwLock<HashMap<LockTarget, Arc<LockRequest>>>,
+    targets_by_txn_id: RwLock<HashMap<TransactionID, Vec<LockTarget>>>,
+    // waiting transaction -> transactions holding the lock it waits for
+    wait_for_graph: Mutex<HashMap<TransactionID, Vec<TransactionID>>>,
+    // transactions without a timeout wait until the lock is released or a deadlock is detected
+    lock_timeouts: Mutex<HashMap<TransactionID, Duration>>,
 }
 
 impl Default for LockManager {
     fn default() -> Self {
         Self {
-            lock_requests_by_rid: RwLock::new(HashMap::new()),
-            rids_by_txn_id: RwLock::new(HashMap::new()),
+            lock_requests: RwLock::new(HashMap::new()),
+            targets_by_txn_id: RwLock::new(HashMap::new()),
+            wait_for_graph: Mutex::new(HashMap::new()),
+            lock_timeouts: Mutex::new(HashMap::new()),
         }
     }
 }
 
 impl LockManager {
-    pub fn lock(&self, rid: RID, txn_id: TransactionID) -> Result<()> {
-        {
-            let requests = self
-                .lock_requests_by_rid
-                .read()
-                .map_err(|_| anyhow!("lock error"))?;
-            if let Some(request) = requests.get(&rid) {
-                let mut locked_txn_id = request.txn_id.lock().map_err(|_| anyhow!("lock error"))?;
-                while locked_txn_id.is_some() {
-                    locked_txn_id = request
+    // a shared lock is upgraded by locking the row again in exclusive mode
+    pub fn lock(&self, rid: RID, txn_id: TransactionID, mode: LockMode) -> Result<()> {
+        self.lock_target(LockTarget::Row(rid), txn_id, mode)
+    }
+    // the key is the serialized key of the index whose first page is given
+    pub fn lock_index_key(
+        &self,
+        index_page_id: PageID,
+        key: Vec<u8>,
+        txn_id: TransactionID,
+    ) -> Result<()> {
+        self.lock_target(
+            LockTarget::IndexKey(index_page_id, key),
+            txn_id,
+            LockMode::Exclusive,
+        )
+    }
+    // the table is identified by its first page
+    pub fn lock_table(
+        &self,
+        first_page_id: PageID,
+        txn_id: TransactionID,
+        mode: LockMode,
+    ) -> Result<()> {
+        self.lock_target(LockTarget::Table(first_page_id), txn_id, mode)
+    }
+    fn lock_target(&self, target: LockTarget, txn_id: TransactionID, mode: LockMode) -> Result<()> {
+        let request = self.request(&target)?;
+        let holders = request.holders.lock().map_err(|_| anyhow!("lock error"))?;
+        let mut holders = self.wait_for_holders(&request, holders, txn_id, mode)?;
+        self.grant(&mut holders, target, txn_id, mode)
+    }
+    // waits until no other transaction holds a lock on the row, without locking it.
+    // the wait times out and detects deadlocks like a lock
+    pub fn wait(&self, rid: RID, txn_id: TransactionID) -> Result<()> {
+        let request = self.request(&LockTarget::Row(rid))?;
+        let holders = request.holders.lock().map_err(|_| anyhow!("lock error"))?;
+        drop(self.wait_for_holders(&request, holders, txn_id, LockMode::Exclusive)?);
+        Ok(())
+    }
+    // returns the holders once none of them conflicts with the mode
+    fn wait_for_holders<'a>(
+        &self,
+        request: &'a LockRequest,
+        mut holders: MutexGuard<'a, HashMap<TransactionID, LockMode>>,
+        txn_id: TransactionID,
+        mode: LockMode,
+    ) -> Result<MutexGuard<'a, HashMap<TransactionID, LockMode>>> {
+        let deadline = self
+            .lock_timeouts
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .get(&txn_id)
+            .map(|lock_timeout| Instant::now() + *lock_timeout);
+        // the map is not held while waiting, so other targets can be locked and unlocked meanwhile
+        loop {
+            let conflicting_txn_ids = conflicting_txn_ids(&holders, txn_id, mode);
+            if conflicting_txn_ids.is_empty() {
+                break;
+            }
+            // the transaction which closes a cycle is the victim, the session aborts it on the error
+            self.add_wait_for_edges(txn_id, conflicting_txn_ids)?;
+            holders = match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        self.wait_for_graph
+                            .lock()
+                            .map_err(|_| anyhow!("lock error"))?
+                            .remove(&txn_id);
+                        return Err(anyhow!(
+                            "lock timeout: transaction {} could not acquire the lock",
+                            txn_id.0
+                        ));
+                    }
+                    request
                         .condvar
-                        .wait(locked_txn_id)
-                        .map_err(|_| anyhow!("lock error"))?;
+                        .wait_timeout(holders, deadline - now)
+                        .map_err(|_| anyhow!("lock error"))?
+                        .0
                 }
-                locked_txn_id.replace(txn_id);
-                self.rids_by_txn_id
+                None => request
+                    .condvar
+                    .wait(holders)
+                    .map_err(|_| anyhow!("lock error"))?,
+            };
+        }
+        self.wait_for_graph
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .remove(&txn_id);
+        Ok(holders)
+    }
+    // returns false instead of waiting if another transaction holds a conflicting lock
+    pub fn try_lock(&self, rid: RID, txn_id: TransactionID, mode: LockMode) -> Result<bool> {
+        self.try_lock_target(LockTarget::Row(rid), txn_id, mode)
+    }
+    pub fn try_lock_table(
+        &self,
+        first_page_id: PageID,
+        txn_id: TransactionID,
+        mode: LockMode,
+    ) -> Result<bool> {
+        self.try_lock_target(LockTarget::Table(first_page_id), txn_id, mode)
+    }
+    fn try_lock_target(
+        &self,
+        target: LockTarget,
+        txn_id: TransactionID,
+        mode: LockMode,
+    ) -> Result<bool> {
+        let request = self.request(&target)?;
+        let mut holders = request.holders.lock().map_err(|_| anyhow!("lock error"))?;
+        if !conflicting_txn_ids(&holders, txn_id, mode).is_empty() {
+            return Ok(false);
+        }
+        self.grant(&mut holders, target, txn_id, mode)?;
+        Ok(true)
+    }
+    pub fn unlock(&self, transaction_id: TransactionID) -> Result<()> {
+        let targets = self
+            .targets_by_txn_id
+            .write()
+            .map_err(|_| anyhow!("lock error"))?
+            .remove(&transaction_id)
+            .unwrap_or_default();
+        // the waiters add their edges again if they have to keep waiting
+        self.wait_for_graph
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .retain(|&waiter, holders| {
+                waiter != transaction_id && !holders.contains(&transaction_id)
+            });
+        self.lock_timeouts
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?
+            .remove(&transaction_id);
+        let requests = self
+            .lock_requests
+            .read()
+            .map_err(|_| anyhow!("lock error"))?;
+        for target in targets.iter() {
+            if let Some(request) = requests.get(target) {
+                let mut holders = request.holders.lock().map_err(|_| anyhow!("lock error"))?;
+                holders.remove(&transaction_id);
+                request.condvar.notify_all();
+            }
+        }
+        Ok(())
+    }
+    // kept until the transaction unlocks
+    pub fn set_lock_timeout(
+        &self,
+        txn_id: TransactionID,
+        lock_timeout: Option<Duration>,
+    ) -> Result<()> {
+        let mut lock_timeouts = self
+            .lock_timeouts
+            .lock()
+            .map_err(|_| anyhow!("lock error"))?;
+        match lock_timeout {
+            Some(lock_timeout) => lock_timeouts.insert(txn_id, lock_timeout),
+            None => lock_timeouts.remove(&txn_id),
+        };
+        Ok(())
+    }
+    fn request(&self, target: &LockTarget) -> Result<Arc<LockRequest>> {
+        Ok(self
+            .lock_requests
+            .write()
+            .map_err(|_| anyhow!("lock error"))?
+            .entry(target.clone())
+            .or_insert_with(|| Arc::new(LockRequest::default()))
+            .clone())
+    }
+    fn grant(
+        &self,
+        holders: &mut HashMap<TransactionID, LockMode>,
+        target: LockTarget,
+        txn_id: TransactionID,
+        mode: LockMode,
+    ) -> Result<()> {
+        match holders.get(&txn_id) {
+            // an exclusive lock is not downgraded
+            Some(LockMode::Exclusive) => {}
+            Some(LockMode::Shared) => {
+                holders.insert(txn_id, mode);
+            }
+            None => {
+                holders.insert(txn_id, mode);
+                self.targets_by_txn_id
                     .write()
                     .map_err(|_| anyhow!("lock error"))?
                     .entry(txn_id)
                     .or_insert_with(Vec::new)
-                    .push(rid);
-                return Ok(());
+                    .push(target);
             }
         }
-
-        let mut requests = self
-            .lock_requests_by_rid
-            .write()
-            .map_err(|_| anyhow!("lock error"))?;
-        let request = LockRequest::new(txn_id);
-        requests.insert(rid, request);
-        self.rids_by_txn_id
-            .write()
-            .map_err(|_| anyhow!("lock error"))?
-            .entry(txn_id)
-            .or_insert_with(Vec::new)
-            .push(rid);
         Ok(())
     }
-    pub fn unlock(&self, transaction_id: TransactionID) -> Result<()> {
-        let mut rids_map = self
-            .rids_by_txn_id
-            .write()
+    fn add_wait_for_edges(&self, waiter: TransactionID, holders: Vec<TransactionID>) -> Result<()> {
+        let mut wait_for_graph = self
+            .wait_for_graph
+            .lock()
             .map_err(|_| anyhow!("lock error"))?;
-        for rid in rids_map.get(&transaction_id).unwrap_or(&vec![]).iter() {
-            let requests = self
-                .lock_requests_by_rid
-                .read()
-                .map_err(|_| anyhow!("lock error"))?;
-            if let Some(request) = requests.get(rid) {
-                let mut locked_txn_id = request.txn_id.lock().map_err(|_| anyhow!("lock error"))?;
-                locked_txn_id.take();
-                request.condvar.notify_all();
+        let mut stack = holders.clone();
+        let mut visited = HashSet::new();
+        while let Some(txn_id) = stack.pop() {
+            if txn_id == waiter {
+                wait_for_graph.remove(&waiter);
+                return Err(TransactionRollbackError(format!(
+                    "deadlock detected: transaction {} is aborted",
+                    waiter.0
+                ))
+                .into());
+            }
+            if visited.insert(txn_id) {
+                if let Some(next_txn_ids) = wait_for_graph.get(&txn_id) {
+                    stack.extend(next_txn_ids.iter().copied());
+                }
             }
         }
-        rids_map.remove(&transaction_id);
+        wait_for_graph.insert(waiter, holders);
         Ok(())
     }
 }
 
+fn conflicting_txn_ids(
+    holders: &HashMap<TransactionID, LockMode>,
+    txn_id: TransactionID,
+    mode: LockMode,
+) -> Vec<TransactionID> {
+    holders
+        .iter()
+        .filter(|(&holder_txn_id, &holder_mode)| {
+            holder_txn_id != txn_id
+                && (mode == LockMode::Exclusive || holder_mode == LockMode::Exclusive)
+        })
+        .map(|(&holder_txn_id, _)| holder_txn_id)
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::{sync::Arc, thread};
@@ -113,7 +313,7 @@ mod tests {
             lock_manager_clone
                 .read()
                 .map_err(|_| anyhow!("lock error"))?
-                .lock(rid, txn_id)?;
+                .lock(rid, txn_id, LockMode::Exclusive)?;
             // println!("locked txn_id: {:?}, rid: {:?}", txn_id, rid);
             thread::sleep(std::time::Duration::from_millis(500));
             lock_manager_clone
@@ -134,7 +334,7 @@ mod tests {
                 lock_manager
                     .read()
                     .map_err(|_| anyhow!("lock error"))?
-                    .lock(rid, txn_id)?;
+                    .lock(rid, txn_id, LockMode::Exclusive)?;
                 // println!("locked txn_id: {:?}, rid: {:?}", txn_id, rid);
                 lock_manager
                     .read()
@@ -152,4 +352,112 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_deadlock_detection() -> Result<()> {
+        let lock_manager = Arc::new(LockManager::default());
+        let rid1 = RID(PageID(1), 1);
+        let rid2 = RID(PageID(1), 2);
+        let rid3 = RID(PageID(1), 3);
+        lock_manager.lock(rid1, TransactionID(1), LockMode::Exclusive)?;
+        lock_manager.lock(rid2, TransactionID(2), LockMode::Exclusive)?;
+        lock_manager.lock(rid3, TransactionID(3), LockMode::Exclusive)?;
+        // 1 -> 2 -> 3 -> 1
+        let spawn_lock = |rid: RID, txn_id: TransactionID| {
+            let lock_manager = lock_manager.clone();
+            thread::spawn(move || lock_manager.lock(rid, txn_id, LockMode::Exclusive))
+        };
+        let handle1 = spawn_lock(rid2, TransactionID(1));
+        let handle2 = spawn_lock(rid3, TransactionID(2));
+        thread::sleep(std::time::Duration::from_millis(100));
+        let result = lock_manager.lock(rid1, TransactionID(3), LockMode::Exclusive);
+        assert!(result.unwrap_err().to_string().contains("deadlock"));
+
+        // the victim releases its locks when it is aborted
+        lock_manager.unlock(TransactionID(3))?;
+        handle2
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+        lock_manager.unlock(TransactionID(2))?;
+        handle1
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+        lock_manager.unlock(TransactionID(1))?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_lock_timeout() -> Result<()> {
+        let lock_manager = LockManager::default();
+        let rid = RID(PageID(1), 1);
+        lock_manager.lock(rid, TransactionID(1), LockMode::Exclusive)?;
+        assert!(lock_manager.try_lock(rid, TransactionID(1), LockMode::Exclusive)?);
+        assert!(!lock_manager.try_lock(rid, TransactionID(2), LockMode::Exclusive)?);
+
+        lock_manager.set_lock_timeout(TransactionID(2), Some(Duration::from_millis(100)))?;
+        let started_at = Instant::now();
+        let result = lock_manager.lock(rid, TransactionID(2), LockMode::Exclusive);
+        assert!(result.unwrap_err().to_string().contains("lock timeout"));
+        assert!(started_at.elapsed() >= Duration::from_millis(100));
+
+        lock_manager.unlock(TransactionID(1))?;
+        assert!(lock_manager.try_lock(rid, TransactionID(2), LockMode::Exclusive)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared_lock() -> Result<()> {
+        let lock_manager = Arc::new(LockManager::default());
+        let rid = RID(PageID(1), 1);
+        lock_manager.lock(rid, TransactionID(1), LockMode::Shared)?;
+        assert!(lock_manager.try_lock(rid, TransactionID(2), LockMode::Shared)?);
+        assert!(!lock_manager.try_lock(rid, TransactionID(3), LockMode::Exclusive)?);
+        // an upgrade waits for the other shared holders
+        assert!(!lock_manager.try_lock(rid, TransactionID(1), LockMode::Exclusive)?);
+
+        // both shared holders try to upgrade
+        let lock_manager_clone = lock_manager.clone();
+        let handle = thread::spawn(move || {
+            lock_manager_clone.lock(rid, TransactionID(1), LockMode::Exclusive)
+        });
+        thread::sleep(std::time::Duration::from_millis(100));
+        let result = lock_manager.lock(rid, TransactionID(2), LockMode::Exclusive);
+        assert!(result.unwrap_err().to_string().contains("deadlock"));
+        lock_manager.unlock(TransactionID(2))?;
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+
+        assert!(!lock_manager.try_lock(rid, TransactionID(3), LockMode::Shared)?);
+        // an exclusive lock is not downgraded
+        assert!(lock_manager.try_lock(rid, TransactionID(1), LockMode::Shared)?);
+        assert!(!lock_manager.try_lock(rid, TransactionID(3), LockMode::Shared)?);
+        lock_manager.unlock(TransactionID(1))?;
+        assert!(lock_manager.try_lock(rid, TransactionID(3), LockMode::Shared)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wait() -> Result<()> {
+        let lock_manager = Arc::new(LockManager::default());
+        let rid = RID(PageID(1), 1);
+        lock_manager.wait(rid, TransactionID(1))?;
+        lock_manager.lock(rid, TransactionID(1), LockMode::Exclusive)?;
+
+        let lock_manager_clone = lock_manager.clone();
+        let handle = thread::spawn(move || lock_manager_clone.wait(rid, TransactionID(2)));
+        thread::sleep(std::time::Duration::from_millis(100));
+        assert!(!handle.is_finished());
+        lock_manager.unlock(TransactionID(1))?;
+        handle
+            .join()
+            .map_err(|_| anyhow::anyhow!("thread error"))??;
+
+        // the waiter does not hold the lock afterwards
+        assert!(lock_manager.try_lock(rid, TransactionID(3), LockMode::Exclusive)?);
+        lock_manager.set_lock_timeout(TransactionID(2), Some(Duration::from_millis(100)))?;
+        let result = lock_manager.wait(rid, TransactionID(2));
+        assert!(result.unwrap_err().to_string().contains("lock timeout"));
+        Ok(())
+    }
 }
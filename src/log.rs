@@ -1,18 +1,29 @@
 use std::{
-    fs::{File, OpenOptions},
-    io::{Read, Seek, Write},
+    collections::{BTreeMap, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
 };
 
 use anyhow::Result;
 
-use crate::common::{PageID, TransactionID, LSN, RID};
+use crate::common::{
+    sync_parent_dir, PageID, TransactionID, INVALID_LSN, INVALID_TRANSACTION_ID, LSN, RID,
+};
 
 const LOG_BUFFER_SIZE: usize = 4096;
+// length, checksum, lsn, txn id and prev lsn
+const LOG_RECORD_HEADER_SIZE: usize = 28;
 
 pub struct LogManager {
     pub log_file: File,
     pub buffer: Vec<u8>,
     pub next_lsn: LSN,
+    log_file_path: String,
+    file_size: u64,
+    // past the end of the file for the records still in the buffer
+    offsets: BTreeMap<LSN, u64>,
+    // the newest record of each unfinished transaction, which its next record points back to
+    last_lsns: HashMap<TransactionID, LSN>,
 }
 impl LogManager {
     pub fn new(log_file_path: &str) -> Result<Self> {
@@ -20,84 +31,256 @@ impl LogManager {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(log_file_path)?;
         let mut log_manager = Self {
             log_file,
             buffer: vec![],
             next_lsn: LSN(1),
+            log_file_path: log_file_path.to_string(),
+            file_size: 0,
+            offsets: BTreeMap::new(),
+            last_lsns: HashMap::new(),
         };
         let records = log_manager.read()?;
         let next_lsn = records
             .last()
             .map_or(LSN(1), |record| LSN(record.lsn.0 + 1));
         log_manager.next_lsn = next_lsn;
+        // the chains of the transactions running at a crash are continued by their undo
+        for record in records.iter() {
+            log_manager
+                .offsets
+                .insert(record.lsn, log_manager.file_size);
+            log_manager.file_size += record.size() as u64;
+            log_manager.track(record.txn_id, record.lsn, &record.body);
+        }
+        // the torn tail of the last write before a crash, the new records are appended in its place
+        let file_length = log_manager.log_file.metadata()?.len();
+        if file_length > log_manager.file_size {
+            println!(
+                "truncating a torn log tail of {} bytes",
+                file_length - log_manager.file_size
+            );
+            log_manager.log_file.set_len(log_manager.file_size)?;
+            log_manager.log_file.sync_all()?;
+            log_manager.log_file.seek(SeekFrom::End(0))?;
+        }
         Ok(log_manager)
     }
     pub fn append(&mut self, txn_id: TransactionID, body: LogRecordBody) -> Result<LSN> {
         let lsn = self.next_lsn;
-        let log_record = LogRecord { lsn, txn_id, body };
+        let prev_lsn = match body {
+            LogRecordBody::BeginTransaction => INVALID_LSN,
+            _ => self.last_lsn(txn_id),
+        };
+        self.track(txn_id, lsn, &body);
+        let log_record = LogRecord {
+            lsn,
+            txn_id,
+            prev_lsn,
+            body,
+        };
         self.next_lsn.0 += 1;
 
         let bytes = log_record.serialize();
-        if bytes.len() > LOG_BUFFER_SIZE {
-            Err(anyhow::anyhow!("log record too large"))?;
-        }
+        // records with page images may be larger than the buffer, they are flushed by the next append
         if self.buffer.len() + bytes.len() > LOG_BUFFER_SIZE {
             self.flush()?;
         }
+        self.offsets
+            .insert(lsn, self.file_size + self.buffer.len() as u64);
         self.buffer.extend_from_slice(&bytes);
         Ok(lsn)
     }
+    pub fn last_lsn(&self, txn_id: TransactionID) -> LSN {
+        self.last_lsns.get(&txn_id).copied().unwrap_or(INVALID_LSN)
+    }
+    // reads one record, from the buffer if it is not flushed yet
+    pub fn read_record(&mut self, lsn: LSN) -> Result<LogRecord> {
+        let offset = *self
+            .offsets
+            .get(&lsn)
+            .ok_or_else(|| anyhow::anyhow!("log record {} not found", lsn.0))?;
+        if offset >= self.file_size {
+            let start = (offset - self.file_size) as usize;
+            return LogRecord::try_from(&self.buffer[start..]);
+        }
+        // the buffer starts at the end of the file, so a record in the file ends there at the latest
+        let end = self
+            .offsets
+            .range(LSN(lsn.0 + 1)..)
+            .next()
+            .map_or(self.file_size, |(_, offset)| *offset)
+            .min(self.file_size);
+        let mut buffer = vec![0u8; (end - offset) as usize];
+        self.log_file.seek(SeekFrom::Start(offset))?;
+        self.log_file.read_exact(&mut buffer)?;
+        self.log_file.seek(SeekFrom::End(0))?;
+        LogRecord::try_from(&buffer[..])
+    }
+    // stops at the first invalid record. it is the torn tail of the last write if no valid record follows,
+    // otherwise the log is broken in the middle, and replaying past it would lose changes
     pub fn read(&mut self) -> Result<Vec<LogRecord>> {
         let mut buffer = vec![];
-        self.log_file.seek(std::io::SeekFrom::Start(0))?;
+        self.log_file.seek(SeekFrom::Start(0))?;
         self.log_file.read_to_end(&mut buffer)?;
-        let mut records = vec![];
+        let mut records: Vec<LogRecord> = vec![];
         let mut offset = 0;
         while offset < buffer.len() {
-            let record = LogRecord::from(&buffer[offset..]);
-            offset += record.size();
+            let size = match LogRecord::check(&buffer[offset..]) {
+                Ok(size) => size,
+                Err(e) => {
+                    let last_lsn = records.last().map_or(INVALID_LSN, |record| record.lsn);
+                    if let Some(next_offset) = (offset + 1..buffer.len()).find(|&next_offset| {
+                        LogRecord::try_from(&buffer[next_offset..])
+                            .is_ok_and(|record| record.lsn > last_lsn)
+                    }) {
+                        return Err(anyhow::anyhow!(
+                            "log record at offset {} after lsn {} is corrupted ({}), but a valid record follows at offset {}",
+                            offset,
+                            last_lsn.0,
+                            e,
+                            next_offset
+                        ));
+                    }
+                    break;
+                }
+            };
+            // an intact record which can not be decoded was written by another version
+            let record = LogRecord::decode(&buffer[offset..offset + size]).map_err(|e| {
+                anyhow::anyhow!("log record at offset {} can not be decoded ({})", offset, e)
+            })?;
+            offset += size;
             records.push(record);
         }
         Ok(records)
     }
     pub fn flush(&mut self) -> Result<()> {
-        self.log_file.write_all(&self.buffer)?;
+        self.write_buffer()?;
         self.log_file.sync_all()?;
+        Ok(())
+    }
+    // like flush, but the returned file is synced by the caller after releasing the log,
+    // so that records can be appended during the sync. the records up to the lsn are written
+    pub fn write(&mut self) -> Result<(File, LSN)> {
+        self.write_buffer()?;
+        Ok((self.log_file.try_clone()?, LSN(self.next_lsn.0 - 1)))
+    }
+    fn write_buffer(&mut self) -> Result<()> {
+        self.log_file.write_all(&self.buffer)?;
+        self.file_size += self.buffer.len() as u64;
         self.buffer.clear();
         Ok(())
     }
+    // removes the records before the lsn once they take at least as much space as the rest, so
+    // that a kept record is copied about once however often this is called. the rest is written
+    // to a new file which replaces the log
+    pub fn truncate(&mut self, lsn: LSN) -> Result<()> {
+        self.flush()?;
+        let start = self
+            .offsets
+            .range(lsn..)
+            .next()
+            .map_or(self.file_size, |(_, offset)| *offset);
+        if start == 0 || start < self.file_size - start {
+            return Ok(());
+        }
+        let mut buffer = vec![0u8; (self.file_size - start) as usize];
+        self.log_file.seek(SeekFrom::Start(start))?;
+        self.log_file.read_exact(&mut buffer)?;
+        let temp_file_path = format!("{}.tmp", self.log_file_path);
+        let mut temp_file = File::create(&temp_file_path)?;
+        temp_file.write_all(&buffer)?;
+        temp_file.sync_all()?;
+        fs::rename(&temp_file_path, &self.log_file_path)?;
+        sync_parent_dir(&self.log_file_path)?;
+        self.log_file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.log_file_path)?;
+        self.log_file.seek(SeekFrom::End(0))?;
+        self.offsets = self
+            .offsets
+            .split_off(&lsn)
+            .into_iter()
+            .map(|(lsn, offset)| (lsn, offset - start))
+            .collect();
+        self.file_size -= start;
+        Ok(())
+    }
+    fn track(&mut self, txn_id: TransactionID, lsn: LSN, body: &LogRecordBody) {
+        if txn_id == INVALID_TRANSACTION_ID {
+            return;
+        }
+        match body {
+            LogRecordBody::CommitTransaction | LogRecordBody::AbortTransaction => {
+                self.last_lsns.remove(&txn_id);
+            }
+            _ => {
+                self.last_lsns.insert(txn_id, lsn);
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LogRecord {
     pub lsn: LSN,
     pub txn_id: TransactionID,
+    // the previous record of the transaction, undo follows the chain backwards
+    pub prev_lsn: LSN,
     pub body: LogRecordBody,
 }
 
-impl From<&[u8]> for LogRecord {
-    fn from(bytes: &[u8]) -> Self {
-        let mut buffer = [0u8; 8];
-        buffer.copy_from_slice(&bytes[0..8]);
-        let lsn = LSN(u64::from_be_bytes(buffer));
-        let mut buffer = [0u8; 4];
-        buffer.copy_from_slice(&bytes[8..12]);
-        let txn_id = TransactionID(u32::from_be_bytes(buffer));
-        let body = LogRecordBody::from(&bytes[12..]);
-        Self { lsn, txn_id, body }
+impl TryFrom<&[u8]> for LogRecord {
+    type Error = anyhow::Error;
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let size = Self::check(bytes)?;
+        Self::decode(&bytes[..size])
     }
 }
 impl LogRecord {
+    // the size of the record at the start of the bytes, if it is complete and intact
+    fn check(bytes: &[u8]) -> Result<usize> {
+        if bytes.len() < LOG_RECORD_HEADER_SIZE {
+            return Err(anyhow::anyhow!("incomplete header"));
+        }
+        let size = read_u32(bytes, 0) as usize;
+        if size < LOG_RECORD_HEADER_SIZE {
+            return Err(anyhow::anyhow!("invalid length {}", size));
+        }
+        if size > bytes.len() {
+            return Err(anyhow::anyhow!("length {} past the end of the log", size));
+        }
+        if crc32fast::hash(&bytes[8..size]) != read_u32(bytes, 4) {
+            return Err(anyhow::anyhow!("checksum mismatch"));
+        }
+        Ok(size)
+    }
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(Self {
+            lsn: LSN(read_u64(bytes, 8)),
+            txn_id: TransactionID(read_u32(bytes, 16)),
+            prev_lsn: LSN(read_u64(bytes, 20)),
+            body: LogRecordBody::try_from(&bytes[LOG_RECORD_HEADER_SIZE..])?,
+        })
+    }
+    // the checksum covers everything after it, so that a torn or broken record is not replayed
     fn serialize(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(self.size() as u32).to_be_bytes());
+        buffer.extend_from_slice(&[0; 4]);
         buffer.extend_from_slice(&self.lsn.0.to_be_bytes());
         buffer.extend_from_slice(&self.txn_id.0.to_be_bytes());
+        buffer.extend_from_slice(&self.prev_lsn.0.to_be_bytes());
         buffer.extend_from_slice(&self.body.serialize());
+        let checksum = crc32fast::hash(&buffer[8..]);
+        buffer[4..8].copy_from_slice(&checksum.to_be_bytes());
         buffer
     }
     fn size(&self) -> usize {
-        12 + self.body.size()
+        LOG_RECORD_HEADER_SIZE + self.body.size()
     }
 }
 
@@ -111,16 +294,63 @@ pub enum LogRecordBody {
     SetNextPageID(SetNextPageID),
     NewTablePage(NewTablePage),
     NewBPlusTreeLeafPage(NewBPlusTreeLeafPage),
+    InsertToBPlusTreeLeafPage(BPlusTreePageEntry),
+    DeleteFromBPlusTreeLeafPage(BPlusTreePageEntry),
+    InsertToBPlusTreeInternalPage(BPlusTreePageEntry),
+    SplitBPlusTreePage(SplitBPlusTreePage),
+    NewBPlusTreeRootPage(NewBPlusTreeRootPage),
+    SetBPlusTreeParentPageID(SetBPlusTreeParentPageID),
+    SetBPlusTreePrevPageID(SetBPlusTreePrevPageID),
+    LoadBPlusTreePage(LoadBPlusTreePage),
+    UndoDeleteFromTablePage(UndoDeleteFromTablePage),
+    PruneTablePage(PruneTablePage),
+    Checkpoint(Checkpoint),
+    Compensation(Compensation),
+    FreePage(FreePage),
+    ReuseFreePage(ReuseFreePage),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct InsertToTablePage {
-    pub page_id: PageID,
+    pub rid: RID,
     pub data: Box<[u8]>,
 }
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DeleteFromTablePage {
     pub rid: RID,
+    // restored by the undo
+    pub prev_xmax: TransactionID,
+}
+// restores the xmax of a tuple whose delete is undone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndoDeleteFromTablePage {
+    pub rid: RID,
+    pub xmax: TransactionID,
+}
+// dead tuples removed by vacuum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PruneTablePage {
+    pub page_id: PageID,
+    pub indexes: Vec<u32>,
+}
+// taken without stopping the writers, redo starts from the oldest lsn of the dirty pages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpoint {
+    // with the lsns of their begin records
+    pub active_transactions: Vec<(TransactionID, LSN)>,
+    // with the lsns from which their changes may not be on disk
+    pub dirty_pages: Vec<(PageID, LSN)>,
+    // the other transactions before next_txn_id which are not running have committed
+    pub next_txn_id: TransactionID,
+    pub aborted_transactions: Vec<TransactionID>,
+    pub free_page_ids: Vec<PageID>,
+}
+// a change made by undo, which is redone but never undone itself
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compensation {
+    // the next record of the transaction to undo, the ones after it are already undone
+    pub undo_next_lsn: LSN,
+    pub body: Box<LogRecordBody>,
 }
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SetNextPageID {
@@ -135,13 +365,61 @@ pub struct NewTablePage {
 pub struct NewBPlusTreeLeafPage {
     pub page_id: PageID,
 }
+// entries are logged as raw bytes with their position, so that redo does not need the key schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BPlusTreePageEntry {
+    pub page_id: PageID,
+    pub index: u32,
+    pub entry: Box<[u8]>,
+}
+// images of both pages right after the split
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitBPlusTreePage {
+    pub page_id: PageID,
+    pub data: Box<[u8]>,
+    pub new_page_id: PageID,
+    pub new_data: Box<[u8]>,
+}
+// images of the rebuilt root and the page which took over its entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewBPlusTreeRootPage {
+    pub page_id: PageID,
+    pub data: Box<[u8]>,
+    pub left_page_id: PageID,
+    pub left_data: Box<[u8]>,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetBPlusTreeParentPageID {
+    pub page_id: PageID,
+    pub parent_page_id: PageID,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetBPlusTreePrevPageID {
+    pub page_id: PageID,
+    pub prev_page_id: PageID,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadBPlusTreePage {
+    pub page_id: PageID,
+    pub data: Box<[u8]>,
+}
+// the free list is rebuilt from these and the last checkpoint, the pages themselves are not changed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FreePage {
+    pub page_id: PageID,
+}
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReuseFreePage {
+    pub page_id: PageID,
+}
 
-impl From<&[u8]> for LogRecordBody {
-    fn from(bytes: &[u8]) -> Self {
+impl TryFrom<&[u8]> for LogRecordBody {
+    type Error = anyhow::Error;
+    fn try_from(bytes: &[u8]) -> Result<Self> {
         let mut buffer = [0u8; 4];
         buffer.copy_from_slice(&bytes[0..4]);
         let type_id = u32::from_be_bytes(buffer);
-        match type_id {
+        Ok(match type_id {
             0 => LogRecordBody::BeginTransaction,
             1 => LogRecordBody::CommitTransaction,
             2 => LogRecordBody::AbortTransaction,
@@ -150,8 +428,28 @@ impl From<&[u8]> for LogRecordBody {
             5 => LogRecordBody::SetNextPageID(SetNextPageID::from(&bytes[4..])),
             6 => LogRecordBody::NewTablePage(NewTablePage::from(&bytes[4..])),
             7 => LogRecordBody::NewBPlusTreeLeafPage(NewBPlusTreeLeafPage::from(&bytes[4..])),
-            _ => panic!("invalid log record type id"),
-        }
+            8 => LogRecordBody::InsertToBPlusTreeLeafPage(BPlusTreePageEntry::from(&bytes[4..])),
+            9 => LogRecordBody::DeleteFromBPlusTreeLeafPage(BPlusTreePageEntry::from(&bytes[4..])),
+            10 => {
+                LogRecordBody::InsertToBPlusTreeInternalPage(BPlusTreePageEntry::from(&bytes[4..]))
+            }
+            11 => LogRecordBody::SplitBPlusTreePage(SplitBPlusTreePage::from(&bytes[4..])),
+            12 => LogRecordBody::NewBPlusTreeRootPage(NewBPlusTreeRootPage::from(&bytes[4..])),
+            13 => {
+                LogRecordBody::SetBPlusTreeParentPageID(SetBPlusTreeParentPageID::from(&bytes[4..]))
+            }
+            14 => LogRecordBody::SetBPlusTreePrevPageID(SetBPlusTreePrevPageID::from(&bytes[4..])),
+            15 => LogRecordBody::LoadBPlusTreePage(LoadBPlusTreePage::from(&bytes[4..])),
+            16 => {
+                LogRecordBody::UndoDeleteFromTablePage(UndoDeleteFromTablePage::from(&bytes[4..]))
+            }
+            17 => LogRecordBody::PruneTablePage(PruneTablePage::from(&bytes[4..])),
+            18 => LogRecordBody::Checkpoint(Checkpoint::from(&bytes[4..])),
+            19 => LogRecordBody::Compensation(Compensation::try_from(&bytes[4..])?),
+            20 => LogRecordBody::FreePage(FreePage::from(&bytes[4..])),
+            21 => LogRecordBody::ReuseFreePage(ReuseFreePage::from(&bytes[4..])),
+            _ => return Err(anyhow::anyhow!("unknown log record type {}", type_id)),
+        })
     }
 }
 impl LogRecordBody {
@@ -187,6 +485,62 @@ impl LogRecordBody {
                 buffer.extend_from_slice(&(7u32).to_be_bytes());
                 buffer.extend_from_slice(&body.serialize());
             }
+            LogRecordBody::InsertToBPlusTreeLeafPage(body) => {
+                buffer.extend_from_slice(&(8u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::DeleteFromBPlusTreeLeafPage(body) => {
+                buffer.extend_from_slice(&(9u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::InsertToBPlusTreeInternalPage(body) => {
+                buffer.extend_from_slice(&(10u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::SplitBPlusTreePage(body) => {
+                buffer.extend_from_slice(&(11u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::NewBPlusTreeRootPage(body) => {
+                buffer.extend_from_slice(&(12u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::SetBPlusTreeParentPageID(body) => {
+                buffer.extend_from_slice(&(13u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::SetBPlusTreePrevPageID(body) => {
+                buffer.extend_from_slice(&(14u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::LoadBPlusTreePage(body) => {
+                buffer.extend_from_slice(&(15u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::UndoDeleteFromTablePage(body) => {
+                buffer.extend_from_slice(&(16u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::PruneTablePage(body) => {
+                buffer.extend_from_slice(&(17u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::Checkpoint(body) => {
+                buffer.extend_from_slice(&(18u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::Compensation(body) => {
+                buffer.extend_from_slice(&(19u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::FreePage(body) => {
+                buffer.extend_from_slice(&(20u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
+            LogRecordBody::ReuseFreePage(body) => {
+                buffer.extend_from_slice(&(21u32).to_be_bytes());
+                buffer.extend_from_slice(&body.serialize());
+            }
         }
         buffer
     }
@@ -200,44 +554,49 @@ impl LogRecordBody {
             LogRecordBody::SetNextPageID(body) => 4 + body.size(),
             LogRecordBody::NewTablePage(body) => 4 + body.size(),
             LogRecordBody::NewBPlusTreeLeafPage(body) => 4 + body.size(),
+            LogRecordBody::InsertToBPlusTreeLeafPage(body) => 4 + body.size(),
+            LogRecordBody::DeleteFromBPlusTreeLeafPage(body) => 4 + body.size(),
+            LogRecordBody::InsertToBPlusTreeInternalPage(body) => 4 + body.size(),
+            LogRecordBody::SplitBPlusTreePage(body) => 4 + body.size(),
+            LogRecordBody::NewBPlusTreeRootPage(body) => 4 + body.size(),
+            LogRecordBody::SetBPlusTreeParentPageID(body) => 4 + body.size(),
+            LogRecordBody::SetBPlusTreePrevPageID(body) => 4 + body.size(),
+            LogRecordBody::LoadBPlusTreePage(body) => 4 + body.size(),
+            LogRecordBody::UndoDeleteFromTablePage(body) => 4 + body.size(),
+            LogRecordBody::PruneTablePage(body) => 4 + body.size(),
+            LogRecordBody::Checkpoint(body) => 4 + body.size(),
+            LogRecordBody::Compensation(body) => 4 + body.size(),
+            LogRecordBody::FreePage(body) => 4 + body.size(),
+            LogRecordBody::ReuseFreePage(body) => 4 + body.size(),
         }
     }
 }
 
 impl From<&[u8]> for InsertToTablePage {
     fn from(bytes: &[u8]) -> Self {
-        let mut buffer = [0u8; 4];
-        buffer.copy_from_slice(&bytes[0..4]);
-        let page_id = PageID(u32::from_be_bytes(buffer));
-        let mut buffer = [0u8; 4];
-        buffer.copy_from_slice(&bytes[4..8]);
-        let size = u32::from_be_bytes(buffer);
-        let data = bytes[8..(8 + size as usize)].to_vec().into_boxed_slice();
-        InsertToTablePage { page_id, data }
+        InsertToTablePage {
+            rid: RID(PageID(read_u32(bytes, 0)), read_u32(bytes, 4)),
+            data: read_bytes(bytes, 8),
+        }
     }
 }
 impl InsertToTablePage {
     fn serialize(&self) -> Vec<u8> {
         let mut buffer = Vec::new();
-        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
-        buffer.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
-        buffer.extend_from_slice(&self.data);
+        buffer.extend_from_slice(&self.rid.0 .0.to_be_bytes());
+        buffer.extend_from_slice(&self.rid.1.to_be_bytes());
+        write_bytes(&mut buffer, &self.data);
         buffer
     }
     fn size(&self) -> usize {
-        8 + self.data.len()
+        12 + self.data.len()
     }
 }
 impl From<&[u8]> for DeleteFromTablePage {
     fn from(bytes: &[u8]) -> Self {
-        let mut buffer = [0u8; 4];
-        buffer.copy_from_slice(&bytes[0..4]);
-        let page_id = PageID(u32::from_be_bytes(buffer));
-        let mut buffer = [0u8; 4];
-        buffer.copy_from_slice(&bytes[4..8]);
-        let index = u32::from_be_bytes(buffer);
         DeleteFromTablePage {
-            rid: RID(page_id, index),
+            rid: RID(PageID(read_u32(bytes, 0)), read_u32(bytes, 4)),
+            prev_xmax: TransactionID(read_u32(bytes, 8)),
         }
     }
 }
@@ -246,10 +605,146 @@ impl DeleteFromTablePage {
         let mut buffer = Vec::new();
         buffer.extend_from_slice(&self.rid.0 .0.to_be_bytes());
         buffer.extend_from_slice(&self.rid.1.to_be_bytes());
+        buffer.extend_from_slice(&self.prev_xmax.0.to_be_bytes());
         buffer
     }
     fn size(&self) -> usize {
-        8
+        12
+    }
+}
+impl From<&[u8]> for UndoDeleteFromTablePage {
+    fn from(bytes: &[u8]) -> Self {
+        UndoDeleteFromTablePage {
+            rid: RID(PageID(read_u32(bytes, 0)), read_u32(bytes, 4)),
+            xmax: TransactionID(read_u32(bytes, 8)),
+        }
+    }
+}
+impl UndoDeleteFromTablePage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.rid.0 .0.to_be_bytes());
+        buffer.extend_from_slice(&self.rid.1.to_be_bytes());
+        buffer.extend_from_slice(&self.xmax.0.to_be_bytes());
+        buffer
+    }
+    fn size(&self) -> usize {
+        12
+    }
+}
+impl From<&[u8]> for PruneTablePage {
+    fn from(bytes: &[u8]) -> Self {
+        let count = read_u32(bytes, 4) as usize;
+        PruneTablePage {
+            page_id: PageID(read_u32(bytes, 0)),
+            indexes: (0..count).map(|i| read_u32(bytes, 8 + i * 4)).collect(),
+        }
+    }
+}
+impl PruneTablePage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        buffer.extend_from_slice(&(self.indexes.len() as u32).to_be_bytes());
+        for index in &self.indexes {
+            buffer.extend_from_slice(&index.to_be_bytes());
+        }
+        buffer
+    }
+    fn size(&self) -> usize {
+        8 + self.indexes.len() * 4
+    }
+}
+impl From<&[u8]> for Checkpoint {
+    fn from(bytes: &[u8]) -> Self {
+        let active_transaction_count = read_u32(bytes, 0) as usize;
+        let active_transactions = (0..active_transaction_count)
+            .map(|i| {
+                let offset = 4 + i * 12;
+                (
+                    TransactionID(read_u32(bytes, offset)),
+                    LSN(read_u64(bytes, offset + 4)),
+                )
+            })
+            .collect();
+        let offset = 4 + active_transaction_count * 12;
+        let dirty_page_count = read_u32(bytes, offset) as usize;
+        let dirty_pages = (0..dirty_page_count)
+            .map(|i| {
+                let offset = offset + 4 + i * 12;
+                (
+                    PageID(read_u32(bytes, offset)),
+                    LSN(read_u64(bytes, offset + 4)),
+                )
+            })
+            .collect();
+        let offset = offset + 4 + dirty_page_count * 12;
+        let next_txn_id = TransactionID(read_u32(bytes, offset));
+        let aborted_transaction_count = read_u32(bytes, offset + 4) as usize;
+        let aborted_transactions = (0..aborted_transaction_count)
+            .map(|i| TransactionID(read_u32(bytes, offset + 8 + i * 4)))
+            .collect();
+        let offset = offset + 8 + aborted_transaction_count * 4;
+        let free_page_count = read_u32(bytes, offset) as usize;
+        let free_page_ids = (0..free_page_count)
+            .map(|i| PageID(read_u32(bytes, offset + 4 + i * 4)))
+            .collect();
+        Checkpoint {
+            active_transactions,
+            dirty_pages,
+            next_txn_id,
+            aborted_transactions,
+            free_page_ids,
+        }
+    }
+}
+impl Checkpoint {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&(self.active_transactions.len() as u32).to_be_bytes());
+        for (txn_id, lsn) in &self.active_transactions {
+            buffer.extend_from_slice(&txn_id.0.to_be_bytes());
+            buffer.extend_from_slice(&lsn.0.to_be_bytes());
+        }
+        buffer.extend_from_slice(&(self.dirty_pages.len() as u32).to_be_bytes());
+        for (page_id, lsn) in &self.dirty_pages {
+            buffer.extend_from_slice(&page_id.0.to_be_bytes());
+            buffer.extend_from_slice(&lsn.0.to_be_bytes());
+        }
+        buffer.extend_from_slice(&self.next_txn_id.0.to_be_bytes());
+        buffer.extend_from_slice(&(self.aborted_transactions.len() as u32).to_be_bytes());
+        for txn_id in &self.aborted_transactions {
+            buffer.extend_from_slice(&txn_id.0.to_be_bytes());
+        }
+        buffer.extend_from_slice(&(self.free_page_ids.len() as u32).to_be_bytes());
+        for page_id in &self.free_page_ids {
+            buffer.extend_from_slice(&page_id.0.to_be_bytes());
+        }
+        buffer
+    }
+    fn size(&self) -> usize {
+        20 + (self.active_transactions.len() + self.dirty_pages.len()) * 12
+            + (self.aborted_transactions.len() + self.free_page_ids.len()) * 4
+    }
+}
+impl TryFrom<&[u8]> for Compensation {
+    type Error = anyhow::Error;
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Ok(Compensation {
+            undo_next_lsn: LSN(read_u64(bytes, 0)),
+            body: Box::new(LogRecordBody::try_from(&bytes[8..])?),
+        })
+    }
+}
+impl Compensation {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.undo_next_lsn.0.to_be_bytes());
+        buffer.extend_from_slice(&self.body.serialize());
+        buffer
+    }
+    fn size(&self) -> usize {
+        8 + self.body.size()
     }
 }
 impl From<&[u8]> for SetNextPageID {
@@ -314,9 +809,199 @@ impl NewBPlusTreeLeafPage {
     }
 }
 
+impl From<&[u8]> for BPlusTreePageEntry {
+    fn from(bytes: &[u8]) -> Self {
+        BPlusTreePageEntry {
+            page_id: PageID(read_u32(bytes, 0)),
+            index: read_u32(bytes, 4),
+            entry: read_bytes(bytes, 8),
+        }
+    }
+}
+impl BPlusTreePageEntry {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        buffer.extend_from_slice(&self.index.to_be_bytes());
+        write_bytes(&mut buffer, &self.entry);
+        buffer
+    }
+    fn size(&self) -> usize {
+        12 + self.entry.len()
+    }
+}
+impl From<&[u8]> for SplitBPlusTreePage {
+    fn from(bytes: &[u8]) -> Self {
+        let page_id = PageID(read_u32(bytes, 0));
+        let data = read_bytes(bytes, 4);
+        let offset = 8 + data.len();
+        let new_page_id = PageID(read_u32(bytes, offset));
+        let new_data = read_bytes(bytes, offset + 4);
+        SplitBPlusTreePage {
+            page_id,
+            data,
+            new_page_id,
+            new_data,
+        }
+    }
+}
+impl SplitBPlusTreePage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        write_bytes(&mut buffer, &self.data);
+        buffer.extend_from_slice(&self.new_page_id.0.to_be_bytes());
+        write_bytes(&mut buffer, &self.new_data);
+        buffer
+    }
+    fn size(&self) -> usize {
+        16 + self.data.len() + self.new_data.len()
+    }
+}
+impl From<&[u8]> for NewBPlusTreeRootPage {
+    fn from(bytes: &[u8]) -> Self {
+        let page_id = PageID(read_u32(bytes, 0));
+        let data = read_bytes(bytes, 4);
+        let offset = 8 + data.len();
+        let left_page_id = PageID(read_u32(bytes, offset));
+        let left_data = read_bytes(bytes, offset + 4);
+        NewBPlusTreeRootPage {
+            page_id,
+            data,
+            left_page_id,
+            left_data,
+        }
+    }
+}
+impl NewBPlusTreeRootPage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        write_bytes(&mut buffer, &self.data);
+        buffer.extend_from_slice(&self.left_page_id.0.to_be_bytes());
+        write_bytes(&mut buffer, &self.left_data);
+        buffer
+    }
+    fn size(&self) -> usize {
+        16 + self.data.len() + self.left_data.len()
+    }
+}
+impl From<&[u8]> for SetBPlusTreeParentPageID {
+    fn from(bytes: &[u8]) -> Self {
+        SetBPlusTreeParentPageID {
+            page_id: PageID(read_u32(bytes, 0)),
+            parent_page_id: PageID(read_u32(bytes, 4)),
+        }
+    }
+}
+impl SetBPlusTreeParentPageID {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        buffer.extend_from_slice(&self.parent_page_id.0.to_be_bytes());
+        buffer
+    }
+    fn size(&self) -> usize {
+        8
+    }
+}
+impl From<&[u8]> for SetBPlusTreePrevPageID {
+    fn from(bytes: &[u8]) -> Self {
+        SetBPlusTreePrevPageID {
+            page_id: PageID(read_u32(bytes, 0)),
+            prev_page_id: PageID(read_u32(bytes, 4)),
+        }
+    }
+}
+impl SetBPlusTreePrevPageID {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        buffer.extend_from_slice(&self.prev_page_id.0.to_be_bytes());
+        buffer
+    }
+    fn size(&self) -> usize {
+        8
+    }
+}
+impl From<&[u8]> for LoadBPlusTreePage {
+    fn from(bytes: &[u8]) -> Self {
+        LoadBPlusTreePage {
+            page_id: PageID(read_u32(bytes, 0)),
+            data: read_bytes(bytes, 4),
+        }
+    }
+}
+impl LoadBPlusTreePage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        write_bytes(&mut buffer, &self.data);
+        buffer
+    }
+    fn size(&self) -> usize {
+        8 + self.data.len()
+    }
+}
+impl From<&[u8]> for FreePage {
+    fn from(bytes: &[u8]) -> Self {
+        FreePage {
+            page_id: PageID(read_u32(bytes, 0)),
+        }
+    }
+}
+impl FreePage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        buffer
+    }
+    fn size(&self) -> usize {
+        4
+    }
+}
+impl From<&[u8]> for ReuseFreePage {
+    fn from(bytes: &[u8]) -> Self {
+        ReuseFreePage {
+            page_id: PageID(read_u32(bytes, 0)),
+        }
+    }
+}
+impl ReuseFreePage {
+    fn serialize(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(&self.page_id.0.to_be_bytes());
+        buffer
+    }
+    fn size(&self) -> usize {
+        4
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buffer = [0u8; 4];
+    buffer.copy_from_slice(&bytes[offset..(offset + 4)]);
+    u32::from_be_bytes(buffer)
+}
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    let mut buffer = [0u8; 8];
+    buffer.copy_from_slice(&bytes[offset..offset + 8]);
+    u64::from_be_bytes(buffer)
+}
+// reads bytes prefixed with their length
+fn read_bytes(bytes: &[u8], offset: usize) -> Box<[u8]> {
+    let size = read_u32(bytes, offset) as usize;
+    bytes[(offset + 4)..(offset + 4 + size)].into()
+}
+fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::PAGE_SIZE;
     use tempfile::tempdir;
 
     #[test]
@@ -329,7 +1014,7 @@ mod tests {
         log_manager.append(
             TransactionID(1),
             LogRecordBody::InsertToTablePage(InsertToTablePage {
-                page_id: PageID(1),
+                rid: RID(PageID(1), 0),
                 data: vec![1, 2, 3].into(),
             }),
         )?;
@@ -339,6 +1024,7 @@ mod tests {
             TransactionID(2),
             LogRecordBody::DeleteFromTablePage(DeleteFromTablePage {
                 rid: RID(PageID(1), 0),
+                prev_xmax: INVALID_TRANSACTION_ID,
             }),
         )?;
         log_manager.append(
@@ -370,10 +1056,11 @@ mod tests {
         assert_eq!(
             records[1].body,
             LogRecordBody::InsertToTablePage(InsertToTablePage {
-                page_id: PageID(1),
+                rid: RID(PageID(1), 0),
                 data: vec![1, 2, 3].into(),
             })
         );
+        assert_eq!(records[1].prev_lsn, LSN(1));
         assert_eq!(records[2].lsn, LSN(3));
         assert_eq!(records[2].txn_id, TransactionID(1));
         assert_eq!(records[2].body, LogRecordBody::CommitTransaction);
@@ -383,8 +1070,10 @@ mod tests {
             records[4].body,
             LogRecordBody::DeleteFromTablePage(DeleteFromTablePage {
                 rid: RID(PageID(1), 0),
+                prev_xmax: INVALID_TRANSACTION_ID,
             })
         );
+        assert_eq!(records[4].prev_lsn, LSN(4));
         assert_eq!(records[5].lsn, LSN(6));
         assert_eq!(records[5].txn_id, TransactionID(2));
         assert_eq!(
@@ -412,4 +1101,266 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_prev_lsn_chain() -> Result<()> {
+        let dir = tempdir()?;
+        let log_file_path = dir.path().join("log");
+        let mut log_manager = LogManager::new(log_file_path.to_str().unwrap())?;
+        let txn_id1 = TransactionID(1);
+        let txn_id2 = TransactionID(2);
+        log_manager.append(txn_id1, LogRecordBody::BeginTransaction)?;
+        log_manager.append(txn_id2, LogRecordBody::BeginTransaction)?;
+        log_manager.append(
+            txn_id1,
+            LogRecordBody::NewTablePage(NewTablePage { page_id: PageID(1) }),
+        )?;
+        log_manager.append(
+            INVALID_TRANSACTION_ID,
+            LogRecordBody::PruneTablePage(PruneTablePage {
+                page_id: PageID(1),
+                indexes: vec![],
+            }),
+        )?;
+        log_manager.flush()?;
+        log_manager.append(
+            txn_id1,
+            LogRecordBody::NewTablePage(NewTablePage { page_id: PageID(2) }),
+        )?;
+        log_manager.append(txn_id2, LogRecordBody::CommitTransaction)?;
+        assert_eq!(log_manager.last_lsn(txn_id1), LSN(5));
+        assert_eq!(log_manager.last_lsn(txn_id2), INVALID_LSN);
+
+        // from the buffer and from the file
+        let record = log_manager.read_record(LSN(5))?;
+        assert_eq!(record.prev_lsn, LSN(3));
+        let record = log_manager.read_record(record.prev_lsn)?;
+        assert_eq!(
+            record.body,
+            LogRecordBody::NewTablePage(NewTablePage { page_id: PageID(1) })
+        );
+        assert_eq!(
+            log_manager.read_record(record.prev_lsn)?.prev_lsn,
+            INVALID_LSN
+        );
+        assert_eq!(log_manager.read_record(LSN(4))?.prev_lsn, INVALID_LSN);
+        // appended after the read
+        log_manager.append(txn_id1, LogRecordBody::AbortTransaction)?;
+        log_manager.flush()?;
+
+        // the chains of unfinished transactions are restored
+        log_manager.append(TransactionID(3), LogRecordBody::BeginTransaction)?;
+        log_manager.flush()?;
+        let mut log_manager = LogManager::new(log_file_path.to_str().unwrap())?;
+        assert_eq!(log_manager.last_lsn(txn_id1), INVALID_LSN);
+        assert_eq!(log_manager.last_lsn(TransactionID(3)), LSN(8));
+        assert_eq!(log_manager.read_record(LSN(7))?.prev_lsn, LSN(5));
+        log_manager.truncate(LSN(5))?;
+        assert_eq!(
+            log_manager.read_record(LSN(8))?.body,
+            LogRecordBody::BeginTransaction
+        );
+        assert!(log_manager.read_record(LSN(4)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate() -> Result<()> {
+        let dir = tempdir()?;
+        let log_file_path = dir.path().join("log");
+        let mut log_manager = LogManager::new(log_file_path.to_str().unwrap())?;
+        for i in 1..=5 {
+            log_manager.append(TransactionID(i), LogRecordBody::BeginTransaction)?;
+        }
+        let lsns = |log_manager: &mut LogManager| -> Result<Vec<LSN>> {
+            Ok(log_manager.read()?.iter().map(|r| r.lsn).collect())
+        };
+        // the records to remove are fewer than the rest, the log is left as it is
+        log_manager.truncate(LSN(3))?;
+        assert_eq!(lsns(&mut log_manager)?.len(), 5);
+        log_manager.truncate(LSN(4))?;
+        assert_eq!(lsns(&mut log_manager)?, vec![LSN(4), LSN(5)]);
+        // appended after the kept records
+        log_manager.append(TransactionID(6), LogRecordBody::BeginTransaction)?;
+        log_manager.flush()?;
+        assert_eq!(log_manager.read_record(LSN(5))?.txn_id, TransactionID(5));
+
+        let mut log_manager = LogManager::new(log_file_path.to_str().unwrap())?;
+        assert_eq!(log_manager.next_lsn, LSN(7));
+        assert_eq!(lsns(&mut log_manager)?, vec![LSN(4), LSN(5), LSN(6)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_torn_tail() -> Result<()> {
+        let dir = tempdir()?;
+        let log_file_path = dir.path().join("log");
+        let log_file_path = log_file_path.to_str().unwrap();
+        let mut log_manager = LogManager::new(log_file_path)?;
+        for i in 1..=3 {
+            log_manager.append(TransactionID(i), LogRecordBody::BeginTransaction)?;
+        }
+        log_manager.flush()?;
+        let file_size = fs::metadata(log_file_path)?.len();
+        let torn_record = LogRecord {
+            lsn: LSN(4),
+            txn_id: INVALID_TRANSACTION_ID,
+            prev_lsn: INVALID_LSN,
+            body: LogRecordBody::LoadBPlusTreePage(LoadBPlusTreePage {
+                page_id: PageID(1),
+                data: vec![1; PAGE_SIZE].into(),
+            }),
+        }
+        .serialize();
+        // half of the record, and the whole record with its end not written
+        let mut broken_record = torn_record.clone();
+        broken_record[torn_record.len() - 10..].fill(0);
+        for tail in [&torn_record[..torn_record.len() / 2], &broken_record[..]] {
+            fs::OpenOptions::new()
+                .append(true)
+                .open(log_file_path)?
+                .write_all(tail)?;
+
+            let mut log_manager = LogManager::new(log_file_path)?;
+            assert_eq!(fs::metadata(log_file_path)?.len(), file_size);
+            assert_eq!(log_manager.next_lsn, LSN(4));
+            log_manager.append(TransactionID(4), LogRecordBody::BeginTransaction)?;
+            log_manager.flush()?;
+            let records = LogManager::new(log_file_path)?.read()?;
+            assert_eq!(
+                records.iter().map(|r| r.lsn).collect::<Vec<_>>(),
+                vec![LSN(1), LSN(2), LSN(3), LSN(4)]
+            );
+            let file = fs::OpenOptions::new().write(true).open(log_file_path)?;
+            file.set_len(file_size)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_corruption() -> Result<()> {
+        let dir = tempdir()?;
+        let log_file_path = dir.path().join("log");
+        let log_file_path = log_file_path.to_str().unwrap();
+        let mut log_manager = LogManager::new(log_file_path)?;
+        for i in 1..=3 {
+            log_manager.append(TransactionID(i), LogRecordBody::BeginTransaction)?;
+        }
+        log_manager.flush()?;
+        let original = fs::read(log_file_path)?;
+
+        // in the middle of the log, the records after it are not lost silently
+        let mut bytes = original.clone();
+        bytes[LOG_RECORD_HEADER_SIZE + 4 + 10] ^= 0xff;
+        fs::write(log_file_path, &bytes)?;
+        let e = LogManager::new(log_file_path).err().unwrap();
+        assert!(e.to_string().contains("is corrupted (checksum mismatch)"));
+
+        // intact, but of an unknown type
+        let mut bytes = original.clone();
+        let mut record = LogRecord {
+            lsn: LSN(4),
+            txn_id: TransactionID(4),
+            prev_lsn: INVALID_LSN,
+            body: LogRecordBody::BeginTransaction,
+        }
+        .serialize();
+        record[LOG_RECORD_HEADER_SIZE..].copy_from_slice(&99u32.to_be_bytes());
+        let checksum = crc32fast::hash(&record[8..]);
+        record[4..8].copy_from_slice(&checksum.to_be_bytes());
+        bytes.extend_from_slice(&record);
+        fs::write(log_file_path, &bytes)?;
+        let e = LogManager::new(log_file_path).err().unwrap();
+        assert!(e.to_string().contains("unknown log record type 99"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_b_plus_tree_log_records() -> Result<()> {
+        let dir = tempdir()?;
+        let log_file_path = dir.path().join("log");
+        let mut log_manager = LogManager::new(log_file_path.to_str().unwrap())?;
+
+        let bodies = vec![
+            LogRecordBody::InsertToBPlusTreeLeafPage(BPlusTreePageEntry {
+                page_id: PageID(3),
+                index: 2,
+                entry: vec![1, 2, 3].into(),
+            }),
+            LogRecordBody::DeleteFromBPlusTreeLeafPage(BPlusTreePageEntry {
+                page_id: PageID(3),
+                index: 0,
+                entry: vec![4, 5].into(),
+            }),
+            LogRecordBody::InsertToBPlusTreeInternalPage(BPlusTreePageEntry {
+                page_id: PageID(4),
+                index: 1,
+                entry: vec![6].into(),
+            }),
+            // page images are larger than the log buffer
+            LogRecordBody::SplitBPlusTreePage(SplitBPlusTreePage {
+                page_id: PageID(3),
+                data: vec![7; PAGE_SIZE].into(),
+                new_page_id: PageID(5),
+                new_data: vec![8; PAGE_SIZE].into(),
+            }),
+            LogRecordBody::NewBPlusTreeRootPage(NewBPlusTreeRootPage {
+                page_id: PageID(3),
+                data: vec![9; PAGE_SIZE].into(),
+                left_page_id: PageID(6),
+                left_data: vec![10; PAGE_SIZE].into(),
+            }),
+            LogRecordBody::SetBPlusTreeParentPageID(SetBPlusTreeParentPageID {
+                page_id: PageID(5),
+                parent_page_id: PageID(3),
+            }),
+            LogRecordBody::SetBPlusTreePrevPageID(SetBPlusTreePrevPageID {
+                page_id: PageID(5),
+                prev_page_id: PageID(6),
+            }),
+            LogRecordBody::LoadBPlusTreePage(LoadBPlusTreePage {
+                page_id: PageID(7),
+                data: vec![11; PAGE_SIZE].into(),
+            }),
+            LogRecordBody::UndoDeleteFromTablePage(UndoDeleteFromTablePage {
+                rid: RID(PageID(1), 2),
+                xmax: TransactionID(3),
+            }),
+            LogRecordBody::PruneTablePage(PruneTablePage {
+                page_id: PageID(1),
+                indexes: vec![0, 3, 4],
+            }),
+            LogRecordBody::Checkpoint(Checkpoint {
+                active_transactions: vec![(TransactionID(2), LSN(4)), (TransactionID(5), LSN(9))],
+                dirty_pages: vec![(PageID(3), LSN(6))],
+                next_txn_id: TransactionID(8),
+                aborted_transactions: vec![TransactionID(3), TransactionID(6)],
+                free_page_ids: vec![PageID(7), PageID(2)],
+            }),
+            LogRecordBody::FreePage(FreePage { page_id: PageID(9) }),
+            LogRecordBody::ReuseFreePage(ReuseFreePage { page_id: PageID(2) }),
+            LogRecordBody::Compensation(Compensation {
+                undo_next_lsn: LSN(3),
+                body: Box::new(LogRecordBody::DeleteFromBPlusTreeLeafPage(
+                    BPlusTreePageEntry {
+                        page_id: PageID(3),
+                        index: 2,
+                        entry: vec![1, 2, 3].into(),
+                    },
+                )),
+            }),
+        ];
+        for body in bodies.iter() {
+            log_manager.append(TransactionID(1), body.clone())?;
+        }
+        log_manager.flush()?;
+
+        let mut log_manager = LogManager::new(log_file_path.to_str().unwrap())?;
+        let records = log_manager.read()?;
+        assert_eq!(
+            records.into_iter().map(|r| r.body).collect::<Vec<_>>(),
+            bodies
+        );
+        Ok(())
+    }
 }
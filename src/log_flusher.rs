@@ -0,0 +1,209 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+
+use crate::{
+    common::{INVALID_LSN, LSN},
+    log::LogManager,
+};
+
+// group commit: committers wait until the flusher thread has synced the log past their
+// commit records, so that the commits which arrive during one sync share the next one.
+// the thread stops when this is dropped
+pub struct LogFlusher {
+    shared: Arc<LogFlusherShared>,
+}
+
+struct LogFlusherShared {
+    log_manager: Arc<Mutex<LogManager>>,
+    state: Mutex<LogFlusherState>,
+    // wakes the flusher thread
+    requested: Condvar,
+    // wakes the committers after a sync
+    flushed: Condvar,
+}
+
+struct LogFlusherState {
+    // a committer is waiting for the log up to here
+    requested_lsn: LSN,
+    flushed_lsn: LSN,
+    // asynchronous commits are synced by then at the latest
+    deadline: Option<Instant>,
+    // a failed sync can not be retried, the written records may or may not be on disk
+    error: Option<String>,
+    stopped: bool,
+}
+
+impl LogFlusher {
+    pub fn start(log_manager: Arc<Mutex<LogManager>>) -> Self {
+        let shared = Arc::new(LogFlusherShared {
+            log_manager,
+            state: Mutex::new(LogFlusherState {
+                requested_lsn: INVALID_LSN,
+                flushed_lsn: INVALID_LSN,
+                deadline: None,
+                error: None,
+                stopped: false,
+            }),
+            requested: Condvar::new(),
+            flushed: Condvar::new(),
+        });
+        let shared_clone = shared.clone();
+        thread::spawn(move || {
+            if let Err(e) = shared_clone.run() {
+                println!("log flusher error: {}", e);
+            }
+        });
+        Self { shared }
+    }
+
+    // returns when the log is on disk up to the lsn
+    pub fn wait_for(&self, lsn: LSN) -> Result<()> {
+        let mut state = self.shared.lock_state()?;
+        if lsn > state.requested_lsn {
+            state.requested_lsn = lsn;
+            self.shared.requested.notify_one();
+        }
+        while state.flushed_lsn < lsn {
+            if let Some(e) = &state.error {
+                return Err(anyhow::anyhow!("log flush failed: {}", e));
+            }
+            if state.stopped {
+                return Err(anyhow::anyhow!("log flusher stopped"));
+            }
+            state = self
+                .shared
+                .flushed
+                .wait(state)
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+        }
+        Ok(())
+    }
+    // returns at once, the log is synced within the window
+    pub fn request(&self, durability_window: Duration) -> Result<()> {
+        let mut state = self.shared.lock_state()?;
+        let deadline = Instant::now() + durability_window;
+        if state.deadline.is_none_or(|d| deadline < d) {
+            state.deadline = Some(deadline);
+            self.shared.requested.notify_one();
+        }
+        Ok(())
+    }
+    pub fn stop(&self) -> Result<()> {
+        let mut state = self.shared.lock_state()?;
+        state.stopped = true;
+        self.shared.requested.notify_one();
+        self.shared.flushed.notify_all();
+        Ok(())
+    }
+}
+impl Drop for LogFlusher {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl LogFlusherShared {
+    fn run(&self) -> Result<()> {
+        let mut state = self.lock_state()?;
+        loop {
+            if state.stopped || state.error.is_some() {
+                return Ok(());
+            }
+            let now = Instant::now();
+            let due = state.requested_lsn > state.flushed_lsn
+                || state.deadline.is_some_and(|deadline| deadline <= now);
+            if !due {
+                state = match state.deadline {
+                    Some(deadline) => {
+                        self.requested
+                            .wait_timeout(state, deadline - now)
+                            .map_err(|_| anyhow::anyhow!("lock error"))?
+                            .0
+                    }
+                    None => self
+                        .requested
+                        .wait(state)
+                        .map_err(|_| anyhow::anyhow!("lock error"))?,
+                };
+                continue;
+            }
+            state.deadline = None;
+            drop(state);
+            let result = self.flush();
+            state = self.lock_state()?;
+            match result {
+                Ok(lsn) => state.flushed_lsn = state.flushed_lsn.max(lsn),
+                Err(e) => state.error = Some(e.to_string()),
+            }
+            self.flushed.notify_all();
+        }
+    }
+    // the log is released during the sync, the commits appended meanwhile make up the next batch
+    fn flush(&self) -> Result<LSN> {
+        let (log_file, lsn) = self
+            .log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .write()?;
+        log_file.sync_data()?;
+        Ok(lsn)
+    }
+    fn lock_state(&self) -> Result<MutexGuard<'_, LogFlusherState>> {
+        self.state.lock().map_err(|_| anyhow::anyhow!("lock error"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use crate::{common::TransactionID, log::LogRecordBody};
+
+    use super::*;
+
+    #[test]
+    fn test_log_flusher() -> Result<()> {
+        let dir = tempdir()?;
+        let log_file_path = dir.path().join("wal.log");
+        let log_file_path = log_file_path.to_str().unwrap().to_string();
+        let log_manager = Arc::new(Mutex::new(LogManager::new(&log_file_path)?));
+        let log_flusher = Arc::new(LogFlusher::start(log_manager.clone()));
+
+        let handles = (1..=8)
+            .map(|i| {
+                let log_manager = log_manager.clone();
+                let log_flusher = log_flusher.clone();
+                thread::spawn(move || -> Result<()> {
+                    let lsn = log_manager
+                        .lock()
+                        .map_err(|_| anyhow::anyhow!("lock error"))?
+                        .append(TransactionID(i), LogRecordBody::CommitTransaction)?;
+                    log_flusher.wait_for(lsn)
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap()?;
+        }
+        // the records are read from the file, not from the buffer
+        assert_eq!(LogManager::new(&log_file_path)?.read()?.len(), 8);
+
+        // asynchronous
+        log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .append(TransactionID(9), LogRecordBody::CommitTransaction)?;
+        log_flusher.request(Duration::from_millis(10))?;
+        thread::sleep(Duration::from_millis(200));
+        assert_eq!(LogManager::new(&log_file_path)?.read()?.len(), 9);
+
+        log_flusher.stop()?;
+        assert!(log_flusher.wait_for(LSN(100)).is_err());
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
-use std::process;
+use std::{process, time::Duration};
 
 use anyhow::Result;
-use junkdb::{client::client_start, server::server_start};
+use junkdb::{autovacuum::AutovacuumConfig, client::client_start, server::server_start};
 
 fn main() -> Result<()> {
     let args: Vec<String> = std::env::args().collect();
@@ -14,7 +14,20 @@ fn main() -> Result<()> {
         "server" => {
             let init = args.iter().any(|arg| arg == "--init");
             let recover = args.iter().any(|arg| arg == "--recover");
-            server_start(init, recover)?;
+            let mut autovacuum_config = AutovacuumConfig::default();
+            let mut checkpoint_interval = Duration::from_secs(60);
+            for arg in args.iter() {
+                if let Some(threshold) = arg.strip_prefix("--autovacuum-threshold=") {
+                    autovacuum_config.threshold = threshold.parse()?;
+                }
+                if let Some(naptime) = arg.strip_prefix("--autovacuum-naptime=") {
+                    autovacuum_config.naptime = Duration::from_secs(naptime.parse()?);
+                }
+                if let Some(interval) = arg.strip_prefix("--checkpoint-interval=") {
+                    checkpoint_interval = Duration::from_secs(interval.parse()?);
+                }
+            }
+            server_start(init, recover, autovacuum_config, checkpoint_interval)?;
         }
         _ => {
             println!("Usage: cargo run client|server");
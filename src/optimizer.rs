@@ -1,12 +1,18 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    ops::Bound,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Result;
 
 use crate::{
-    binder::BoundExpressionAST,
-    catalog::Catalog,
+    binder::{BoundBinaryExpressionAST, BoundExpressionAST},
+    catalog::{Catalog, DataType},
     common::TransactionID,
-    plan::{IndexScanPlan, Plan},
+    index::IndexRange,
+    parser::BinaryOperator,
+    plan::{FilterPlan, IndexOnlyScanPlan, IndexScanPlan, Plan, SeqScanPlan},
+    value::Value,
 };
 
 pub struct Optimizer {
@@ -20,8 +26,91 @@ impl Optimizer {
     pub fn optimize(&self, plan: Plan) -> Result<Plan> {
         let mut optimized_plan = plan;
         optimized_plan = self.optimize_filter_index_scan(optimized_plan)?;
+        optimized_plan = self.optimize_index_only_scan(optimized_plan)?;
+        Ok(optimized_plan)
+    }
+    // the index scan under a projection or an aggregation is replaced when they and the filters
+    // between them only refer to the columns of the index key
+    fn optimize_index_only_scan(&self, plan: Plan) -> Result<Plan> {
+        let mut column_indexes = vec![];
+        let scan_child = match &plan {
+            Plan::Project(project_plan) => {
+                for select_element in project_plan.select_elements.iter() {
+                    collect_column_indexes(&select_element.expression, &mut column_indexes);
+                }
+                Some(&*project_plan.child)
+            }
+            Plan::Aggregate(aggregate_plan) => {
+                for expression in aggregate_plan.group_by.iter() {
+                    collect_column_indexes(expression, &mut column_indexes);
+                }
+                for function_call in aggregate_plan.aggregate_functions.iter() {
+                    for argument in function_call.arguments.iter() {
+                        collect_column_indexes(argument, &mut column_indexes);
+                    }
+                }
+                Some(&*aggregate_plan.child)
+            }
+            _ => None,
+        };
+        if let Some(scan_child) = scan_child {
+            if let Some(child) = self.plan_index_only_scan(scan_child, &mut column_indexes)? {
+                let mut optimized_plan = plan.clone();
+                optimized_plan.set_children(vec![child]);
+                return Ok(optimized_plan);
+            }
+        }
+        let mut children = Vec::new();
+        for child in plan.children() {
+            children.push(self.optimize_index_only_scan(*child)?);
+        }
+        let mut optimized_plan = plan;
+        optimized_plan.set_children(children);
         Ok(optimized_plan)
     }
+    fn plan_index_only_scan(
+        &self,
+        plan: &Plan,
+        column_indexes: &mut Vec<usize>,
+    ) -> Result<Option<Plan>> {
+        match plan {
+            Plan::Filter(filter_plan) => {
+                collect_column_indexes(&filter_plan.condition, column_indexes);
+                Ok(self
+                    .plan_index_only_scan(&filter_plan.child, column_indexes)?
+                    .map(|child| {
+                        Plan::Filter(FilterPlan {
+                            condition: filter_plan.condition.clone(),
+                            schema: filter_plan.schema.clone(),
+                            child: Box::new(child),
+                        })
+                    }))
+            }
+            Plan::IndexScan(index_scan_plan) => {
+                let index = self
+                    .catalog
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
+                    .get_index(index_scan_plan.index_id, self.txn_id)?;
+                let covered = column_indexes.iter().all(|&i| {
+                    index
+                        .columns
+                        .contains(&index_scan_plan.schema.columns[i].name)
+                });
+                if !covered {
+                    return Ok(None);
+                }
+                Ok(Some(Plan::IndexOnlyScan(IndexOnlyScanPlan {
+                    index_id: index_scan_plan.index_id,
+                    first_page_id: index_scan_plan.first_page_id,
+                    schema: index_scan_plan.schema.clone(),
+                    ranges: index_scan_plan.ranges.clone(),
+                })))
+            }
+            _ => Ok(None),
+        }
+    }
+    // replaces the scan under the filter, the filter is kept to check the whole condition
     fn optimize_filter_index_scan(&self, plan: Plan) -> Result<Plan> {
         let mut children = Vec::new();
         for child in plan.children() {
@@ -30,40 +119,229 @@ impl Optimizer {
         }
         let mut source_plan = plan.clone();
         source_plan.set_children(children);
-        if let Plan::Filter(filter_plan) = plan {
-            if let BoundExpressionAST::Binary(binary_expression) = filter_plan.condition {
-                let binary_expression_clone = binary_expression.clone();
-                if let BoundExpressionAST::Path(path_expression) = *binary_expression.left {
-                    let indexes = self
-                        .catalog
-                        .lock()
-                        .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
-                        .get_indexes_by_table_name(&path_expression.table_name, self.txn_id)?;
-                    for index in indexes {
-                        if index.columns.len() == 1
-                            && index.columns[0] == path_expression.column_name
-                        {
-                            if let BoundExpressionAST::Literal(_) = *binary_expression.right {
-                                let table_schema = self
-                                    .catalog
-                                    .lock()
-                                    .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
-                                    .get_schema_by_table_name(
-                                        &path_expression.table_name,
-                                        self.txn_id,
-                                    )?;
-                                return Ok(Plan::IndexScan(IndexScanPlan {
-                                    index_id: index.id,
-                                    schema: filter_plan.schema,
-                                    binary_expression: binary_expression_clone,
-                                    table_schema,
-                                }));
-                            }
-                        }
-                    }
+        if let Plan::Filter(filter_plan) = &source_plan {
+            if let Plan::SeqScan(seq_scan_plan) = &*filter_plan.child {
+                if let Some(index_scan_plan) =
+                    self.plan_index_scan(&filter_plan.condition, seq_scan_plan)?
+                {
+                    return Ok(Plan::Filter(FilterPlan {
+                        condition: filter_plan.condition.clone(),
+                        schema: filter_plan.schema.clone(),
+                        child: Box::new(Plan::IndexScan(index_scan_plan)),
+                    }));
                 }
             }
         }
         Ok(source_plan)
     }
+    // uses equalities on the leading columns of the index and a range on the column after them
+    fn plan_index_scan(
+        &self,
+        condition: &BoundExpressionAST,
+        seq_scan_plan: &SeqScanPlan,
+    ) -> Result<Option<IndexScanPlan>> {
+        let indexes = self
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Catalog lock error"))?
+            .get_indexes_by_table_name(&seq_scan_plan.table_name, self.txn_id)?;
+        let conditions = split_conjunction(condition);
+        let mut best_plan: Option<(usize, IndexScanPlan)> = None;
+        for index in indexes {
+            let mut prefix = vec![];
+            let mut ranges = None;
+            let mut matched_columns = 0;
+            for column_name in index.columns.iter() {
+                let data_type = match seq_scan_plan.schema.column_index(column_name) {
+                    Some(i) => &seq_scan_plan.schema.columns[i].data_type,
+                    None => break,
+                };
+                let mut column_ranges = None;
+                for condition in conditions.iter() {
+                    let condition_ranges = match column_ranges_of_condition(
+                        condition,
+                        &seq_scan_plan.table_name,
+                        column_name,
+                        data_type,
+                    ) {
+                        Some(condition_ranges) => condition_ranges,
+                        None => continue,
+                    };
+                    column_ranges = Some(intersect_ranges(
+                        &column_ranges.unwrap_or_else(|| vec![IndexRange::full()]),
+                        &condition_ranges,
+                    )?);
+                }
+                let column_ranges = match column_ranges {
+                    Some(column_ranges) => column_ranges,
+                    None => break,
+                };
+                matched_columns += 1;
+                match point_of_ranges(&column_ranges)? {
+                    Some(value) => prefix.push(value),
+                    None => {
+                        ranges = Some(
+                            column_ranges
+                                .iter()
+                                .map(|range| prepend_prefix(&prefix, range))
+                                .collect::<Vec<_>>(),
+                        );
+                        break;
+                    }
+                }
+            }
+            if matched_columns == 0 {
+                continue;
+            }
+            if best_plan
+                .as_ref()
+                .is_some_and(|(best_matched_columns, _)| *best_matched_columns >= matched_columns)
+            {
+                continue;
+            }
+            let ranges = ranges.unwrap_or_else(|| {
+                vec![IndexRange {
+                    start: Bound::Included(prefix.clone()),
+                    end: Bound::Included(prefix),
+                }]
+            });
+            best_plan = Some((
+                matched_columns,
+                IndexScanPlan {
+                    index_id: index.id,
+                    first_page_id: seq_scan_plan.first_page_id,
+                    schema: seq_scan_plan.schema.clone(),
+                    ranges,
+                },
+            ));
+        }
+        Ok(best_plan.map(|(_, plan)| plan))
+    }
+}
+
+fn intersect_ranges(a: &[IndexRange], b: &[IndexRange]) -> Result<Vec<IndexRange>> {
+    let mut ranges = vec![];
+    for a_range in a.iter() {
+        for b_range in b.iter() {
+            if let Some(range) = a_range.intersect(b_range)? {
+                ranges.push(range);
+            }
+        }
+    }
+    Ok(ranges)
+}
+
+// the value if the ranges only contain a single value
+fn point_of_ranges(ranges: &[IndexRange]) -> Result<Option<Value>> {
+    if let [IndexRange {
+        start: Bound::Included(start),
+        end: Bound::Included(end),
+    }] = ranges
+    {
+        if Value::compare_values(start, end)?.is_eq() {
+            return Ok(Some(start[0].clone()));
+        }
+    }
+    Ok(None)
+}
+
+fn prepend_prefix(prefix: &[Value], range: &IndexRange) -> IndexRange {
+    // an unbounded side is limited to the keys which start with the prefix
+    let prepend = |bound: &Bound<Vec<Value>>| match bound {
+        Bound::Included(key) => Bound::Included([prefix, key].concat()),
+        Bound::Excluded(key) => Bound::Excluded([prefix, key].concat()),
+        Bound::Unbounded if prefix.is_empty() => Bound::Unbounded,
+        Bound::Unbounded => Bound::Included(prefix.to_vec()),
+    };
+    IndexRange {
+        start: prepend(&range.start),
+        end: prepend(&range.end),
+    }
+}
+
+fn collect_column_indexes(expression: &BoundExpressionAST, column_indexes: &mut Vec<usize>) {
+    match expression {
+        BoundExpressionAST::Path(path_expression) => {
+            column_indexes.push(path_expression.column_index)
+        }
+        BoundExpressionAST::Literal(_) => {}
+        BoundExpressionAST::Unary(unary_expression) => {
+            collect_column_indexes(&unary_expression.operand, column_indexes)
+        }
+        BoundExpressionAST::Binary(binary_expression) => {
+            collect_column_indexes(&binary_expression.left, column_indexes);
+            collect_column_indexes(&binary_expression.right, column_indexes);
+        }
+        BoundExpressionAST::FunctionCall(function_call_expression) => {
+            for argument in function_call_expression.arguments.iter() {
+                collect_column_indexes(argument, column_indexes);
+            }
+        }
+    }
+}
+
+fn split_conjunction(condition: &BoundExpressionAST) -> Vec<&BoundExpressionAST> {
+    match condition {
+        BoundExpressionAST::Binary(BoundBinaryExpressionAST {
+            operator: BinaryOperator::And,
+            left,
+            right,
+        }) => {
+            let mut conditions = split_conjunction(left);
+            conditions.extend(split_conjunction(right));
+            conditions
+        }
+        _ => vec![condition],
+    }
+}
+
+// ranges of the column satisfying `column <op> literal`, None if the condition is not of that form
+fn column_ranges_of_condition(
+    condition: &BoundExpressionAST,
+    table_name: &str,
+    column_name: &str,
+    data_type: &DataType,
+) -> Option<Vec<IndexRange>> {
+    let binary_expression = match condition {
+        BoundExpressionAST::Binary(binary_expression) => binary_expression,
+        _ => return None,
+    };
+    let (path_expression, literal_expression, operator) =
+        match (&*binary_expression.left, &*binary_expression.right) {
+            (BoundExpressionAST::Path(path), BoundExpressionAST::Literal(literal)) => {
+                (path, literal, binary_expression.operator.clone())
+            }
+            (BoundExpressionAST::Literal(literal), BoundExpressionAST::Path(path)) => {
+                let operator = match binary_expression.operator {
+                    BinaryOperator::LessThan => BinaryOperator::GreaterThan,
+                    BinaryOperator::LessThanOrEqual => BinaryOperator::GreaterThanOrEqual,
+                    BinaryOperator::GreaterThan => BinaryOperator::LessThan,
+                    BinaryOperator::GreaterThanOrEqual => BinaryOperator::LessThanOrEqual,
+                    ref operator => operator.clone(),
+                };
+                (path, literal, operator)
+            }
+            _ => return None,
+        };
+    if path_expression.table_name != table_name || path_expression.column_name != column_name {
+        return None;
+    }
+    if literal_expression.value.is_null_value() {
+        return None;
+    }
+    let key = vec![literal_expression.value.convert_to(data_type).ok()?];
+    let range = |start, end| IndexRange { start, end };
+    let ranges = match operator {
+        BinaryOperator::Equal => vec![range(Bound::Included(key.clone()), Bound::Included(key))],
+        BinaryOperator::NotEqual => vec![
+            range(Bound::Unbounded, Bound::Excluded(key.clone())),
+            range(Bound::Excluded(key), Bound::Unbounded),
+        ],
+        BinaryOperator::LessThan => vec![range(Bound::Unbounded, Bound::Excluded(key))],
+        BinaryOperator::LessThanOrEqual => vec![range(Bound::Unbounded, Bound::Included(key))],
+        BinaryOperator::GreaterThan => vec![range(Bound::Excluded(key), Bound::Unbounded)],
+        BinaryOperator::GreaterThanOrEqual => vec![range(Bound::Included(key), Bound::Unbounded)],
+        _ => return None,
+    };
+    Some(ranges)
 }
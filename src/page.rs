@@ -1,6 +1,7 @@
-use crate::common::{PageID, INVALID_PAGE_ID};
+use crate::common::{PageID, INVALID_PAGE_ID, LSN};
 
 use self::{
+    b_plus_tree_internal_page::{BPlusTreeInternalPage, B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE},
     b_plus_tree_leaf_page::{BPlusTreeLeafPage, B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE},
     table_page::{TablePage, TABLE_PAGE_PAGE_TYPE},
 };
@@ -21,6 +22,7 @@ pub struct PageType(pub u32);
 pub enum Page {
     Table(TablePage),
     BPlusTreeLeaf(BPlusTreeLeafPage),
+    BPlusTreeInternal(BPlusTreeInternalPage),
 }
 impl Page {
     pub fn with_table_page<F, R>(&self, f: F) -> R
@@ -59,6 +61,24 @@ impl Page {
             _ => panic!("page type not supported"),
         }
     }
+    pub fn with_b_plus_tree_internal_page<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&BPlusTreeInternalPage) -> R,
+    {
+        match self {
+            Page::BPlusTreeInternal(b_plus_tree_internal_page) => f(b_plus_tree_internal_page),
+            _ => panic!("page type not supported"),
+        }
+    }
+    pub fn with_b_plus_tree_internal_page_mut<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&mut BPlusTreeInternalPage) -> R,
+    {
+        match self {
+            Page::BPlusTreeInternal(b_plus_tree_internal_page) => f(b_plus_tree_internal_page),
+            _ => panic!("page type not supported"),
+        }
+    }
     pub fn new(page_id: PageID, page_type: PageType) -> Self {
         match page_type {
             TABLE_PAGE_PAGE_TYPE => Page::Table(TablePage::new(page_id)),
@@ -66,6 +86,9 @@ impl Page {
             B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE => {
                 Page::BPlusTreeLeaf(BPlusTreeLeafPage::new(page_id, INVALID_PAGE_ID, None))
             }
+            B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE => {
+                Page::BPlusTreeInternal(BPlusTreeInternalPage::new_empty(page_id, INVALID_PAGE_ID))
+            }
             _ => panic!("page type not supported"),
         }
     }
@@ -75,6 +98,7 @@ impl Page {
         let page_type = match u32::from_le_bytes(bytes) {
             1 => TABLE_PAGE_PAGE_TYPE,
             2 => B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE,
+            3 => B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE,
             _ => panic!("page type not supported"),
         };
         match page_type {
@@ -82,6 +106,9 @@ impl Page {
             B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE => {
                 Page::BPlusTreeLeaf(BPlusTreeLeafPage::from_data(data))
             }
+            B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE => {
+                Page::BPlusTreeInternal(BPlusTreeInternalPage::from_data(data))
+            }
             _ => panic!("page type not supported"),
         }
     }
@@ -89,15 +116,48 @@ impl Page {
         match self {
             Page::Table(table_page) => &table_page.data,
             Page::BPlusTreeLeaf(b_plus_tree_leaf_page) => &b_plus_tree_leaf_page.data,
+            Page::BPlusTreeInternal(b_plus_tree_internal_page) => &b_plus_tree_internal_page.data,
         }
     }
     pub fn page_id(&self) -> PageID {
         match self {
             Page::Table(table_page) => table_page.page_id(),
             Page::BPlusTreeLeaf(b_plus_tree_leaf_page) => b_plus_tree_leaf_page.page_id(),
+            Page::BPlusTreeInternal(b_plus_tree_internal_page) => {
+                b_plus_tree_internal_page.page_id()
+            }
+        }
+    }
+    pub fn lsn(&self) -> LSN {
+        match self {
+            Page::Table(table_page) => table_page.lsn(),
+            Page::BPlusTreeLeaf(b_plus_tree_leaf_page) => b_plus_tree_leaf_page.lsn(),
+            Page::BPlusTreeInternal(b_plus_tree_internal_page) => b_plus_tree_internal_page.lsn(),
+        }
+    }
+    pub fn set_lsn(&mut self, lsn: LSN) {
+        match self {
+            Page::Table(table_page) => table_page.set_lsn(lsn),
+            Page::BPlusTreeLeaf(b_plus_tree_leaf_page) => b_plus_tree_leaf_page.set_lsn(lsn),
+            Page::BPlusTreeInternal(b_plus_tree_internal_page) => {
+                b_plus_tree_internal_page.set_lsn(lsn)
+            }
         }
     }
     pub fn is_b_plus_tree_leaf(&self) -> bool {
         matches!(self, Page::BPlusTreeLeaf(_))
     }
 }
+
+// index to split entries at so that both halves have about the same size in bytes
+fn split_index(entries: &[Vec<u8>]) -> usize {
+    let total = entries.iter().map(|entry| entry.len()).sum::<usize>();
+    let mut size = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        size += entry.len();
+        if size * 2 >= total {
+            return (i + 1).clamp(1, entries.len().max(2) - 1);
+        }
+    }
+    entries.len() / 2
+}
@@ -5,7 +5,9 @@ use crate::{
     value::Value,
 };
 
-use super::{PageType, PAGE_ID_OFFSET, PAGE_ID_SIZE, PAGE_TYPE_OFFSET, PAGE_TYPE_SIZE};
+use super::{
+    split_index, PageType, PAGE_ID_OFFSET, PAGE_ID_SIZE, PAGE_TYPE_OFFSET, PAGE_TYPE_SIZE,
+};
 
 pub const B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE: PageType = PageType(3);
 
@@ -30,6 +32,7 @@ const LINE_POINTER_SIZE: usize = LINE_POINTER_OFFSET_SIZE + LINE_POINTER_SIZE_SI
 // page_id
 const VALUE_SIZE: usize = 4;
 
+#[derive(Debug)]
 pub struct BPlusTreeInternalPage {
     pub data: Box<[u8]>,
 }
@@ -76,10 +79,29 @@ impl BPlusTreeInternalPage {
 
         BPlusTreeInternalPage { data: data.into() }
     }
+    pub fn new_empty(page_id: PageID, parent_page_id: PageID) -> Self {
+        let mut data = vec![0u8; PAGE_SIZE];
+        data[PAGE_TYPE_OFFSET..(PAGE_TYPE_OFFSET + PAGE_TYPE_SIZE)]
+            .copy_from_slice(&B_PLUS_TREE_INTERNAL_PAGE_PAGE_TYPE.0.to_le_bytes());
+        data[PAGE_ID_OFFSET..(PAGE_ID_OFFSET + PAGE_ID_SIZE)]
+            .copy_from_slice(&page_id.0.to_le_bytes());
+        data[PARENT_PAGE_ID_OFFSET..(PARENT_PAGE_ID_OFFSET + PARENT_PAGE_ID_SIZE)]
+            .copy_from_slice(&parent_page_id.0.to_le_bytes());
+        data[LOWER_OFFSET_OFFSET..(LOWER_OFFSET_OFFSET + LOWER_OFFSET_SIZE)]
+            .copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        data[UPPER_OFFSET_OFFSET..(UPPER_OFFSET_OFFSET + UPPER_OFFSET_SIZE)]
+            .copy_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+        BPlusTreeInternalPage { data: data.into() }
+    }
     pub fn from_data(data: &[u8]) -> Self {
         BPlusTreeInternalPage { data: data.into() }
     }
 
+    pub fn page_id(&self) -> PageID {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&self.data[PAGE_ID_OFFSET..(PAGE_ID_OFFSET + PAGE_ID_SIZE)]);
+        PageID(u32::from_le_bytes(bytes))
+    }
     pub fn lsn(&self) -> LSN {
         let mut buf = [0u8; 8];
         buf.copy_from_slice(&self.data[LSN_OFFSET..(LSN_OFFSET + LSN_SIZE)]);
@@ -171,6 +193,33 @@ impl BPlusTreeInternalPage {
         }
         ok as usize
     }
+    // largest index whose key is less than the given key
+    pub fn key_index_lower(&self, key: &[Value], schema: &Schema) -> usize {
+        let mut ng = self.num_line_pointers() as i32;
+        let mut ok = 0;
+        while (ok - ng).abs() > 1 {
+            let mid = (ok + ng) / 2;
+            if let Some(mid_key) = self.key_at(mid as usize, schema) {
+                let order = Value::compare_values(&mid_key, key).unwrap();
+                if order.is_lt() {
+                    ok = mid;
+                } else {
+                    ng = mid;
+                }
+            } else {
+                ok = mid;
+            }
+        }
+        ok as usize
+    }
+    pub fn values(&self) -> Vec<PageID> {
+        (0..self.num_line_pointers())
+            .map(|i| self.value_at(i as usize))
+            .collect()
+    }
+    pub fn contains_value(&self, value: PageID) -> bool {
+        self.values().contains(&value)
+    }
     pub fn value_index(&self, value: PageID) -> usize {
         for i in 0..self.num_line_pointers() {
             if self.value_at(i as usize) == value {
@@ -200,13 +249,66 @@ impl BPlusTreeInternalPage {
         }
         self.value_at(0)
     }
-    pub fn insert_after(&mut self, prev_value: PageID, key: &[Value], value: PageID) {
+    // returns the index of the new entry
+    pub fn insert_after(&mut self, prev_value: PageID, key: &[Value], value: PageID) -> usize {
         let index = self.value_index(prev_value) + 1;
 
         let key_bytes = Tuple::temp_tuple(key).data;
         let value_bytes = Box::new(value.0.to_le_bytes());
         let new_entry = [value_bytes, key_bytes].concat();
 
+        self.insert_entry_at(index, &new_entry);
+        index
+    }
+    pub fn entry_at(&self, index: usize) -> Vec<u8> {
+        let offset = self.line_pointer_offset(index) as usize;
+        let size = self.line_pointer_size(index) as usize;
+        self.data[offset..(offset + size)].to_vec()
+    }
+    pub fn insert_entry_at(&mut self, index: usize, entry: &[u8]) {
+        let mut entries = self.entries();
+        entries.insert(index, entry.to_vec());
+        self.set_entries(&entries);
+    }
+    pub fn free_space(&self) -> usize {
+        (self.upper_offset() - self.lower_offset()) as usize
+    }
+    pub fn has_space_for(&self, key: &[Value]) -> bool {
+        Self::entry_size(key) <= self.free_space()
+    }
+    // size of line pointer + page id + key
+    pub fn entry_size(key: &[Value]) -> usize {
+        LINE_POINTER_SIZE + VALUE_SIZE + Tuple::temp_tuple(key).data.len()
+    }
+    pub fn capacity() -> usize {
+        PAGE_SIZE - HEADER_SIZE
+    }
+    // appends an entry, the key of the first entry is dropped
+    pub fn push(&mut self, key: &[Value], value: PageID) {
+        let mut entries = self.entries();
+        let mut new_entry = value.0.to_le_bytes().to_vec();
+        if !entries.is_empty() {
+            new_entry.extend_from_slice(&Tuple::temp_tuple(key).data);
+        }
+        entries.push(new_entry);
+        self.set_entries(&entries);
+    }
+    // moves the upper half of the entries to the empty right page and returns the key that rises to the parent
+    pub fn split(&mut self, right: &mut BPlusTreeInternalPage, schema: &Schema) -> Vec<Value> {
+        let mut entries = self.entries();
+        let mut right_entries = entries.split_off(split_index(&entries));
+        let risen_key = Tuple::new(None, &right_entries[0][VALUE_SIZE..]).values(schema);
+        right_entries[0].truncate(VALUE_SIZE);
+        self.set_entries(&entries);
+        right.set_entries(&right_entries);
+        risen_key
+    }
+    pub fn move_all_to(&mut self, dest: &mut BPlusTreeInternalPage) {
+        dest.set_entries(&self.entries());
+        self.set_entries(&[]);
+    }
+
+    fn entries(&self) -> Vec<Vec<u8>> {
         let mut entries = vec![];
         for i in 0..self.num_line_pointers() {
             let offset = self.line_pointer_offset(i as usize) as usize;
@@ -214,23 +316,21 @@ impl BPlusTreeInternalPage {
             let entry_bytes = self.data[offset..(offset + size)].to_vec();
             entries.push(entry_bytes);
         }
-        entries.insert(index, new_entry);
-
+        entries
+    }
+    fn set_entries(&mut self, entries: &[Vec<u8>]) {
         let mut current_offset = PAGE_SIZE;
         for (i, entry) in entries.iter().enumerate() {
             let size = entry.len() as u32;
-            let offset_byttes = ((current_offset as u32) - size).to_le_bytes();
+            let offset_bytes = ((current_offset as u32) - size).to_le_bytes();
             let size_bytes = size.to_le_bytes();
             self.data[(HEADER_SIZE + i * LINE_POINTER_SIZE)
                 ..(HEADER_SIZE + i * LINE_POINTER_SIZE + LINE_POINTER_OFFSET_SIZE)]
-                .copy_from_slice(&offset_byttes);
+                .copy_from_slice(&offset_bytes);
             self.data[(HEADER_SIZE + i * LINE_POINTER_SIZE + LINE_POINTER_OFFSET_SIZE)
-                ..(HEADER_SIZE
-                    + i * LINE_POINTER_SIZE
-                    + LINE_POINTER_OFFSET_SIZE
-                    + LINE_POINTER_SIZE_SIZE)]
+                ..(HEADER_SIZE + i * LINE_POINTER_SIZE + LINE_POINTER_SIZE)]
                 .copy_from_slice(&size_bytes);
-            self.data[(current_offset - size as usize)..current_offset].copy_from_slice(entry);
+            self.data[current_offset - size as usize..current_offset].copy_from_slice(entry);
             current_offset -= size as usize;
         }
         self.set_lower_offset((HEADER_SIZE + entries.len() * LINE_POINTER_SIZE) as u32);
@@ -263,7 +363,7 @@ mod tests {
                 },
             ],
         };
-        let values_list = vec![
+        let values_list = [
             (
                 vec![
                     Value::Integer(IntegerValue(1)),
@@ -363,4 +463,59 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_split() -> Result<()> {
+        let schema = Schema {
+            columns: vec![Column {
+                name: "id".to_string(),
+                data_type: DataType::Integer,
+            }],
+        };
+        let mut page = BPlusTreeInternalPage::new(
+            PageID(1),
+            INVALID_PAGE_ID,
+            PageID(10),
+            PageID(11),
+            Tuple::temp_tuple(&[Value::Integer(IntegerValue(1))]),
+        );
+        for i in 2..10 {
+            page.insert_after(
+                PageID(9 + i),
+                &[Value::Integer(IntegerValue(i as i64))],
+                PageID(10 + i),
+            );
+        }
+        let mut right = BPlusTreeInternalPage::new_empty(PageID(2), INVALID_PAGE_ID);
+        let risen_key = page.split(&mut right, &schema);
+
+        let left_values = page.values();
+        let right_values = right.values();
+        assert_eq!(
+            [left_values.clone(), right_values.clone()].concat(),
+            (10..20).map(PageID).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            risen_key,
+            vec![Value::Integer(IntegerValue(left_values.len() as i64))]
+        );
+        assert_eq!(right.key_at(0, &schema), None);
+        assert_eq!(
+            right.key_at(1, &schema),
+            Some(vec![Value::Integer(IntegerValue(
+                left_values.len() as i64 + 1
+            ))])
+        );
+        assert!(right.contains_value(PageID(19)));
+        assert!(!page.contains_value(PageID(19)));
+        assert_eq!(
+            page.key_index_lower(&[Value::Integer(IntegerValue(1))], &schema),
+            0
+        );
+        assert_eq!(
+            page.key_index_lower(&[Value::Integer(IntegerValue(2))], &schema),
+            1
+        );
+        Ok(())
+    }
 }
@@ -5,7 +5,9 @@ use crate::{
     value::Value,
 };
 
-use super::{PageType, PAGE_ID_OFFSET, PAGE_ID_SIZE, PAGE_TYPE_OFFSET, PAGE_TYPE_SIZE};
+use super::{
+    split_index, PageType, PAGE_ID_OFFSET, PAGE_ID_SIZE, PAGE_TYPE_OFFSET, PAGE_TYPE_SIZE,
+};
 
 pub const B_PLUS_TREE_LEAF_PAGE_PAGE_TYPE: PageType = PageType(2);
 
@@ -230,7 +232,8 @@ impl BPlusTreeLeafPage {
         }
         Some(rids)
     }
-    pub fn insert(&mut self, key: &[Value], value: RID, schema: &Schema) {
+    // returns the index of the new entry
+    pub fn insert(&mut self, key: &[Value], value: RID, schema: &Schema) -> usize {
         let index = self.key_index_upper(key, schema);
 
         let key_bytes = Tuple::temp_tuple(key).data;
@@ -239,6 +242,70 @@ impl BPlusTreeLeafPage {
             .into_boxed_slice();
         let new_entry = [value_bytes, key_bytes].concat();
 
+        self.insert_entry_at(index, &new_entry);
+        index
+    }
+    // index of the entry with the key and the value
+    pub fn find(&self, key: &[Value], value: RID, schema: &Schema) -> Option<usize> {
+        (self.key_index(key, schema)..self.num_line_pointers() as usize)
+            .take_while(|&i| {
+                Value::compare_values(&self.key_at(i, schema), key)
+                    .map(|order| order.is_eq())
+                    .unwrap_or(false)
+            })
+            .find(|&i| self.value_at(i) == value)
+    }
+    // entries as raw bytes, used by the WAL which has no schema to compare keys
+    pub fn entry_at(&self, index: usize) -> Vec<u8> {
+        let offset = self.line_pointer_offset(index) as usize;
+        let size = self.line_pointer_size(index) as usize;
+        self.data[offset..(offset + size)].to_vec()
+    }
+    pub fn entry_index(&self, entry: &[u8]) -> Option<usize> {
+        (0..self.num_line_pointers() as usize).find(|&i| self.entry_at(i) == entry)
+    }
+    pub fn insert_entry_at(&mut self, index: usize, entry: &[u8]) {
+        let mut entries = self.entries();
+        entries.insert(index, entry.to_vec());
+        self.set_entries(&entries);
+    }
+    pub fn remove_entry_at(&mut self, index: usize) -> Vec<u8> {
+        let mut entries = self.entries();
+        let entry = entries.remove(index);
+        self.set_entries(&entries);
+        entry
+    }
+    pub fn free_space(&self) -> usize {
+        (self.upper_offset() - self.lower_offset()) as usize
+    }
+    pub fn has_space_for(&self, key: &[Value]) -> bool {
+        Self::entry_size(key) <= self.free_space()
+    }
+    // size of line pointer + RID + key
+    pub fn entry_size(key: &[Value]) -> usize {
+        LINE_POINTER_SIZE + VALUE_SIZE + Tuple::temp_tuple(key).data.len()
+    }
+    pub fn capacity() -> usize {
+        PAGE_SIZE - HEADER_SIZE
+    }
+    // a quarter of the page, so that either half of a split page can always take one more entry
+    pub fn max_entry_size() -> usize {
+        (PAGE_SIZE - HEADER_SIZE) / 4
+    }
+    // moves the upper half (by bytes) of the entries to the empty right page and returns its first key
+    pub fn split(&mut self, right: &mut BPlusTreeLeafPage, schema: &Schema) -> Vec<Value> {
+        let mut entries = self.entries();
+        let right_entries = entries.split_off(split_index(&entries));
+        self.set_entries(&entries);
+        right.set_entries(&right_entries);
+        right.key_at(0, schema)
+    }
+    pub fn move_all_to(&mut self, dest: &mut BPlusTreeLeafPage) {
+        dest.set_entries(&self.entries());
+        self.set_entries(&[]);
+    }
+
+    fn entries(&self) -> Vec<Vec<u8>> {
         let mut entries = vec![];
         for i in 0..self.num_line_pointers() {
             let offset = self.line_pointer_offset(i as usize) as usize;
@@ -246,8 +313,9 @@ impl BPlusTreeLeafPage {
             let entry_bytes = self.data[offset..(offset + size)].to_vec();
             entries.push(entry_bytes);
         }
-        entries.insert(index, new_entry);
-
+        entries
+    }
+    fn set_entries(&mut self, entries: &[Vec<u8>]) {
         let mut current_offset = PAGE_SIZE;
         for (i, entry) in entries.iter().enumerate() {
             let size = entry.len() as u32;
@@ -292,7 +360,7 @@ mod tests {
                 },
             ],
         };
-        let values_list = vec![
+        let values_list = [
             (
                 vec![
                     Value::Integer(IntegerValue(1)),
@@ -17,16 +17,22 @@ const LOWER_OFFSET_OFFSET: usize = NEXT_PAGE_ID_OFFSET + NEXT_PAGE_ID_SIZE;
 const LOWER_OFFSET_SIZE: usize = 4;
 const UPPER_OFFSET_OFFSET: usize = LOWER_OFFSET_OFFSET + LOWER_OFFSET_SIZE;
 const UPPER_OFFSET_SIZE: usize = 4;
+const FLAGS_OFFSET: usize = UPPER_OFFSET_OFFSET + UPPER_OFFSET_SIZE;
+const FLAGS_SIZE: usize = 4;
 const HEADER_SIZE: usize = PAGE_TYPE_SIZE
     + PAGE_ID_SIZE
     + LSN_SIZE
     + NEXT_PAGE_ID_SIZE
     + LOWER_OFFSET_SIZE
-    + UPPER_OFFSET_SIZE;
+    + UPPER_OFFSET_SIZE
+    + FLAGS_SIZE;
 const LINE_POINTER_OFFSET_SIZE: usize = 4;
 const LINE_POINTER_SIZE_SIZE: usize = 4;
 const LINE_POINTER_SIZE: usize = LINE_POINTER_OFFSET_SIZE + LINE_POINTER_SIZE_SIZE;
 
+// every tuple on the page is visible to all transactions, so index-only scans can skip the tuples
+const ALL_VISIBLE_FLAG: u32 = 1;
+
 #[derive(Debug)]
 pub struct TablePage {
     pub data: Box<[u8]>,
@@ -71,6 +77,7 @@ impl TablePage {
             ..((lower_offset as usize) + LINE_POINTER_SIZE)]
             .copy_from_slice(&data_size.to_le_bytes());
         self.data[(next_upper_offset as usize)..(upper_offset as usize)].copy_from_slice(data);
+        self.set_all_visible(false);
 
         Ok(RID(
             self.page_id(),
@@ -78,20 +85,46 @@ impl TablePage {
         ))
     }
     pub fn delete(&mut self, index: u32, txn_id: TransactionID) {
+        self.set_xmax(index, txn_id);
+    }
+    pub fn set_xmax(&mut self, index: u32, xmax: TransactionID) {
         let offset = self.line_pointer_offset(index as usize) as usize;
         let size = self.line_pointer_size(index as usize) as usize;
         let mut tuple = Tuple::new(None, &self.data[offset..(offset + size)]);
-        tuple.set_xmax(txn_id);
+        tuple.set_xmax(xmax);
         self.data[offset..(offset + size)].copy_from_slice(&tuple.data);
+        self.set_all_visible(false);
+    }
+    // removes the tuples and compacts the rest, the line pointers are kept unused
+    // so that the rids of the other tuples do not change
+    pub fn prune(&mut self, indexes: &[u32]) {
+        for index in indexes {
+            self.set_line_pointer(*index as usize, 0, 0);
+        }
+        let tuples = self.get_tuples();
+        let mut upper_offset = PAGE_SIZE;
+        for (index, data) in tuples.iter().enumerate() {
+            if let Some(data) = data {
+                upper_offset -= data.len();
+                self.data[upper_offset..(upper_offset + data.len())].copy_from_slice(data);
+                self.set_line_pointer(index, upper_offset as u32, data.len() as u32);
+            }
+        }
+        self.data[UPPER_OFFSET_OFFSET..(UPPER_OFFSET_OFFSET + UPPER_OFFSET_SIZE)]
+            .copy_from_slice(&(upper_offset as u32).to_le_bytes());
     }
-    pub fn get_tuples(&self) -> Vec<Box<[u8]>> {
+    // the positions are the tuple indexes, None for the unused line pointers
+    pub fn get_tuples(&self) -> Vec<Option<Box<[u8]>>> {
         let count = self.tuple_count();
         (0..count).map(|i| self.get_tuple(i)).collect()
     }
-    pub fn get_tuple(&self, index: usize) -> Box<[u8]> {
+    pub fn get_tuple(&self, index: usize) -> Option<Box<[u8]>> {
         let offset = self.line_pointer_offset(index) as usize;
         let size = self.line_pointer_size(index) as usize;
-        self.data[offset..(offset + size)].into()
+        if size == 0 {
+            return None;
+        }
+        Some(self.data[offset..(offset + size)].into())
     }
     pub fn tuple_count(&self) -> usize {
         let lower_offset = self.lower_offset();
@@ -121,6 +154,23 @@ impl TablePage {
         self.data[NEXT_PAGE_ID_OFFSET..(NEXT_PAGE_ID_OFFSET + NEXT_PAGE_ID_SIZE)]
             .copy_from_slice(&page_id.0.to_le_bytes());
     }
+    // not logged, a lost flag is only set again by the next vacuum
+    pub fn is_all_visible(&self) -> bool {
+        self.flags() & ALL_VISIBLE_FLAG != 0
+    }
+    pub fn set_all_visible(&mut self, all_visible: bool) {
+        let flags = if all_visible {
+            self.flags() | ALL_VISIBLE_FLAG
+        } else {
+            self.flags() & !ALL_VISIBLE_FLAG
+        };
+        self.data[FLAGS_OFFSET..(FLAGS_OFFSET + FLAGS_SIZE)].copy_from_slice(&flags.to_le_bytes());
+    }
+    fn flags(&self) -> u32 {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&self.data[FLAGS_OFFSET..(FLAGS_OFFSET + FLAGS_SIZE)]);
+        u32::from_le_bytes(bytes)
+    }
     fn free_space(&self) -> usize {
         let lower_offset = self.lower_offset();
         let upper_offset = self.upper_offset();
@@ -140,6 +190,14 @@ impl TablePage {
         );
         u32::from_le_bytes(bytes)
     }
+    fn set_line_pointer(&mut self, index: usize, offset: u32, size: u32) {
+        let line_pointer_offset = HEADER_SIZE + index * LINE_POINTER_SIZE;
+        self.data[line_pointer_offset..(line_pointer_offset + LINE_POINTER_OFFSET_SIZE)]
+            .copy_from_slice(&offset.to_le_bytes());
+        self.data[(line_pointer_offset + LINE_POINTER_OFFSET_SIZE)
+            ..(line_pointer_offset + LINE_POINTER_SIZE)]
+            .copy_from_slice(&size.to_le_bytes());
+    }
     fn line_pointer_offset(&self, index: usize) -> u32 {
         let offset = HEADER_SIZE + index * LINE_POINTER_SIZE;
         let mut bytes = [0u8; 4];
@@ -153,3 +211,51 @@ impl TablePage {
         u32::from_le_bytes(bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_all_visible_flag() -> Result<()> {
+        let mut page = TablePage::new(PageID(1));
+        assert!(!page.is_all_visible());
+        page.insert(&[1, 2, 3])?;
+        page.set_all_visible(true);
+        assert!(page.is_all_visible());
+        let page_copy = TablePage::from_data(&page.data);
+        assert!(page_copy.is_all_visible());
+        assert_eq!(page_copy.get_tuple(0), page.get_tuple(0));
+
+        // modifications clear the flag
+        page.insert(&[0; 16])?;
+        assert!(!page.is_all_visible());
+        page.set_all_visible(true);
+        page.delete(1, TransactionID(2));
+        assert!(!page.is_all_visible());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prune() -> Result<()> {
+        let mut page = TablePage::new(PageID(1));
+        page.insert(&[1; 100])?;
+        page.insert(&[2; 200])?;
+        page.insert(&[3; 300])?;
+        let free_space = page.free_space();
+        page.prune(&[0, 1]);
+        assert_eq!(page.free_space(), free_space + 300);
+        assert_eq!(page.tuple_count(), 3);
+        assert_eq!(
+            page.get_tuples(),
+            vec![None, None, Some(vec![3; 300].into())]
+        );
+
+        // the rid of a new tuple is not the one of a removed tuple
+        let rid = page.insert(&[4; 400])?;
+        assert_eq!(rid, RID(PageID(1), 3));
+        assert_eq!(page.get_tuple(3), Some(vec![4; 400].into()));
+        assert_eq!(page.get_tuple(2), Some(vec![3; 300].into()));
+        Ok(())
+    }
+}
@@ -2,37 +2,69 @@ use anyhow::{anyhow, Result};
 
 use crate::{
     catalog::DataType,
+    concurrency::IsolationLevel,
     lexer::{Keyword, Token},
     value::{integer::IntegerValue, Value},
 };
 
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum StatementAST {
     CreateTable(CreateTableStatementAST),
     CreateIndex(CreateIndexStatementAST),
+    DropIndex(DropIndexStatementAST),
+    ShowIndexes(ShowIndexesStatementAST),
     Select(SelectStatementAST),
     Insert(InsertStatementAST),
     Delete(DeleteStatementAST),
     Update(UpdateStatementAST),
-    Begin,
+    Set(SetStatementAST),
+    SetTransaction(SetTransactionStatementAST),
+    Begin(BeginStatementAST),
     Commit,
     Rollback,
+    Savepoint(SavepointStatementAST),
+    RollbackToSavepoint(RollbackToSavepointStatementAST),
+    ReleaseSavepoint(ReleaseSavepointStatementAST),
+    Vacuum(VacuumStatementAST),
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CreateTableStatementAST {
     pub table_name: String,
     pub elements: Vec<TableElementAST>,
+    pub constraints: Vec<TableConstraintAST>,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct TableElementAST {
     pub column_name: String,
     pub data_type: DataType,
+    pub constraints: Vec<ConstraintTypeAST>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TableConstraintAST {
+    pub constraint_type: ConstraintTypeAST,
+    pub column_names: Vec<String>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ConstraintTypeAST {
+    PrimaryKey,
+    Unique,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct CreateIndexStatementAST {
     pub index_name: String,
     pub table_name: String,
     pub column_names: Vec<String>,
+    pub unique: bool,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DropIndexStatementAST {
+    pub index_name: String,
+    pub if_exists: bool,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ShowIndexesStatementAST {
+    pub table_name: String,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SelectStatementAST {
@@ -43,6 +75,7 @@ pub struct SelectStatementAST {
     pub having: Option<ExpressionAST>,
     pub order_by: Option<Vec<OrderByElementAST>>,
     pub limit: Option<LimitAST>,
+    pub locking_clause: Option<LockingClauseAST>,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SelectElementAST {
@@ -93,6 +126,22 @@ pub struct LimitAST {
     pub offset: ExpressionAST,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LockingClauseAST {
+    pub strength: LockStrength,
+    pub wait_policy: WaitPolicy,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LockStrength {
+    Update,
+    Share,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum WaitPolicy {
+    Wait,
+    NoWait,
+    SkipLocked,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct InsertStatementAST {
     pub table_name: String,
     pub column_names: Option<Vec<String>>,
@@ -111,6 +160,35 @@ pub struct UpdateStatementAST {
     pub condition: Option<ExpressionAST>,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SetStatementAST {
+    pub name: String,
+    pub value: Value,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SetTransactionStatementAST {
+    pub isolation_level: IsolationLevel,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BeginStatementAST {
+    pub isolation_level: Option<IsolationLevel>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SavepointStatementAST {
+    pub name: String,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RollbackToSavepointStatementAST {
+    pub name: String,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ReleaseSavepointStatementAST {
+    pub name: String,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct VacuumStatementAST {
+    pub table_name: Option<String>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct AssignmentAST {
     pub target: PathExpressionAST,
     pub value: ExpressionAST,
@@ -195,10 +273,21 @@ impl Parser {
             return Ok(StatementAST::CreateTable(self.create_table_statement()?));
         }
         if self.match_token(Token::Keyword(Keyword::Create))
-            && self.match_look_ahead(Token::Keyword(Keyword::Index))
+            && (self.match_look_ahead(Token::Keyword(Keyword::Index))
+                || self.match_look_ahead(Token::Keyword(Keyword::Unique)))
         {
             return Ok(StatementAST::CreateIndex(self.create_index_statement()?));
         }
+        if self.match_token(Token::Keyword(Keyword::Drop))
+            && self.match_look_ahead(Token::Keyword(Keyword::Index))
+        {
+            return Ok(StatementAST::DropIndex(self.drop_index_statement()?));
+        }
+        if self.match_token(Token::Keyword(Keyword::Show))
+            && self.match_look_ahead(Token::Keyword(Keyword::Indexes))
+        {
+            return Ok(StatementAST::ShowIndexes(self.show_indexes_statement()?));
+        }
         if self.match_token(Token::Keyword(Keyword::Select)) {
             return Ok(StatementAST::Select(self.select_statement()?));
         }
@@ -211,18 +300,45 @@ impl Parser {
         if self.match_token(Token::Keyword(Keyword::Update)) {
             return Ok(StatementAST::Update(self.update_statement()?));
         }
+        if self.match_token(Token::Keyword(Keyword::Set))
+            && self.match_look_ahead(Token::Keyword(Keyword::Transaction))
+        {
+            return Ok(StatementAST::SetTransaction(
+                self.set_transaction_statement()?,
+            ));
+        }
+        if self.match_token(Token::Keyword(Keyword::Set)) {
+            return Ok(StatementAST::Set(self.set_statement()?));
+        }
         if self.match_token(Token::Keyword(Keyword::Begin)) {
-            self.consume_token(Token::Keyword(Keyword::Begin));
-            return Ok(StatementAST::Begin);
+            return Ok(StatementAST::Begin(self.begin_statement()?));
         }
         if self.match_token(Token::Keyword(Keyword::Commit)) {
             self.consume_token(Token::Keyword(Keyword::Commit));
             return Ok(StatementAST::Commit);
         }
+        if self.match_token(Token::Keyword(Keyword::Rollback))
+            && self.match_look_ahead(Token::Keyword(Keyword::To))
+        {
+            return Ok(StatementAST::RollbackToSavepoint(
+                self.rollback_to_savepoint_statement()?,
+            ));
+        }
         if self.match_token(Token::Keyword(Keyword::Rollback)) {
             self.consume_token(Token::Keyword(Keyword::Rollback));
             return Ok(StatementAST::Rollback);
         }
+        if self.match_token(Token::Keyword(Keyword::Savepoint)) {
+            return Ok(StatementAST::Savepoint(self.savepoint_statement()?));
+        }
+        if self.match_token(Token::Keyword(Keyword::Vacuum)) {
+            return Ok(StatementAST::Vacuum(self.vacuum_statement()?));
+        }
+        if self.match_token(Token::Keyword(Keyword::Release)) {
+            return Ok(StatementAST::ReleaseSavepoint(
+                self.release_savepoint_statement()?,
+            ));
+        }
         Err(anyhow!("invalid statement"))
     }
     fn create_table_statement(&mut self) -> Result<CreateTableStatementAST> {
@@ -231,8 +347,16 @@ impl Parser {
         let table_name = self.identifier()?;
         self.consume_token_or_error(Token::LeftParen)?;
         let mut elements = Vec::new();
+        let mut constraints = Vec::new();
         loop {
-            elements.push(self.table_element()?);
+            if let Some(constraint_type) = self.constraint_type()? {
+                constraints.push(TableConstraintAST {
+                    constraint_type,
+                    column_names: self.column_name_list()?,
+                });
+            } else {
+                elements.push(self.table_element()?);
+            }
             if !self.consume_token(Token::Comma) {
                 break;
             }
@@ -241,16 +365,44 @@ impl Parser {
         Ok(CreateTableStatementAST {
             table_name,
             elements,
+            constraints,
         })
     }
     fn table_element(&mut self) -> Result<TableElementAST> {
         let column_name = self.identifier()?;
         let data_type = self.data_type()?;
+        let mut constraints = Vec::new();
+        while let Some(constraint_type) = self.constraint_type()? {
+            constraints.push(constraint_type);
+        }
         Ok(TableElementAST {
             column_name,
             data_type,
+            constraints,
         })
     }
+    fn constraint_type(&mut self) -> Result<Option<ConstraintTypeAST>> {
+        if self.consume_token(Token::Keyword(Keyword::Primary)) {
+            self.consume_token_or_error(Token::Keyword(Keyword::Key))?;
+            return Ok(Some(ConstraintTypeAST::PrimaryKey));
+        }
+        if self.consume_token(Token::Keyword(Keyword::Unique)) {
+            return Ok(Some(ConstraintTypeAST::Unique));
+        }
+        Ok(None)
+    }
+    fn column_name_list(&mut self) -> Result<Vec<String>> {
+        self.consume_token_or_error(Token::LeftParen)?;
+        let mut column_names = Vec::new();
+        loop {
+            column_names.push(self.identifier()?);
+            if !self.consume_token(Token::Comma) {
+                break;
+            }
+        }
+        self.consume_token_or_error(Token::RightParen)?;
+        Ok(column_names)
+    }
     fn data_type(&mut self) -> Result<DataType> {
         match self.tokens[self.position] {
             Token::Keyword(Keyword::Int) => {
@@ -274,25 +426,39 @@ impl Parser {
     }
     fn create_index_statement(&mut self) -> Result<CreateIndexStatementAST> {
         self.consume_token_or_error(Token::Keyword(Keyword::Create))?;
+        let unique = self.consume_token(Token::Keyword(Keyword::Unique));
         self.consume_token_or_error(Token::Keyword(Keyword::Index))?;
         let index_name = self.identifier()?;
         self.consume_token_or_error(Token::Keyword(Keyword::On))?;
         let table_name = self.identifier()?;
-        self.consume_token_or_error(Token::LeftParen)?;
-        let mut column_names = Vec::new();
-        loop {
-            column_names.push(self.identifier()?);
-            if !self.consume_token(Token::Comma) {
-                break;
-            }
-        }
-        self.consume_token_or_error(Token::RightParen)?;
+        let column_names = self.column_name_list()?;
         Ok(CreateIndexStatementAST {
             index_name,
             table_name,
             column_names,
+            unique,
+        })
+    }
+    fn drop_index_statement(&mut self) -> Result<DropIndexStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Drop))?;
+        self.consume_token_or_error(Token::Keyword(Keyword::Index))?;
+        let if_exists = self.consume_token(Token::Keyword(Keyword::If));
+        if if_exists {
+            self.consume_token_or_error(Token::Keyword(Keyword::Exists))?;
+        }
+        let index_name = self.identifier()?;
+        Ok(DropIndexStatementAST {
+            index_name,
+            if_exists,
         })
     }
+    fn show_indexes_statement(&mut self) -> Result<ShowIndexesStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Show))?;
+        self.consume_token_or_error(Token::Keyword(Keyword::Indexes))?;
+        self.consume_token_or_error(Token::Keyword(Keyword::From))?;
+        let table_name = self.identifier()?;
+        Ok(ShowIndexesStatementAST { table_name })
+    }
     fn select_statement(&mut self) -> Result<SelectStatementAST> {
         self.consume_token_or_error(Token::Keyword(Keyword::Select))?;
         let select_elements = if self.consume_token(Token::Asterisk) {
@@ -361,6 +527,28 @@ impl Parser {
         } else {
             None
         };
+        let locking_clause = if self.consume_token(Token::Keyword(Keyword::For)) {
+            let strength = if self.consume_token(Token::Keyword(Keyword::Share)) {
+                LockStrength::Share
+            } else {
+                self.consume_token_or_error(Token::Keyword(Keyword::Update))?;
+                LockStrength::Update
+            };
+            let wait_policy = if self.consume_token(Token::Keyword(Keyword::Nowait)) {
+                WaitPolicy::NoWait
+            } else if self.consume_token(Token::Keyword(Keyword::Skip)) {
+                self.consume_token_or_error(Token::Keyword(Keyword::Locked))?;
+                WaitPolicy::SkipLocked
+            } else {
+                WaitPolicy::Wait
+            };
+            Some(LockingClauseAST {
+                strength,
+                wait_policy,
+            })
+        } else {
+            None
+        };
         Ok(SelectStatementAST {
             select_elements,
             table_reference,
@@ -369,6 +557,7 @@ impl Parser {
             having,
             order_by,
             limit,
+            locking_clause,
         })
     }
     fn select_element(&mut self) -> Result<SelectElementAST> {
@@ -483,7 +672,11 @@ impl Parser {
             }
         }
         self.consume_token_or_error(Token::RightParen)?;
-        Ok(InsertStatementAST { table_name, column_names, values })
+        Ok(InsertStatementAST {
+            table_name,
+            column_names,
+            values,
+        })
     }
     fn delete_statement(&mut self) -> Result<DeleteStatementAST> {
         self.consume_token_or_error(Token::Keyword(Keyword::Delete))?;
@@ -527,6 +720,70 @@ impl Parser {
         let value = self.expression()?;
         Ok(AssignmentAST { target, value })
     }
+    fn set_statement(&mut self) -> Result<SetStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Set))?;
+        let name = self.identifier()?;
+        self.consume_token_or_error(Token::Equal)?;
+        let value = self.literal()?;
+        Ok(SetStatementAST { name, value })
+    }
+    fn set_transaction_statement(&mut self) -> Result<SetTransactionStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Set))?;
+        self.consume_token_or_error(Token::Keyword(Keyword::Transaction))?;
+        let isolation_level = self.isolation_level()?;
+        Ok(SetTransactionStatementAST { isolation_level })
+    }
+    fn begin_statement(&mut self) -> Result<BeginStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Begin))?;
+        self.consume_token(Token::Keyword(Keyword::Transaction));
+        let isolation_level = if self.match_token(Token::Keyword(Keyword::Isolation)) {
+            Some(self.isolation_level()?)
+        } else {
+            None
+        };
+        Ok(BeginStatementAST { isolation_level })
+    }
+    fn vacuum_statement(&mut self) -> Result<VacuumStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Vacuum))?;
+        let table_name = if self.match_identifier() {
+            Some(self.identifier()?)
+        } else {
+            None
+        };
+        Ok(VacuumStatementAST { table_name })
+    }
+    fn savepoint_statement(&mut self) -> Result<SavepointStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Savepoint))?;
+        let name = self.identifier()?;
+        Ok(SavepointStatementAST { name })
+    }
+    fn rollback_to_savepoint_statement(&mut self) -> Result<RollbackToSavepointStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Rollback))?;
+        self.consume_token_or_error(Token::Keyword(Keyword::To))?;
+        self.consume_token(Token::Keyword(Keyword::Savepoint));
+        let name = self.identifier()?;
+        Ok(RollbackToSavepointStatementAST { name })
+    }
+    fn release_savepoint_statement(&mut self) -> Result<ReleaseSavepointStatementAST> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Release))?;
+        self.consume_token(Token::Keyword(Keyword::Savepoint));
+        let name = self.identifier()?;
+        Ok(ReleaseSavepointStatementAST { name })
+    }
+    fn isolation_level(&mut self) -> Result<IsolationLevel> {
+        self.consume_token_or_error(Token::Keyword(Keyword::Isolation))?;
+        self.consume_token_or_error(Token::Keyword(Keyword::Level))?;
+        if self.consume_token(Token::Keyword(Keyword::Serializable)) {
+            return Ok(IsolationLevel::Serializable);
+        }
+        if self.consume_token(Token::Keyword(Keyword::Repeatable)) {
+            self.consume_token_or_error(Token::Keyword(Keyword::Read))?;
+            return Ok(IsolationLevel::RepeatableRead);
+        }
+        self.consume_token_or_error(Token::Keyword(Keyword::Read))?;
+        self.consume_token_or_error(Token::Keyword(Keyword::Committed))?;
+        Ok(IsolationLevel::ReadCommitted)
+    }
 
     fn expression(&mut self) -> Result<ExpressionAST> {
         self.logical_or_expression()
@@ -773,20 +1030,25 @@ mod tests {
                     TableElementAST {
                         column_name: String::from("c0"),
                         data_type: DataType::Integer,
+                        constraints: vec![],
                     },
                     TableElementAST {
                         column_name: String::from("c1"),
                         data_type: DataType::Integer,
+                        constraints: vec![],
                     },
                     TableElementAST {
                         column_name: String::from("c2"),
                         data_type: DataType::Varchar,
+                        constraints: vec![],
                     },
                     TableElementAST {
                         column_name: String::from("c3"),
                         data_type: DataType::Boolean,
+                        constraints: vec![],
                     },
                 ],
+                constraints: vec![],
             })
         );
         Ok(())
@@ -806,6 +1068,112 @@ mod tests {
                 index_name: String::from("id_name_index"),
                 table_name: String::from("users"),
                 column_names: vec![String::from("id"), String::from("name")],
+                unique: false,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_create_table_with_constraints() -> Result<()> {
+        let sql = r#"
+            CREATE TABLE users (
+                id INT PRIMARY KEY,
+                email VARCHAR UNIQUE,
+                org_id INT,
+                name VARCHAR,
+                UNIQUE (org_id, name)
+            );
+        "#;
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+
+        let statement = parser.parse()?;
+        assert_eq!(
+            statement,
+            StatementAST::CreateTable(CreateTableStatementAST {
+                table_name: String::from("users"),
+                elements: vec![
+                    TableElementAST {
+                        column_name: String::from("id"),
+                        data_type: DataType::Integer,
+                        constraints: vec![ConstraintTypeAST::PrimaryKey],
+                    },
+                    TableElementAST {
+                        column_name: String::from("email"),
+                        data_type: DataType::Varchar,
+                        constraints: vec![ConstraintTypeAST::Unique],
+                    },
+                    TableElementAST {
+                        column_name: String::from("org_id"),
+                        data_type: DataType::Integer,
+                        constraints: vec![],
+                    },
+                    TableElementAST {
+                        column_name: String::from("name"),
+                        data_type: DataType::Varchar,
+                        constraints: vec![],
+                    },
+                ],
+                constraints: vec![TableConstraintAST {
+                    constraint_type: ConstraintTypeAST::Unique,
+                    column_names: vec![String::from("org_id"), String::from("name")],
+                }],
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_create_unique_index() -> Result<()> {
+        let sql = r#"
+            CREATE UNIQUE INDEX email_index ON users (email);
+        "#;
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+
+        let statement = parser.parse()?;
+        assert_eq!(
+            statement,
+            StatementAST::CreateIndex(CreateIndexStatementAST {
+                index_name: String::from("email_index"),
+                table_name: String::from("users"),
+                column_names: vec![String::from("email")],
+                unique: true,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_drop_index() -> Result<()> {
+        let sql = "DROP INDEX email_index";
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+        assert_eq!(
+            parser.parse()?,
+            StatementAST::DropIndex(DropIndexStatementAST {
+                index_name: String::from("email_index"),
+                if_exists: false,
+            })
+        );
+        let sql = "DROP INDEX IF EXISTS email_index";
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+        assert_eq!(
+            parser.parse()?,
+            StatementAST::DropIndex(DropIndexStatementAST {
+                index_name: String::from("email_index"),
+                if_exists: true,
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_show_indexes() -> Result<()> {
+        let sql = "SHOW INDEXES FROM users";
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+        assert_eq!(
+            parser.parse()?,
+            StatementAST::ShowIndexes(ShowIndexesStatementAST {
+                table_name: String::from("users"),
             })
         );
         Ok(())
@@ -886,6 +1254,7 @@ mod tests {
                         value: Value::Integer(IntegerValue(5)),
                     }),
                 }),
+                locking_clause: None,
             })
         );
         Ok(())
@@ -910,11 +1279,66 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
     }
 
+    #[test]
+    fn test_parse_select_locking_clause() -> Result<()> {
+        for (sql, strength, wait_policy) in [
+            (
+                "SELECT * FROM jobs FOR UPDATE",
+                LockStrength::Update,
+                WaitPolicy::Wait,
+            ),
+            (
+                "SELECT * FROM jobs FOR UPDATE NOWAIT",
+                LockStrength::Update,
+                WaitPolicy::NoWait,
+            ),
+            (
+                "SELECT * FROM jobs FOR UPDATE SKIP LOCKED",
+                LockStrength::Update,
+                WaitPolicy::SkipLocked,
+            ),
+            (
+                "SELECT * FROM jobs FOR SHARE",
+                LockStrength::Share,
+                WaitPolicy::Wait,
+            ),
+            (
+                "SELECT * FROM jobs FOR SHARE NOWAIT",
+                LockStrength::Share,
+                WaitPolicy::NoWait,
+            ),
+        ] {
+            let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+            let statement = parser.parse()?;
+            assert_eq!(
+                statement,
+                StatementAST::Select(SelectStatementAST {
+                    select_elements: vec![],
+                    table_reference: Some(TableReferenceAST::Base(BaseTableReferenceAST {
+                        table_name: String::from("jobs"),
+                        alias: None,
+                    })),
+                    condition: None,
+                    group_by: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    locking_clause: Some(LockingClauseAST {
+                        strength,
+                        wait_policy,
+                    }),
+                })
+            );
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_parse_select_group_by() -> Result<()> {
         let sql = "SELECT id FROM users GROUP BY id, name HAVING id = 1";
@@ -954,6 +1378,7 @@ mod tests {
                 })),
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -1089,6 +1514,111 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_set() -> Result<()> {
+        let sql = "SET lock_timeout = 1000";
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+        assert_eq!(
+            parser.parse()?,
+            StatementAST::Set(SetStatementAST {
+                name: String::from("lock_timeout"),
+                value: Value::Integer(IntegerValue(1000)),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_isolation_level() -> Result<()> {
+        let parse = |sql: &str| -> Result<StatementAST> {
+            Parser::new(tokenize(&mut sql.chars().peekable())?).parse()
+        };
+        assert_eq!(
+            parse("BEGIN")?,
+            StatementAST::Begin(BeginStatementAST {
+                isolation_level: None
+            })
+        );
+        assert_eq!(
+            parse("BEGIN TRANSACTION ISOLATION LEVEL SERIALIZABLE")?,
+            StatementAST::Begin(BeginStatementAST {
+                isolation_level: Some(IsolationLevel::Serializable)
+            })
+        );
+        assert_eq!(
+            parse("BEGIN ISOLATION LEVEL READ COMMITTED")?,
+            StatementAST::Begin(BeginStatementAST {
+                isolation_level: Some(IsolationLevel::ReadCommitted)
+            })
+        );
+        assert_eq!(
+            parse("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ")?,
+            StatementAST::SetTransaction(SetTransactionStatementAST {
+                isolation_level: IsolationLevel::RepeatableRead
+            })
+        );
+        assert!(parse("SET TRANSACTION ISOLATION LEVEL READ").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_savepoint() -> Result<()> {
+        let parse = |sql: &str| -> Result<StatementAST> {
+            Parser::new(tokenize(&mut sql.chars().peekable())?).parse()
+        };
+        assert_eq!(
+            parse("SAVEPOINT sp1")?,
+            StatementAST::Savepoint(SavepointStatementAST {
+                name: String::from("sp1")
+            })
+        );
+        assert_eq!(
+            parse("ROLLBACK TO SAVEPOINT sp1")?,
+            StatementAST::RollbackToSavepoint(RollbackToSavepointStatementAST {
+                name: String::from("sp1")
+            })
+        );
+        assert_eq!(
+            parse("ROLLBACK TO sp1")?,
+            StatementAST::RollbackToSavepoint(RollbackToSavepointStatementAST {
+                name: String::from("sp1")
+            })
+        );
+        assert_eq!(
+            parse("RELEASE SAVEPOINT sp1")?,
+            StatementAST::ReleaseSavepoint(ReleaseSavepointStatementAST {
+                name: String::from("sp1")
+            })
+        );
+        assert_eq!(
+            parse("RELEASE sp1")?,
+            StatementAST::ReleaseSavepoint(ReleaseSavepointStatementAST {
+                name: String::from("sp1")
+            })
+        );
+        assert_eq!(parse("ROLLBACK")?, StatementAST::Rollback);
+        assert!(parse("SAVEPOINT").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_vacuum() -> Result<()> {
+        let parse = |sql: &str| -> Result<StatementAST> {
+            Parser::new(tokenize(&mut sql.chars().peekable())?).parse()
+        };
+        assert_eq!(
+            parse("VACUUM")?,
+            StatementAST::Vacuum(VacuumStatementAST { table_name: None })
+        );
+        assert_eq!(
+            parse("VACUUM t1;")?,
+            StatementAST::Vacuum(VacuumStatementAST {
+                table_name: Some(String::from("t1"))
+            })
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_parse_nested_join() -> Result<()> {
         let sql = r#"
@@ -1162,6 +1692,7 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -1200,6 +1731,7 @@ mod tests {
                             having: None,
                             order_by: None,
                             limit: None,
+                            locking_clause: None,
                         }),
                         alias: String::from("a"),
                     })),
@@ -1215,6 +1747,7 @@ mod tests {
                             having: None,
                             order_by: None,
                             limit: None,
+                            locking_clause: None,
                         }),
                         alias: String::from("u"),
                     })),
@@ -1234,6 +1767,7 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -1266,6 +1800,7 @@ mod tests {
                 having: None,
                 order_by: None,
                 limit: None,
+                locking_clause: None,
             })
         );
         Ok(())
@@ -1,25 +1,29 @@
 use crate::{
     binder::{
-        BoundAssignmentAST, BoundBaseTableReferenceAST, BoundBinaryExpressionAST,
-        BoundDeleteStatementAST, BoundExpressionAST, BoundFunctionCallExpressionAST,
-        BoundInsertStatementAST, BoundJoinTableReferenceAST, BoundLimitAST, BoundOrderByElementAST,
-        BoundSelectElementAST, BoundSelectStatementAST, BoundStatementAST,
-        BoundSubqueryTableReferenceAST, BoundTableReferenceAST, BoundUpdateStatementAST,
+        BoundAssignmentAST, BoundBaseTableReferenceAST, BoundDeleteStatementAST,
+        BoundExpressionAST, BoundFunctionCallExpressionAST, BoundInsertStatementAST,
+        BoundJoinTableReferenceAST, BoundLimitAST, BoundOrderByElementAST, BoundSelectElementAST,
+        BoundSelectStatementAST, BoundStatementAST, BoundSubqueryTableReferenceAST,
+        BoundSystemViewReferenceAST, BoundTableReferenceAST, BoundUpdateStatementAST,
     },
     catalog::{Column, DataType, Schema},
     common::PageID,
-    parser::JoinType,
+    index::IndexRange,
+    parser::{JoinType, LockStrength, WaitPolicy},
 };
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Plan {
     SeqScan(SeqScanPlan),
+    SystemViewScan(SystemViewScanPlan),
     IndexScan(IndexScanPlan),
+    IndexOnlyScan(IndexOnlyScanPlan),
     Filter(FilterPlan),
     Project(ProjectPlan),
     NestedLoopJoin(NestedLoopJoinPlan),
     Aggregate(AggregatePlan),
     Sort(SortPlan),
+    LockRows(LockRowsPlan),
     Limit(LimitPlan),
     EmptyRow(EmptyRowPlan),
     Insert(InsertPlan),
@@ -30,12 +34,15 @@ impl Plan {
     pub fn schema(&self) -> &Schema {
         match self {
             Plan::SeqScan(plan) => &plan.schema,
+            Plan::SystemViewScan(plan) => &plan.schema,
             Plan::IndexScan(plan) => &plan.schema,
+            Plan::IndexOnlyScan(plan) => &plan.schema,
             Plan::Filter(plan) => &plan.schema,
             Plan::Project(plan) => &plan.schema,
             Plan::NestedLoopJoin(plan) => &plan.schema,
             Plan::Aggregate(plan) => &plan.schema,
             Plan::Sort(plan) => &plan.schema,
+            Plan::LockRows(plan) => &plan.schema,
             Plan::Limit(plan) => &plan.schema,
             Plan::EmptyRow(plan) => &plan.schema,
             Plan::Insert(plan) => &plan.schema,
@@ -46,12 +53,15 @@ impl Plan {
     pub fn children(&self) -> Vec<Box<Plan>> {
         match self {
             Plan::SeqScan(_) => vec![],
+            Plan::SystemViewScan(_) => vec![],
             Plan::IndexScan(_) => vec![],
+            Plan::IndexOnlyScan(_) => vec![],
             Plan::Filter(plan) => vec![plan.child.clone()],
             Plan::Project(plan) => vec![plan.child.clone()],
             Plan::NestedLoopJoin(plan) => plan.children.clone(),
             Plan::Aggregate(plan) => vec![plan.child.clone()],
             Plan::Sort(plan) => vec![plan.child.clone()],
+            Plan::LockRows(plan) => vec![plan.child.clone()],
             Plan::Limit(plan) => vec![plan.child.clone()],
             Plan::EmptyRow(_) => vec![],
             Plan::Insert(_) => vec![],
@@ -62,34 +72,52 @@ impl Plan {
     pub fn set_children(&mut self, children: Vec<Plan>) {
         match self {
             Plan::SeqScan(_) => {}
+            Plan::SystemViewScan(_) => {}
             Plan::IndexScan(_) => {}
-            Plan::Filter(plan) => plan.child = Box::new(children[0].clone()),
-            Plan::Project(plan) => plan.child = Box::new(children[0].clone()),
+            Plan::IndexOnlyScan(_) => {}
+            Plan::Filter(plan) => *plan.child = children[0].clone(),
+            Plan::Project(plan) => *plan.child = children[0].clone(),
             Plan::NestedLoopJoin(plan) => {
                 plan.children = children.into_iter().map(Box::new).collect()
             }
-            Plan::Aggregate(plan) => plan.child = Box::new(children[0].clone()),
-            Plan::Sort(plan) => plan.child = Box::new(children[0].clone()),
-            Plan::Limit(plan) => plan.child = Box::new(children[0].clone()),
+            Plan::Aggregate(plan) => *plan.child = children[0].clone(),
+            Plan::Sort(plan) => *plan.child = children[0].clone(),
+            Plan::LockRows(plan) => *plan.child = children[0].clone(),
+            Plan::Limit(plan) => *plan.child = children[0].clone(),
             Plan::EmptyRow(_) => {}
             Plan::Insert(_) => {}
-            Plan::Delete(plan) => plan.child = Box::new(children[0].clone()),
-            Plan::Update(plan) => plan.child = Box::new(children[0].clone()),
+            Plan::Delete(plan) => *plan.child = children[0].clone(),
+            Plan::Update(plan) => *plan.child = children[0].clone(),
         }
     }
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SeqScanPlan {
+    pub table_name: String,
     pub first_page_id: PageID,
     pub schema: Schema,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SystemViewScanPlan {
+    pub view_name: String,
+    pub schema: Schema,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IndexScanPlan {
     pub index_id: i64,
+    pub first_page_id: PageID,
     pub schema: Schema,
-    pub binary_expression: BoundBinaryExpressionAST,
-    // TODO: remove
-    pub table_schema: Schema,
+    // disjoint and in ascending order
+    pub ranges: Vec<IndexRange>,
+}
+// rows have the schema of the table, the columns which are not in the index key are null
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct IndexOnlyScanPlan {
+    pub index_id: i64,
+    pub first_page_id: PageID,
+    pub schema: Schema,
+    // disjoint and in ascending order
+    pub ranges: Vec<IndexRange>,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FilterPlan {
@@ -124,6 +152,15 @@ pub struct SortPlan {
     pub child: Box<Plan>,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LockRowsPlan {
+    pub first_page_id: PageID,
+    pub table_name: String,
+    pub strength: LockStrength,
+    pub wait_policy: WaitPolicy,
+    pub schema: Schema,
+    pub child: Box<Plan>,
+}
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct LimitPlan {
     pub limit: BoundLimitAST,
     pub schema: Schema,
@@ -145,12 +182,14 @@ pub struct InsertPlan {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct DeletePlan {
     pub first_page_id: PageID,
+    pub table_name: String,
     pub schema: Schema,
     pub child: Box<Plan>,
 }
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct UpdatePlan {
     pub first_page_id: PageID,
+    pub table_name: String,
     pub assignments: Vec<BoundAssignmentAST>,
     pub schema: Schema,
     pub child: Box<Plan>,
@@ -295,6 +334,20 @@ impl Planner {
                 child: Box::new(plan),
             });
         }
+        // below the limit, so that skipped rows are not counted
+        if let (Some(locking_clause), Some(BoundTableReferenceAST::Base(table_reference))) = (
+            &select_statement.locking_clause,
+            select_statement.table_reference.as_deref(),
+        ) {
+            plan = Plan::LockRows(LockRowsPlan {
+                first_page_id: table_reference.first_page_id,
+                table_name: table_reference.table_name.clone(),
+                strength: locking_clause.strength.clone(),
+                wait_policy: locking_clause.wait_policy.clone(),
+                schema: plan.schema().clone(),
+                child: Box::new(plan),
+            });
+        }
         if let Some(limit) = &select_statement.limit {
             plan = Plan::Limit(LimitPlan {
                 limit: limit.clone(),
@@ -315,14 +368,24 @@ impl Planner {
             BoundTableReferenceAST::Subquery(table_reference) => {
                 self.plan_subquery_table_reference(table_reference)
             }
+            BoundTableReferenceAST::SystemView(table_reference) => {
+                self.plan_system_view_reference(table_reference)
+            }
         }
     }
     fn plan_base_table_reference(&self, table_reference: &BoundBaseTableReferenceAST) -> Plan {
         Plan::SeqScan(SeqScanPlan {
+            table_name: table_reference.table_name.clone(),
             first_page_id: table_reference.first_page_id,
             schema: table_reference.schema.clone(),
         })
     }
+    fn plan_system_view_reference(&self, table_reference: &BoundSystemViewReferenceAST) -> Plan {
+        Plan::SystemViewScan(SystemViewScanPlan {
+            view_name: table_reference.view_name.clone(),
+            schema: table_reference.schema.clone(),
+        })
+    }
     fn plan_join_table_reference(&self, table_reference: &BoundJoinTableReferenceAST) -> Plan {
         let mut conditions = vec![table_reference.condition.clone()];
         let mut join_types = vec![table_reference.join_type.clone()];
@@ -377,6 +440,9 @@ impl Planner {
             BoundTableReferenceAST::Subquery(table_reference) => {
                 vec![self.plan_subquery_table_reference(table_reference)]
             }
+            BoundTableReferenceAST::SystemView(table_reference) => {
+                vec![self.plan_system_view_reference(table_reference)]
+            }
         }
     }
     fn plan_insert_statement(&self, insert_statement: &BoundInsertStatementAST) -> Plan {
@@ -409,6 +475,7 @@ impl Planner {
         }
         Plan::Delete(DeletePlan {
             first_page_id,
+            table_name: delete_statement.table_reference.table_name.clone(),
             schema: Schema {
                 columns: vec![Column {
                     name: "__delete_count".to_owned(),
@@ -433,6 +500,7 @@ impl Planner {
         }
         Plan::Update(UpdatePlan {
             first_page_id,
+            table_name: update_statement.table_reference.table_name.clone(),
             assignments: update_statement.assignments.clone(),
             schema: Schema {
                 columns: vec![Column {
@@ -478,6 +546,7 @@ mod tests {
             plan,
             Plan::Delete(DeletePlan {
                 first_page_id: PageID(5),
+                table_name: "t1".to_string(),
                 schema: Schema {
                     columns: vec![Column {
                         name: "__delete_count".to_owned(),
@@ -513,6 +582,7 @@ mod tests {
                         ],
                     },
                     child: Box::new(Plan::SeqScan(SeqScanPlan {
+                        table_name: "t1".to_owned(),
                         first_page_id: PageID(5),
                         schema: Schema {
                             columns: vec![
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+
+use crate::{
+    common::{PageID, TransactionID},
+    concurrency::TransactionRollbackError,
+};
+
+// SIREAD locks only record what a serializable transaction has read, they never block
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum PredicateLockTarget {
+    // a sequential scan reads every row of the table, including the ones inserted later
+    Table(PageID),
+    // an index scan reads the whole key space of the index, inserted keys conflict with it
+    Index(PageID),
+    // a heap page of the tuples read through an index, deleted tuples conflict with it
+    Page(PageID),
+}
+
+struct SerializableTransaction {
+    // the next transaction id at the commit, transactions before it were concurrent
+    committed_before: Option<TransactionID>,
+    targets: HashSet<PredicateLockTarget>,
+    // rw-antidependencies: in_conflicts read what this transaction wrote,
+    // out_conflicts wrote what this transaction read
+    in_conflicts: HashSet<TransactionID>,
+    out_conflicts: HashSet<TransactionID>,
+}
+
+#[derive(Default)]
+pub struct PredicateLockManager {
+    locks: HashMap<PredicateLockTarget, HashSet<TransactionID>>,
+    transactions: HashMap<TransactionID, SerializableTransaction>,
+}
+
+impl PredicateLockManager {
+    pub fn register(&mut self, txn_id: TransactionID) {
+        self.transactions.insert(
+            txn_id,
+            SerializableTransaction {
+                committed_before: None,
+                targets: HashSet::new(),
+                in_conflicts: HashSet::new(),
+                out_conflicts: HashSet::new(),
+            },
+        );
+    }
+    pub fn is_registered(&self, txn_id: TransactionID) -> bool {
+        self.transactions.contains_key(&txn_id)
+    }
+    pub fn acquire(&mut self, txn_id: TransactionID, target: PredicateLockTarget) {
+        if let Some(transaction) = self.transactions.get_mut(&txn_id) {
+            if transaction.targets.insert(target) {
+                self.locks.entry(target).or_default().insert(txn_id);
+            }
+        }
+    }
+    // called before a write, concurrent readers of the targets did not see it
+    pub fn check_write(
+        &mut self,
+        txn_id: TransactionID,
+        targets: &[PredicateLockTarget],
+    ) -> Result<()> {
+        if !self.is_registered(txn_id) {
+            return Ok(());
+        }
+        let mut readers = vec![];
+        for target in targets {
+            if let Some(holders) = self.locks.get(target) {
+                readers.extend(holders.iter().copied());
+            }
+        }
+        for reader in readers {
+            if self.is_concurrent(reader, txn_id) {
+                self.add_conflict(reader, txn_id)?;
+            }
+        }
+        Ok(())
+    }
+    // the reader did not see a version of the concurrent writer
+    pub fn add_conflict(&mut self, reader: TransactionID, writer: TransactionID) -> Result<()> {
+        if reader == writer || !self.is_registered(reader) || !self.is_registered(writer) {
+            return Ok(());
+        }
+        self.transactions
+            .get_mut(&reader)
+            .unwrap()
+            .out_conflicts
+            .insert(writer);
+        self.transactions
+            .get_mut(&writer)
+            .unwrap()
+            .in_conflicts
+            .insert(reader);
+        // a dangerous structure has a pivot with both an in and an out conflict,
+        // the transaction adding the last edge is aborted
+        if !self.transactions[&reader].in_conflicts.is_empty()
+            || !self.transactions[&writer].out_conflicts.is_empty()
+        {
+            return Err(TransactionRollbackError(
+                "could not serialize access due to read/write dependencies among transactions"
+                    .to_string(),
+            )
+            .into());
+        }
+        Ok(())
+    }
+    // locks of a committed transaction are kept while transactions concurrent with it are running
+    pub fn commit(
+        &mut self,
+        txn_id: TransactionID,
+        next_txn_id: TransactionID,
+        oldest_active_txn_id: Option<TransactionID>,
+    ) {
+        if let Some(transaction) = self.transactions.get_mut(&txn_id) {
+            transaction.committed_before = Some(next_txn_id);
+        }
+        let finished = self
+            .transactions
+            .iter()
+            .filter(|(_, transaction)| match transaction.committed_before {
+                Some(committed_before) => match oldest_active_txn_id {
+                    Some(oldest_active_txn_id) => committed_before <= oldest_active_txn_id,
+                    None => true,
+                },
+                None => false,
+            })
+            .map(|(txn_id, _)| *txn_id)
+            .collect::<Vec<_>>();
+        // conflicts with them are kept in the sets of others, they still count for a pivot
+        for txn_id in finished {
+            self.release(txn_id);
+        }
+    }
+    pub fn abort(&mut self, txn_id: TransactionID) {
+        self.release(txn_id);
+        for transaction in self.transactions.values_mut() {
+            transaction.in_conflicts.remove(&txn_id);
+            transaction.out_conflicts.remove(&txn_id);
+        }
+    }
+    fn release(&mut self, txn_id: TransactionID) {
+        if let Some(transaction) = self.transactions.remove(&txn_id) {
+            for target in transaction.targets {
+                if let Some(holders) = self.locks.get_mut(&target) {
+                    holders.remove(&txn_id);
+                    if holders.is_empty() {
+                        self.locks.remove(&target);
+                    }
+                }
+            }
+        }
+    }
+    fn is_concurrent(&self, txn_id: TransactionID, other_txn_id: TransactionID) -> bool {
+        if txn_id == other_txn_id {
+            return false;
+        }
+        match self.transactions.get(&txn_id) {
+            Some(transaction) => match transaction.committed_before {
+                Some(committed_before) => other_txn_id < committed_before,
+                None => true,
+            },
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dangerous_structure() -> Result<()> {
+        let mut predicate_lock_manager = PredicateLockManager::default();
+        let table = PredicateLockTarget::Table(PageID(1));
+        let txn_id1 = TransactionID(1);
+        let txn_id2 = TransactionID(2);
+        let txn_id3 = TransactionID(3);
+        predicate_lock_manager.register(txn_id1);
+        predicate_lock_manager.register(txn_id2);
+        predicate_lock_manager.register(txn_id3);
+
+        // write skew: both read the table, then both write to it
+        predicate_lock_manager.acquire(txn_id1, table);
+        predicate_lock_manager.acquire(txn_id2, table);
+        predicate_lock_manager.check_write(txn_id1, &[table])?;
+        predicate_lock_manager.commit(txn_id1, TransactionID(4), Some(txn_id2));
+        assert!(predicate_lock_manager
+            .check_write(txn_id2, &[table])
+            .is_err());
+        predicate_lock_manager.abort(txn_id2);
+
+        // the conflicts of the aborted transaction do not count
+        predicate_lock_manager.check_write(txn_id3, &[table])?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrency() -> Result<()> {
+        let mut predicate_lock_manager = PredicateLockManager::default();
+        let table = PredicateLockTarget::Table(PageID(1));
+        let txn_id1 = TransactionID(1);
+        let txn_id2 = TransactionID(2);
+        let txn_id3 = TransactionID(3);
+        predicate_lock_manager.register(txn_id1);
+        predicate_lock_manager.register(txn_id2);
+        predicate_lock_manager.acquire(txn_id1, table);
+        predicate_lock_manager.commit(txn_id1, TransactionID(3), Some(txn_id2));
+        assert!(predicate_lock_manager.is_registered(txn_id1));
+
+        // began after the reader committed, so it is not a conflict
+        predicate_lock_manager.register(txn_id3);
+        predicate_lock_manager.check_write(txn_id3, &[table])?;
+        assert!(predicate_lock_manager.transactions[&txn_id3]
+            .in_conflicts
+            .is_empty());
+        predicate_lock_manager.check_write(txn_id2, &[table])?;
+        assert!(predicate_lock_manager.transactions[&txn_id2]
+            .in_conflicts
+            .contains(&txn_id1));
+
+        // released once no transaction concurrent with it is running
+        predicate_lock_manager.commit(txn_id2, TransactionID(4), Some(txn_id3));
+        assert!(!predicate_lock_manager.is_registered(txn_id1));
+        assert!(predicate_lock_manager.is_registered(txn_id2));
+        predicate_lock_manager.commit(txn_id3, TransactionID(4), None);
+        assert!(predicate_lock_manager.transactions.is_empty());
+        assert!(predicate_lock_manager.locks.is_empty());
+        Ok(())
+    }
+}
@@ -1,33 +1,57 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
 
 use anyhow::Result;
 
 use crate::{
     buffer::BufferPoolManager,
-    common::{TransactionID, INVALID_PAGE_ID},
-    log::{LogRecord, LogRecordBody},
+    common::{
+        PageID, TransactionID, INVALID_LSN, INVALID_PAGE_ID, INVALID_TRANSACTION_ID, LSN, RID,
+    },
+    concurrency::TransactionStatus,
+    log::{
+        BPlusTreePageEntry, Compensation, DeleteFromTablePage, LogManager, LogRecord,
+        LogRecordBody, UndoDeleteFromTablePage,
+    },
     page::{b_plus_tree_leaf_page::BPlusTreeLeafPage, table_page::TablePage, Page},
 };
 
 pub struct RecoveryManager {
     buffer_pool_manager: Arc<Mutex<BufferPoolManager>>,
+    log_manager: Arc<Mutex<LogManager>>,
     log_records: Vec<LogRecord>,
+    checkpoint_lsn: Option<LSN>,
     active_txn_ids: Vec<TransactionID>,
+    redo_lsn: LSN,
+    statuses: HashMap<TransactionID, TransactionStatus>,
+    next_txn_id: TransactionID,
+    free_page_ids: Vec<PageID>,
 }
 
 impl RecoveryManager {
     pub fn new(
         buffer_pool_manager: Arc<Mutex<BufferPoolManager>>,
+        log_manager: Arc<Mutex<LogManager>>,
         log_records: Vec<LogRecord>,
+        checkpoint_lsn: Option<LSN>,
     ) -> Self {
         Self {
             buffer_pool_manager,
+            log_manager,
             log_records,
+            checkpoint_lsn,
             active_txn_ids: vec![],
+            redo_lsn: LSN(0),
+            statuses: HashMap::new(),
+            next_txn_id: TransactionID(1),
+            free_page_ids: vec![],
         }
     }
 
     pub fn recover(&mut self) -> Result<()> {
+        self.analyze()?;
         self.redo()?;
         self.undo()?;
         self.buffer_pool_manager
@@ -37,114 +61,1136 @@ impl RecoveryManager {
         Ok(())
     }
 
-    pub fn redo(&mut self) -> Result<()> {
-        for log_record in &self.log_records {
+    // finds the transactions running at the crash, where redo starts, the statuses of the other
+    // transactions and the free pages, all from the last checkpoint if there is one
+    pub fn analyze(&mut self) -> Result<()> {
+        let mut start = 0;
+        if let Some(checkpoint_lsn) = self.checkpoint_lsn {
+            let i = self
+                .log_records
+                .iter()
+                .position(|log_record| log_record.lsn == checkpoint_lsn)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("checkpoint record {} not found", checkpoint_lsn.0)
+                })?;
+            let checkpoint = match &self.log_records[i].body {
+                LogRecordBody::Checkpoint(checkpoint) => checkpoint,
+                _ => {
+                    return Err(anyhow::anyhow!(
+                        "log record {} is not a checkpoint",
+                        checkpoint_lsn.0
+                    ))
+                }
+            };
+            self.active_txn_ids = checkpoint
+                .active_transactions
+                .iter()
+                .map(|(txn_id, _)| *txn_id)
+                .collect();
+            self.redo_lsn = checkpoint
+                .dirty_pages
+                .iter()
+                .map(|(_, rec_lsn)| *rec_lsn)
+                .min()
+                .map_or(checkpoint_lsn, |rec_lsn| rec_lsn.min(checkpoint_lsn));
+            for txn_id in 1..checkpoint.next_txn_id.0 {
+                let txn_id = TransactionID(txn_id);
+                if !self.active_txn_ids.contains(&txn_id) {
+                    self.statuses.insert(txn_id, TransactionStatus::Committed);
+                }
+            }
+            for txn_id in checkpoint.aborted_transactions.iter() {
+                self.statuses.insert(*txn_id, TransactionStatus::Aborted);
+            }
+            self.next_txn_id = checkpoint.next_txn_id;
+            self.free_page_ids = checkpoint.free_page_ids.clone();
+            start = i + 1;
+        }
+        for log_record in &self.log_records[start..] {
             match log_record.body {
                 LogRecordBody::BeginTransaction => {
                     self.active_txn_ids.push(log_record.txn_id);
+                    self.next_txn_id = self.next_txn_id.max(TransactionID(log_record.txn_id.0 + 1));
                 }
-                LogRecordBody::CommitTransaction | LogRecordBody::AbortTransaction => {
+                LogRecordBody::CommitTransaction => {
                     self.active_txn_ids.retain(|&x| x != log_record.txn_id);
+                    self.statuses
+                        .insert(log_record.txn_id, TransactionStatus::Committed);
                 }
-                LogRecordBody::InsertToTablePage(ref body) => {
-                    let page = self
-                        .buffer_pool_manager
-                        .lock()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .fetch_page(body.page_id)?;
-                    let page_lsn = page
-                        .read()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .with_table_page(|table_page| table_page.lsn());
-                    if page_lsn < log_record.lsn {
-                        page.write()
-                            .map_err(|_| anyhow::anyhow!("lock error"))?
-                            .with_table_page_mut(|table_page| -> Result<()> {
-                                table_page
-                                    .insert(&body.data)
-                                    .map_err(|e| anyhow::anyhow!("{}", e))?;
-                                table_page.set_lsn(log_record.lsn);
-                                Ok(())
-                            })?;
-                    }
-                    self.buffer_pool_manager
-                        .lock()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .unpin_page(body.page_id, true)?;
+                LogRecordBody::AbortTransaction => {
+                    self.active_txn_ids.retain(|&x| x != log_record.txn_id);
+                    self.statuses
+                        .insert(log_record.txn_id, TransactionStatus::Aborted);
                 }
-                LogRecordBody::DeleteFromTablePage(ref body) => {
-                    let page = self
-                        .buffer_pool_manager
-                        .lock()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .fetch_page(body.rid.0)?;
-                    let page_lsn = page
-                        .read()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .with_table_page(|table_page| table_page.lsn());
-                    if page_lsn < log_record.lsn {
-                        page.write()
-                            .map_err(|_| anyhow::anyhow!("lock error"))?
-                            .with_table_page_mut(|table_page| {
-                                table_page.delete(body.rid.1, log_record.txn_id);
-                                table_page.set_lsn(log_record.lsn);
-                            });
-                    }
-                    self.buffer_pool_manager
-                        .lock()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .unpin_page(body.rid.0, true)?;
+                LogRecordBody::FreePage(ref body) => {
+                    self.free_page_ids.push(body.page_id);
                 }
-                LogRecordBody::NewTablePage(ref body) => {
-                    let mut table_page = TablePage::new(body.page_id);
-                    table_page.set_lsn(log_record.lsn);
-                    let page = Page::Table(table_page);
-                    self.buffer_pool_manager
-                        .lock()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .init_page_for_recovery(body.page_id, page)?;
+                LogRecordBody::ReuseFreePage(ref body) => {
+                    self.free_page_ids.retain(|&x| x != body.page_id);
                 }
-                LogRecordBody::NewBPlusTreeLeafPage(ref body) => {
-                    // TODO:
-                    let mut b_plus_tree_leaf_page =
-                        BPlusTreeLeafPage::new(body.page_id, INVALID_PAGE_ID, None);
-                    b_plus_tree_leaf_page.set_lsn(log_record.lsn);
-                    let page = Page::BPlusTreeLeaf(b_plus_tree_leaf_page);
-                    self.buffer_pool_manager
-                        .lock()
+                _ => {}
+            }
+        }
+        // the transactions running at the crash are rolled back by undo
+        for txn_id in self.active_txn_ids.iter() {
+            self.statuses.insert(*txn_id, TransactionStatus::Aborted);
+        }
+        Ok(())
+    }
+    pub fn statuses(&self) -> HashMap<TransactionID, TransactionStatus> {
+        self.statuses.clone()
+    }
+    pub fn next_txn_id(&self) -> TransactionID {
+        self.next_txn_id
+    }
+    pub fn free_page_ids(&self) -> Vec<PageID> {
+        self.free_page_ids.clone()
+    }
+
+    pub fn redo(&mut self) -> Result<()> {
+        for log_record in &self.log_records {
+            if log_record.lsn < self.redo_lsn {
+                continue;
+            }
+            self.redo_record(log_record.lsn, log_record.txn_id, &log_record.body)?;
+        }
+        Ok(())
+    }
+    fn redo_record(&self, lsn: LSN, txn_id: TransactionID, body: &LogRecordBody) -> Result<()> {
+        match body {
+            LogRecordBody::BeginTransaction
+            | LogRecordBody::CommitTransaction
+            | LogRecordBody::AbortTransaction
+            | LogRecordBody::Checkpoint(_)
+            | LogRecordBody::FreePage(_)
+            | LogRecordBody::ReuseFreePage(_) => {}
+            // the undo done before the crash is repeated like any other change
+            LogRecordBody::Compensation(body) => {
+                self.redo_record(lsn, txn_id, &body.body)?;
+            }
+            LogRecordBody::InsertToTablePage(body) => {
+                let page = self
+                    .buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .fetch_page(body.rid.0)?;
+                let page_lsn = page
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .with_table_page(|table_page| table_page.lsn());
+                if page_lsn < lsn {
+                    page.write()
                         .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .init_page_for_recovery(body.page_id, page)?;
+                        .with_table_page_mut(|table_page| -> Result<()> {
+                            table_page
+                                .insert(&body.data)
+                                .map_err(|e| anyhow::anyhow!("{}", e))?;
+                            table_page.set_lsn(lsn);
+                            Ok(())
+                        })?;
                 }
-                LogRecordBody::SetNextPageID(ref body) => {
-                    let page = self
-                        .buffer_pool_manager
-                        .lock()
-                        .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .fetch_page(body.page_id)?;
-                    let page_lsn = page
-                        .read()
+                self.buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .unpin_page(body.rid.0, true)?;
+            }
+            LogRecordBody::DeleteFromTablePage(body) => {
+                let page = self
+                    .buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .fetch_page(body.rid.0)?;
+                let page_lsn = page
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .with_table_page(|table_page| table_page.lsn());
+                if page_lsn < lsn {
+                    page.write()
                         .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .with_table_page(|table_page| table_page.lsn());
-                    if page_lsn < log_record.lsn {
-                        page.write()
-                            .map_err(|_| anyhow::anyhow!("lock error"))?
-                            .with_table_page_mut(|table_page| {
-                                table_page.set_next_page_id(body.next_page_id);
-                                table_page.set_lsn(log_record.lsn);
-                            });
+                        .with_table_page_mut(|table_page| {
+                            table_page.delete(body.rid.1, txn_id);
+                            table_page.set_lsn(lsn);
+                        });
+                }
+                self.buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .unpin_page(body.rid.0, true)?;
+            }
+            LogRecordBody::UndoDeleteFromTablePage(body) => {
+                self.redo_page(body.rid.0, lsn, |page| {
+                    page.with_table_page_mut(|table_page| {
+                        table_page.set_xmax(body.rid.1, body.xmax)
+                    })
+                })?;
+            }
+            LogRecordBody::PruneTablePage(body) => {
+                self.redo_page(body.page_id, lsn, |page| {
+                    page.with_table_page_mut(|table_page| table_page.prune(&body.indexes))
+                })?;
+            }
+            // the page id may be reused, so an older page is replaced as a whole
+            LogRecordBody::NewTablePage(body) => {
+                let mut table_page = TablePage::new(body.page_id);
+                table_page.set_lsn(lsn);
+                self.redo_page_image(body.page_id, &table_page.data, lsn)?;
+            }
+            LogRecordBody::NewBPlusTreeLeafPage(body) => {
+                let mut b_plus_tree_leaf_page =
+                    BPlusTreeLeafPage::new(body.page_id, INVALID_PAGE_ID, None);
+                b_plus_tree_leaf_page.set_lsn(lsn);
+                self.redo_page_image(body.page_id, &b_plus_tree_leaf_page.data, lsn)?;
+            }
+            LogRecordBody::InsertToBPlusTreeLeafPage(body) => {
+                self.redo_page(body.page_id, lsn, |page| {
+                    page.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                        leaf_page.insert_entry_at(body.index as usize, &body.entry)
+                    })
+                })?;
+            }
+            LogRecordBody::DeleteFromBPlusTreeLeafPage(body) => {
+                self.redo_page(body.page_id, lsn, |page| {
+                    page.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                        leaf_page.remove_entry_at(body.index as usize);
+                    })
+                })?;
+            }
+            LogRecordBody::InsertToBPlusTreeInternalPage(body) => {
+                self.redo_page(body.page_id, lsn, |page| {
+                    page.with_b_plus_tree_internal_page_mut(|internal_page| {
+                        internal_page.insert_entry_at(body.index as usize, &body.entry)
+                    })
+                })?;
+            }
+            LogRecordBody::SplitBPlusTreePage(body) => {
+                self.redo_page_image(body.page_id, &body.data, lsn)?;
+                self.redo_page_image(body.new_page_id, &body.new_data, lsn)?;
+            }
+            LogRecordBody::NewBPlusTreeRootPage(body) => {
+                self.redo_page_image(body.page_id, &body.data, lsn)?;
+                self.redo_page_image(body.left_page_id, &body.left_data, lsn)?;
+            }
+            LogRecordBody::SetBPlusTreeParentPageID(body) => {
+                self.redo_page(body.page_id, lsn, |page| match page {
+                    Page::BPlusTreeLeaf(leaf_page) => {
+                        leaf_page.set_parent_page_id(body.parent_page_id)
+                    }
+                    Page::BPlusTreeInternal(internal_page) => {
+                        internal_page.set_parent_page_id(body.parent_page_id)
                     }
-                    self.buffer_pool_manager
-                        .lock()
+                    _ => {}
+                })?;
+            }
+            LogRecordBody::SetBPlusTreePrevPageID(body) => {
+                self.redo_page(body.page_id, lsn, |page| {
+                    page.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                        leaf_page.set_prev_page_id(body.prev_page_id)
+                    })
+                })?;
+            }
+            LogRecordBody::LoadBPlusTreePage(body) => {
+                self.redo_page_image(body.page_id, &body.data, lsn)?;
+            }
+            LogRecordBody::SetNextPageID(body) => {
+                let page = self
+                    .buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .fetch_page(body.page_id)?;
+                let page_lsn = page
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .with_table_page(|table_page| table_page.lsn());
+                if page_lsn < lsn {
+                    page.write()
                         .map_err(|_| anyhow::anyhow!("lock error"))?
-                        .unpin_page(body.page_id, true)?;
+                        .with_table_page_mut(|table_page| {
+                            table_page.set_next_page_id(body.next_page_id);
+                            table_page.set_lsn(lsn);
+                        });
                 }
+                self.buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .unpin_page(body.page_id, true)?;
             }
         }
         Ok(())
     }
 
+    // the losers are rolled back one by one, the rows they changed are locked by none of the others
     pub fn undo(&self) -> Result<()> {
-        // TODO:
+        let undo_manager =
+            UndoManager::new(self.buffer_pool_manager.clone(), self.log_manager.clone());
+        for txn_id in self.active_txn_ids.iter() {
+            undo_manager.rollback(*txn_id, INVALID_LSN)?;
+            // the loser is not undone again after another crash
+            self.log_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .append(*txn_id, LogRecordBody::AbortTransaction)?;
+        }
+        self.log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .flush()
+    }
+
+    fn redo_page<F>(&self, page_id: PageID, lsn: LSN, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Page),
+    {
+        let page = self.fetch_page(page_id)?;
+        let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+        if page_guard.lsn() < lsn {
+            f(&mut page_guard);
+            page_guard.set_lsn(lsn);
+        }
+        drop(page_guard);
+        self.unpin_page(page_id, true)
+    }
+    // the page may never have been written, so the image is installed before it is fetched
+    fn redo_page_image(&self, page_id: PageID, data: &[u8], lsn: LSN) -> Result<()> {
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .init_page_for_recovery(page_id, Page::from_data(data))?;
+        self.redo_page(page_id, lsn, |page| *page = Page::from_data(data))
+    }
+    fn fetch_page(&self, page_id: PageID) -> Result<Arc<RwLock<Page>>> {
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .fetch_page(page_id)
+    }
+    fn unpin_page(&self, page_id: PageID, is_dirty: bool) -> Result<()> {
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(page_id, is_dirty)
+    }
+}
+
+// rolls back the changes of a transaction by following its log records backwards.
+// each undo is logged as a compensation, which is skipped when the rollback is resumed
+pub struct UndoManager {
+    buffer_pool_manager: Arc<Mutex<BufferPoolManager>>,
+    log_manager: Arc<Mutex<LogManager>>,
+}
+
+impl UndoManager {
+    pub fn new(
+        buffer_pool_manager: Arc<Mutex<BufferPoolManager>>,
+        log_manager: Arc<Mutex<LogManager>>,
+    ) -> Self {
+        Self {
+            buffer_pool_manager,
+            log_manager,
+        }
+    }
+
+    // undoes the records after the lsn, an invalid lsn undoes all of them
+    pub fn rollback(&self, txn_id: TransactionID, lsn: LSN) -> Result<()> {
+        let mut undo_next_lsn = self
+            .log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .last_lsn(txn_id);
+        while undo_next_lsn > lsn {
+            let log_record = self
+                .log_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .read_record(undo_next_lsn)?;
+            undo_next_lsn = match log_record.body {
+                LogRecordBody::Compensation(ref compensation) => compensation.undo_next_lsn,
+                _ => {
+                    self.undo(&log_record)?;
+                    log_record.prev_lsn
+                }
+            };
+        }
+        Ok(())
+    }
+
+    fn undo(&self, log_record: &LogRecord) -> Result<()> {
+        match log_record.body {
+            // an inserted tuple is deleted by the transaction itself, so that no one can see it
+            LogRecordBody::InsertToTablePage(ref body) => self.undo_table_page(
+                log_record,
+                body.rid,
+                log_record.txn_id,
+                LogRecordBody::DeleteFromTablePage(DeleteFromTablePage {
+                    rid: body.rid,
+                    prev_xmax: INVALID_TRANSACTION_ID,
+                }),
+            ),
+            LogRecordBody::DeleteFromTablePage(ref body) => self.undo_table_page(
+                log_record,
+                body.rid,
+                body.prev_xmax,
+                LogRecordBody::UndoDeleteFromTablePage(UndoDeleteFromTablePage {
+                    rid: body.rid,
+                    xmax: body.prev_xmax,
+                }),
+            ),
+            LogRecordBody::InsertToBPlusTreeLeafPage(ref body) => {
+                self.undo_leaf_insert(log_record, body)
+            }
+            LogRecordBody::DeleteFromBPlusTreeLeafPage(ref body) => {
+                let page = self.fetch_page(body.page_id)?;
+                let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+                let index = page_guard.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                    if leaf_page.entry_index(&body.entry).is_some() {
+                        return None;
+                    }
+                    let index = (body.index as usize).min(leaf_page.num_line_pointers() as usize);
+                    leaf_page.insert_entry_at(index, &body.entry);
+                    Some(index)
+                });
+                if let Some(index) = index {
+                    self.log_compensation(
+                        &mut page_guard,
+                        log_record,
+                        LogRecordBody::InsertToBPlusTreeLeafPage(BPlusTreePageEntry {
+                            page_id: body.page_id,
+                            index: index as u32,
+                            entry: body.entry.clone(),
+                        }),
+                    )?;
+                }
+                drop(page_guard);
+                self.unpin_page(body.page_id, index.is_some())
+            }
+            // structure modifications are kept, the tree is consistent without them being undone
+            _ => Ok(()),
+        }
+    }
+    fn undo_table_page(
+        &self,
+        log_record: &LogRecord,
+        rid: RID,
+        xmax: TransactionID,
+        body: LogRecordBody,
+    ) -> Result<()> {
+        let page = self.fetch_page(rid.0)?;
+        let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+        page_guard.with_table_page_mut(|table_page| table_page.set_xmax(rid.1, xmax));
+        self.log_compensation(&mut page_guard, log_record, body)?;
+        drop(page_guard);
+        self.unpin_page(rid.0, true)
+    }
+    // the entry may have been moved to a right sibling by a later split,
+    // or to the leftmost leaf if the page was the root
+    fn undo_leaf_insert(&self, log_record: &LogRecord, body: &BPlusTreePageEntry) -> Result<()> {
+        let mut page_id = body.page_id;
+        while page_id != INVALID_PAGE_ID {
+            let page = self.fetch_page(page_id)?;
+            let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+            if let Page::BPlusTreeInternal(internal_page) = &*page_guard {
+                let child_page_id = internal_page.value_at(0);
+                drop(page_guard);
+                self.unpin_page(page_id, false)?;
+                page_id = child_page_id;
+                continue;
+            }
+            let (index, next_page_id) = page_guard.with_b_plus_tree_leaf_page_mut(|leaf_page| {
+                let index = leaf_page.entry_index(&body.entry);
+                if let Some(index) = index {
+                    leaf_page.remove_entry_at(index);
+                }
+                (index, leaf_page.next_page_id())
+            });
+            if let Some(index) = index {
+                self.log_compensation(
+                    &mut page_guard,
+                    log_record,
+                    LogRecordBody::DeleteFromBPlusTreeLeafPage(BPlusTreePageEntry {
+                        page_id,
+                        index: index as u32,
+                        entry: body.entry.clone(),
+                    }),
+                )?;
+                drop(page_guard);
+                return self.unpin_page(page_id, true);
+            }
+            drop(page_guard);
+            self.unpin_page(page_id, false)?;
+            page_id = next_page_id;
+        }
+        Ok(())
+    }
+    // logged while the page is latched, so that its lsn follows the order of the log
+    fn log_compensation(
+        &self,
+        page: &mut Page,
+        log_record: &LogRecord,
+        body: LogRecordBody,
+    ) -> Result<()> {
+        let lsn = self
+            .log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .append(
+                log_record.txn_id,
+                LogRecordBody::Compensation(Compensation {
+                    undo_next_lsn: log_record.prev_lsn,
+                    body: Box::new(body),
+                }),
+            )?;
+        page.set_lsn(lsn);
+        Ok(())
+    }
+    fn fetch_page(&self, page_id: PageID) -> Result<Arc<RwLock<Page>>> {
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .fetch_page(page_id)
+    }
+    fn unpin_page(&self, page_id: PageID, is_dirty: bool) -> Result<()> {
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(page_id, is_dirty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ops::Bound, thread, time::Duration};
+
+    use tempfile::tempdir;
+
+    use crate::{
+        control::ControlFile,
+        disk::DiskManager,
+        index::{IndexManager, IndexRange},
+        instance::Instance,
+        lexer::tokenize,
+        log::LogManager,
+        parser::{Parser, StatementAST},
+        value::{integer::IntegerValue, varchar::VarcharValue, Value},
+    };
+
+    use super::*;
+
+    fn execute(sql: &str, instance: &Instance, txn_id: TransactionID) -> Result<Vec<Vec<Value>>> {
+        let mut parser = Parser::new(tokenize(&mut sql.chars().peekable())?);
+        match parser.parse()? {
+            StatementAST::CreateTable(statement) => {
+                instance.create_table(&statement, txn_id)?;
+                Ok(vec![])
+            }
+            StatementAST::CreateIndex(statement) => {
+                instance.create_index(&statement, txn_id)?;
+                Ok(vec![])
+            }
+            StatementAST::DropIndex(statement) => {
+                instance.drop_index(&statement, txn_id)?;
+                Ok(vec![])
+            }
+            statement => Ok(instance.execute(&statement, txn_id)?.0),
+        }
+    }
+
+    fn index_keys(instance: &Instance, txn_id: TransactionID) -> Result<Vec<Vec<Value>>> {
+        let catalog = instance
+            .catalog
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?;
+        let mut index = catalog.get_indexes_by_table_name("t", txn_id)?.remove(0);
+        index.set_schema(catalog.get_schema_by_table_name("t", txn_id)?);
+        drop(catalog);
+        let index_manager = IndexManager::new(
+            index,
+            instance.buffer_pool_manager.clone(),
+            instance.log_manager.clone(),
+            txn_id,
+        );
+        index_manager
+            .scan(&IndexRange {
+                start: Bound::Unbounded,
+                end: Bound::Unbounded,
+            })?
+            .map(|entry| entry.map(|(key, _)| key))
+            .collect()
+    }
+
+    #[test]
+    fn test_recover_index() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, c1 VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+        // enough rows to split leaf and internal pages
+        for i in 0..20 {
+            let txn_id = instance.begin(None)?;
+            for j in 0..100 {
+                let sql = format!(
+                    "INSERT INTO t VALUES ({}, 'value of a padded row {}')",
+                    i * 100 + j,
+                    j
+                );
+                execute(&sql, &instance, txn_id)?;
+            }
+            instance.commit(txn_id)?;
+        }
+        let loser_txn_id = instance.begin(None)?;
+        for i in 2000..2300 {
+            let sql = format!("INSERT INTO t VALUES ({}, 'loser')", i);
+            execute(&sql, &instance, loser_txn_id)?;
+        }
+        // flushes the log records of the loser
+        let txn_id = instance.begin(None)?;
+        instance.commit(txn_id)?;
+        drop(instance);
+
+        let instance = Instance::new(dir, false, true)?;
+        let txn_id = instance.begin(None)?;
+        let keys = index_keys(&instance, txn_id)?;
+        assert_eq!(
+            keys,
+            (0..2000)
+                .map(|i| vec![Value::Integer(IntegerValue(i))])
+                .collect::<Vec<_>>()
+        );
+        let rows = execute("SELECT * FROM t WHERE id = 1234", &instance, txn_id)?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][0], Value::Integer(IntegerValue(1234)));
+        let rows = execute("SELECT * FROM t WHERE id >= 1990", &instance, txn_id)?;
+        assert_eq!(rows.len(), 10);
+        execute(
+            "INSERT INTO t VALUES (2000, 'after recovery')",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_index_build() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE t (id INTEGER, c1 VARCHAR)", &instance, txn_id)?;
+        for i in 0..2000 {
+            let sql = format!(
+                "INSERT INTO t VALUES ({}, 'value of a padded row {}')",
+                i, i
+            );
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        // built with leaf and internal pages below the root
+        let txn_id = instance.begin(None)?;
+        execute("CREATE INDEX t_id_index ON t (id)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        drop(instance);
+
+        let instance = Instance::new(dir, false, true)?;
+        let txn_id = instance.begin(None)?;
+        let keys = index_keys(&instance, txn_id)?;
+        assert_eq!(
+            keys,
+            (0..2000)
+                .map(|i| vec![Value::Integer(IntegerValue(i))])
+                .collect::<Vec<_>>()
+        );
+        execute(
+            "INSERT INTO t VALUES (2000, 'after recovery')",
+            &instance,
+            txn_id,
+        )?;
+        let rows = execute("SELECT * FROM t WHERE id >= 1999", &instance, txn_id)?;
+        assert_eq!(rows.len(), 2);
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_after_unclean_shutdown() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE t (id INTEGER, c1 VARCHAR)", &instance, txn_id)?;
+        execute("INSERT INTO t VALUES (1, 'foo')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let loser_txn_id = instance.begin(None)?;
+        execute("INSERT INTO t VALUES (2, 'loser')", &instance, loser_txn_id)?;
+        let txn_id = instance.begin(None)?;
+        instance.commit(txn_id)?;
+        drop(instance);
+        let control_file_path = format!("{}/control", dir);
+        assert!(!ControlFile::load(&control_file_path)?.clean_shutdown);
+
+        // the table exists only in the log, so it is found only if the start recovers
+        let instance = Instance::new(dir, false, false)?;
+        let txn_id = instance.begin(None)?;
+        let rows = execute("SELECT * FROM t", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Integer(IntegerValue(1)),
+                Value::Varchar(VarcharValue("foo".to_string())),
+            ]]
+        );
+        execute("INSERT INTO t VALUES (3, 'bar')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        // a running transaction leaves the shutdown unclean
+        let txn_id = instance.begin(None)?;
+        instance.shutdown()?;
+        assert!(!ControlFile::load(&control_file_path)?.clean_shutdown);
+        instance.rollback(txn_id)?;
+        instance.shutdown()?;
+        assert!(ControlFile::load(&control_file_path)?.clean_shutdown);
+        drop(instance);
+
+        let instance = Instance::new(dir, false, false)?;
+        assert!(!ControlFile::load(&control_file_path)?.clean_shutdown);
+        let txn_id = instance.begin(None)?;
+        let rows = execute("SELECT * FROM t", &instance, txn_id)?;
+        assert_eq!(rows.len(), 2);
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_transaction_statuses() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let committed_txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE t (id INTEGER, c1 VARCHAR)",
+            &instance,
+            committed_txn_id,
+        )?;
+        execute(
+            "INSERT INTO t VALUES (1, 'foo')",
+            &instance,
+            committed_txn_id,
+        )?;
+        instance.commit(committed_txn_id)?;
+        let aborted_txn_id = instance.begin(None)?;
+        execute("INSERT INTO t VALUES (2, 'bar')", &instance, aborted_txn_id)?;
+        instance.rollback(aborted_txn_id)?;
+        // the log is truncated to the checkpoint, which keeps the statuses
+        instance.shutdown()?;
+        drop(instance);
+
+        let instance = Instance::new(dir, false, false)?;
+        let loser_txn_id = instance.begin(None)?;
+        assert!(loser_txn_id > aborted_txn_id);
+        execute("INSERT INTO t VALUES (3, 'loser')", &instance, loser_txn_id)?;
+        let txn_id = instance.begin(None)?;
+        instance.commit(txn_id)?;
+        drop(instance);
+
+        // the loser is aborted by recovery, and stays aborted through the next checkpoint
+        for _ in 0..2 {
+            let instance = Instance::new(dir, false, false)?;
+            let transaction_manager = instance
+                .transaction_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+            assert_eq!(
+                transaction_manager.status(committed_txn_id),
+                TransactionStatus::Committed
+            );
+            assert_eq!(
+                transaction_manager.status(aborted_txn_id),
+                TransactionStatus::Aborted
+            );
+            assert_eq!(
+                transaction_manager.status(loser_txn_id),
+                TransactionStatus::Aborted
+            );
+            assert_eq!(
+                transaction_manager.status(txn_id),
+                TransactionStatus::Committed
+            );
+            assert!(transaction_manager.next_txn_id() > txn_id);
+            drop(transaction_manager);
+            instance.shutdown()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_async_commit() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE t (id INTEGER, c1 VARCHAR)", &instance, txn_id)?;
+        execute("INSERT INTO t VALUES (1, 'sync')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        // the crash comes within the window, so the commit is lost
+        let txn_id = instance.begin(None)?;
+        execute("INSERT INTO t VALUES (2, 'lost')", &instance, txn_id)?;
+        instance.commit_with_durability_window(txn_id, Some(Duration::from_secs(3600)))?;
+        let txn_id = instance.begin(None)?;
+        assert_eq!(execute("SELECT * FROM t", &instance, txn_id)?.len(), 2);
+        drop(instance);
+
+        let instance = Instance::new(dir, false, false)?;
+        let txn_id = instance.begin(None)?;
+        assert_eq!(execute("SELECT * FROM t", &instance, txn_id)?.len(), 1);
+        execute("INSERT INTO t VALUES (3, 'async')", &instance, txn_id)?;
+        instance.commit_with_durability_window(txn_id, Some(Duration::from_millis(10)))?;
+        thread::sleep(Duration::from_millis(200));
+        drop(instance);
+
+        let instance = Instance::new(dir, false, false)?;
+        let txn_id = instance.begin(None)?;
+        let rows = execute("SELECT * FROM t", &instance, txn_id)?;
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1][0], Value::Integer(IntegerValue(3)));
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_rollback_to_savepoint() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE t (id INTEGER, c1 VARCHAR)", &instance, txn_id)?;
+        execute("INSERT INTO t VALUES (1, 'foo')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        instance.savepoint(txn_id, "sp1")?;
+        execute("DELETE FROM t", &instance, txn_id)?;
+        execute("INSERT INTO t VALUES (2, 'bar')", &instance, txn_id)?;
+        instance.rollback_to_savepoint(txn_id, "sp1")?;
+        execute("INSERT INTO t VALUES (3, 'baz')", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        drop(instance);
+
+        // the compensations are redone like any other change
+        let instance = Instance::new(dir, false, true)?;
+        let txn_id = instance.begin(None)?;
+        let rows = execute("SELECT id FROM t ORDER BY id ASC", &instance, txn_id)?;
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Integer(IntegerValue(1))],
+                vec![Value::Integer(IntegerValue(3))]
+            ]
+        );
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_vacuum() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, c1 VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        for i in 0..300 {
+            let sql = format!("INSERT INTO t VALUES ({}, 'foo')", i);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        execute("UPDATE t SET c1 = 'bar' WHERE id < 200", &instance, txn_id)?;
+        execute("DELETE FROM t WHERE id >= 250", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        // running at the crash
+        let loser_txn_id = instance.begin(None)?;
+        execute(
+            "INSERT INTO t VALUES (300, 'loser')",
+            &instance,
+            loser_txn_id,
+        )?;
+        assert_eq!(instance.vacuum(Some("t"))?, 250);
+        drop(instance);
+
+        let instance = Instance::new(dir, false, true)?;
+        let txn_id = instance.begin(None)?;
+        let keys = index_keys(&instance, txn_id)?;
+        assert_eq!(
+            keys,
+            (0..250)
+                .map(|i| vec![Value::Integer(IntegerValue(i))])
+                .collect::<Vec<_>>()
+        );
+        let rows = execute("SELECT COUNT(*) FROM t", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(250))]]);
+        let rows = execute("SELECT * FROM t WHERE id = 42", &instance, txn_id)?;
+        assert_eq!(rows[0][1], Value::Varchar(VarcharValue("bar".to_string())));
+        instance.commit(txn_id)?;
+        // only the tuple of the loser is left to remove
+        assert_eq!(instance.vacuum(Some("t"))?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_from_checkpoint() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, c1 VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        instance.commit(txn_id)?;
+        let insert = |range: std::ops::Range<i64>, txn_id: TransactionID| -> Result<()> {
+            for i in range {
+                let sql = format!("INSERT INTO t VALUES ({}, 'value of a padded row')", i);
+                execute(&sql, &instance, txn_id)?;
+            }
+            Ok(())
+        };
+        for i in 0..5 {
+            let txn_id = instance.begin(None)?;
+            insert(i * 100..(i + 1) * 100, txn_id)?;
+            instance.commit(txn_id)?;
+        }
+        instance.checkpoint()?;
+        // running through the checkpoint and at the crash
+        let loser_txn_id = instance.begin(None)?;
+        insert(1000..1100, loser_txn_id)?;
+        instance.checkpoint()?;
+        let wal_log_file = format!("{}/wal.log", dir);
+        let log_records = LogManager::new(&wal_log_file)?.read()?;
+        assert!(log_records[0].lsn > LSN(1));
+        assert!(log_records
+            .iter()
+            .any(|log_record| log_record.txn_id == loser_txn_id
+                && log_record.body == LogRecordBody::BeginTransaction));
+
+        let txn_id = instance.begin(None)?;
+        insert(500..700, txn_id)?;
+        instance.commit(txn_id)?;
+        insert(1100..1200, loser_txn_id)?;
+        // flushes the log records of the loser
+        let txn_id = instance.begin(None)?;
+        instance.commit(txn_id)?;
+        drop(instance);
+
+        let instance = Instance::new(dir, false, true)?;
+        let txn_id = instance.begin(None)?;
+        let keys = index_keys(&instance, txn_id)?;
+        assert_eq!(
+            keys,
+            (0..700)
+                .map(|i| vec![Value::Integer(IntegerValue(i))])
+                .collect::<Vec<_>>()
+        );
+        let rows = execute("SELECT COUNT(*) FROM t", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(700))]]);
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_free_pages() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE t (id INTEGER, c1 VARCHAR)", &instance, txn_id)?;
+        for i in 0..500 {
+            let sql = format!(
+                "INSERT INTO t VALUES ({}, 'value of a padded row {}')",
+                i, i
+            );
+            execute(&sql, &instance, txn_id)?;
+        }
+        execute("CREATE INDEX t_c1_index1 ON t (c1)", &instance, txn_id)?;
+        execute("CREATE INDEX t_c1_index2 ON t (c1)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let free_page_ids = |instance: &Instance| -> Result<Vec<PageID>> {
+            let mut free_page_ids = instance
+                .buffer_pool_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .free_page_ids();
+            free_page_ids.sort();
+            Ok(free_page_ids)
+        };
+
+        // freed before and after the checkpoint, and reused after it
+        let txn_id = instance.begin(None)?;
+        execute("DROP INDEX t_c1_index1", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        instance.checkpoint()?;
+        let txn_id = instance.begin(None)?;
+        execute("DROP INDEX t_c1_index2", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        execute("CREATE TABLE u (id INTEGER)", &instance, txn_id)?;
+        instance.commit(txn_id)?;
+        let expected = free_page_ids(&instance)?;
+        assert!(expected.len() > 2);
+        drop(instance);
+
+        let instance = Instance::new(dir, false, false)?;
+        assert_eq!(free_page_ids(&instance)?, expected);
+        instance.shutdown()?;
+        drop(instance);
+        let instance = Instance::new(dir, false, false)?;
+        assert_eq!(free_page_ids(&instance)?, expected);
+
+        // the pages in use are not handed out again
+        let txn_id = instance.begin(None)?;
+        execute("CREATE INDEX t_c1_index3 ON t (c1)", &instance, txn_id)?;
+        execute("INSERT INTO u VALUES (1)", &instance, txn_id)?;
+        let rows = execute("SELECT COUNT(*) FROM t", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(500))]]);
+        let rows = execute("SELECT * FROM u", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(1))]]);
+        instance.commit(txn_id)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_recover_crash_during_undo() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let dir = temp_dir.path().join("test");
+        let dir = dir.to_str().unwrap();
+        let instance = Instance::new(dir, true, false)?;
+        let txn_id = instance.begin(None)?;
+        execute(
+            "CREATE TABLE t (id INTEGER PRIMARY KEY, c1 VARCHAR)",
+            &instance,
+            txn_id,
+        )?;
+        for i in 0..300 {
+            let sql = format!("INSERT INTO t VALUES ({}, 'foo')", i);
+            execute(&sql, &instance, txn_id)?;
+        }
+        instance.commit(txn_id)?;
+        let loser_txn_id = instance.begin(None)?;
+        for i in 1000..1200 {
+            let sql = format!("INSERT INTO t VALUES ({}, 'loser')", i);
+            execute(&sql, &instance, loser_txn_id)?;
+        }
+        execute(
+            "UPDATE t SET c1 = 'loser' WHERE id < 100",
+            &instance,
+            loser_txn_id,
+        )?;
+        execute(
+            "DELETE FROM t WHERE id >= 250 AND id < 1000",
+            &instance,
+            loser_txn_id,
+        )?;
+        // flushes the log records of the loser
+        let txn_id = instance.begin(None)?;
+        instance.commit(txn_id)?;
+        drop(instance);
+
+        // the first restart crashes halfway through the undo of the loser
+        let wal_log_file = format!("{}/wal.log", dir);
+        let log_manager = Arc::new(Mutex::new(LogManager::new(&wal_log_file)?));
+        let buffer_pool_manager = Arc::new(Mutex::new(BufferPoolManager::new(
+            DiskManager::new(&format!("{}/data.db", dir))?,
+            log_manager.clone(),
+            32,
+        )));
+        let log_records = log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .read()?;
+        let loser_lsns = log_records
+            .iter()
+            .filter(|log_record| log_record.txn_id == loser_txn_id)
+            .map(|log_record| log_record.lsn)
+            .collect::<Vec<_>>();
+        let mut recovery_manager = RecoveryManager::new(
+            buffer_pool_manager.clone(),
+            log_manager.clone(),
+            log_records,
+            ControlFile::load(&format!("{}/control", dir))?.checkpoint_lsn,
+        );
+        recovery_manager.analyze()?;
+        recovery_manager.redo()?;
+        UndoManager::new(buffer_pool_manager, log_manager.clone())
+            .rollback(loser_txn_id, loser_lsns[loser_lsns.len() / 2])?;
+        log_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .flush()?;
+        drop(recovery_manager);
+
+        let instance = Instance::new(dir, false, true)?;
+        let txn_id = instance.begin(None)?;
+        let keys = index_keys(&instance, txn_id)?;
+        assert_eq!(
+            keys,
+            (0..300)
+                .map(|i| vec![Value::Integer(IntegerValue(i))])
+                .collect::<Vec<_>>()
+        );
+        let rows = execute("SELECT COUNT(*) FROM t", &instance, txn_id)?;
+        assert_eq!(rows, vec![vec![Value::Integer(IntegerValue(300))]]);
+        let rows = execute("SELECT c1 FROM t WHERE id = 42", &instance, txn_id)?;
+        assert_eq!(rows[0][0], Value::Varchar(VarcharValue("foo".to_string())));
+        instance.commit(txn_id)?;
+
+        // each change of the loser is compensated once, and the loser is ended
+        let log_records = LogManager::new(&wal_log_file)?.read()?;
+        let loser_records = log_records
+            .iter()
+            .filter(|log_record| log_record.txn_id == loser_txn_id)
+            .collect::<Vec<_>>();
+        let changes = loser_records
+            .iter()
+            .filter(|log_record| {
+                matches!(
+                    log_record.body,
+                    LogRecordBody::InsertToTablePage(_)
+                        | LogRecordBody::DeleteFromTablePage(_)
+                        | LogRecordBody::InsertToBPlusTreeLeafPage(_)
+                )
+            })
+            .count();
+        let compensations = loser_records
+            .iter()
+            .filter(|log_record| matches!(log_record.body, LogRecordBody::Compensation(_)))
+            .count();
+        assert_eq!(compensations, changes);
+        assert_eq!(
+            loser_records.last().map(|log_record| &log_record.body),
+            Some(&LogRecordBody::AbortTransaction)
+        );
+
+        // a rollback undoes the index entries as well
+        let txn_id = instance.begin(None)?;
+        execute("INSERT INTO t VALUES (300, 'bar')", &instance, txn_id)?;
+        instance.rollback(txn_id)?;
+        let txn_id = instance.begin(None)?;
+        assert_eq!(index_keys(&instance, txn_id)?.len(), 300);
+        instance.commit(txn_id)?;
         Ok(())
     }
 }
@@ -3,6 +3,7 @@ use std::{
     net::{TcpListener, TcpStream},
     sync::{Arc, RwLock},
     thread,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Result};
@@ -10,25 +11,37 @@ use prettytable::{Cell, Row, Table};
 use signal_hook::{consts::TERM_SIGNALS, iterator::Signals};
 
 use crate::{
-    common::TransactionID,
+    autovacuum::{autovacuum_start, AutovacuumConfig},
+    catalog::Schema,
+    common::{TransactionID, LSN},
+    concurrency::{IsolationLevel, TransactionRollbackError},
     instance::Instance,
     lexer::tokenize,
-    parser::{Parser, StatementAST},
+    parser::{Parser, SetStatementAST, StatementAST},
+    value::Value,
 };
 
 const SERVER_DEFAULT_PORT: u16 = 7878;
 
-pub fn server_start(init: bool, recover: bool) -> Result<()> {
+pub fn server_start(
+    init: bool,
+    recover: bool,
+    autovacuum_config: AutovacuumConfig,
+    checkpoint_interval: Duration,
+) -> Result<()> {
     println!("junkdb server started");
 
     // init
+    // sessions run concurrently, a statement waiting for a row lock must not block the commit of its holder
     let instance = Arc::new(RwLock::new(Instance::new("data", init, recover)?));
+    autovacuum_start(instance.clone(), autovacuum_config);
+    checkpointer_start(instance.clone(), checkpoint_interval);
 
     // trap signals
     let instance_clone = instance.clone();
     let mut signals = Signals::new(TERM_SIGNALS)?;
     thread::spawn(move || {
-        signals.forever().for_each(|_| {
+        if signals.forever().next().is_some() {
             println!("junkdb server shutdown...");
             if let Ok(instance) = instance_clone.read() {
                 if let Err(e) = instance.shutdown() {
@@ -40,7 +53,7 @@ pub fn server_start(init: bool, recover: bool) -> Result<()> {
                 std::process::exit(1);
             }
             std::process::exit(0);
-        });
+        }
     });
 
     // listen
@@ -57,10 +70,31 @@ pub fn server_start(init: bool, recover: bool) -> Result<()> {
     Ok(())
 }
 
+fn checkpointer_start(instance: Arc<RwLock<Instance>>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        let result = match instance.read() {
+            Ok(instance) => instance.checkpoint(),
+            Err(_) => Err(anyhow!("lock error")),
+        };
+        if let Err(e) = result {
+            println!("checkpoint error: {}", e);
+        }
+    });
+}
+
 struct Session {
     stream: TcpStream,
     instance: Arc<RwLock<Instance>>,
     current_txn_id: Option<TransactionID>,
+    // SET TRANSACTION is allowed only before the first query of the transaction
+    current_txn_queried: bool,
+    lock_timeout: Option<Duration>,
+    // commits return before their records are on disk, which happens within this window
+    async_commit_window: Option<Duration>,
+    default_isolation_level: IsolationLevel,
+    // a failed statement in a transaction block is undone to here, the transaction goes on
+    statement_undo_position: Option<LSN>,
 }
 impl Session {
     pub fn new(stream: TcpStream, instance: Arc<RwLock<Instance>>) -> Self {
@@ -68,6 +102,11 @@ impl Session {
             stream,
             instance,
             current_txn_id: None,
+            current_txn_queried: false,
+            lock_timeout: None,
+            async_commit_window: None,
+            default_isolation_level: IsolationLevel::default(),
+            statement_undo_position: None,
         }
     }
     fn start(&mut self) -> Result<()> {
@@ -79,7 +118,7 @@ impl Session {
                 }
                 Err(e) => {
                     self.write(&format!("error: {}", e))?;
-                    self.rollback()?;
+                    self.rollback_statement(&e)?;
                 }
             }
         }
@@ -119,29 +158,163 @@ impl Session {
     fn rollback(&mut self) -> Result<()> {
         if let Some(txn_id) = self.current_txn_id {
             self.instance
-                .write()
+                .read()
                 .map_err(|_| anyhow!("lock error"))?
                 .rollback(txn_id)?;
             self.current_txn_id = None;
         }
         Ok(())
     }
+    // deadlocks and serialization failures cannot be resolved inside the transaction
+    fn rollback_statement(&mut self, e: &anyhow::Error) -> Result<()> {
+        if let (Some(txn_id), Some(undo_position)) =
+            (self.current_txn_id, self.statement_undo_position)
+        {
+            if e.downcast_ref::<TransactionRollbackError>().is_none()
+                && self
+                    .instance
+                    .read()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .rollback_to(txn_id, undo_position)
+                    .is_ok()
+            {
+                return Ok(());
+            }
+        }
+        self.rollback()
+    }
+    fn set(&mut self, statement: &SetStatementAST) -> Result<()> {
+        match statement.name.as_str() {
+            // in milliseconds, 0 waits forever
+            "lock_timeout" => {
+                let milliseconds = match &statement.value {
+                    Value::Integer(value) if value.0 >= 0 => value.0 as u64,
+                    _ => return Err(anyhow!("lock_timeout must be a non-negative integer")),
+                };
+                self.lock_timeout = (milliseconds > 0).then(|| Duration::from_millis(milliseconds));
+            }
+            // in milliseconds, 0 commits synchronously
+            "async_commit_window" => {
+                let milliseconds = match &statement.value {
+                    Value::Integer(value) if value.0 >= 0 => value.0 as u64,
+                    _ => {
+                        return Err(anyhow!(
+                            "async_commit_window must be a non-negative integer"
+                        ))
+                    }
+                };
+                self.async_commit_window =
+                    (milliseconds > 0).then(|| Duration::from_millis(milliseconds));
+            }
+            // for the transactions which begin without an isolation level
+            "default_transaction_isolation" => {
+                self.default_isolation_level = match &statement.value {
+                    Value::Varchar(value) => IsolationLevel::try_from(value.0.as_str())?,
+                    _ => return Err(anyhow!("default_transaction_isolation must be a string")),
+                };
+            }
+            name => return Err(anyhow!("unrecognized configuration parameter {}", name)),
+        }
+        Ok(())
+    }
     fn execute(&mut self, query: &str) -> Result<String> {
+        // taken before parsing, so that any failed statement in a transaction block, even one
+        // which does not parse, is undone only to its own start
+        self.statement_undo_position = None;
+        if let Some(txn_id) = self.current_txn_id {
+            self.statement_undo_position = Some(
+                self.instance
+                    .read()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .undo_position(txn_id)?,
+            );
+        }
+
         // parse
         let mut iter = query.chars().peekable();
         let tokens = tokenize(&mut iter)?;
         let mut parser = Parser::new(tokens);
         let statement = parser.parse()?;
 
+        // a failed commit or rollback ends the transaction
+        if matches!(statement, StatementAST::Commit | StatementAST::Rollback) {
+            self.statement_undo_position = None;
+        }
         let response = match statement {
-            StatementAST::Begin => {
-                let txn_id = self
+            StatementAST::Begin(ast) => {
+                // a transaction in progress goes on with its isolation level
+                if self.current_txn_id.is_none() {
+                    let txn_id = self
+                        .instance
+                        .read()
+                        .map_err(|_| anyhow!("lock error"))?
+                        .begin_with_isolation_level(
+                            ast.isolation_level.unwrap_or(self.default_isolation_level),
+                        )?;
+                    self.current_txn_id = Some(txn_id);
+                    self.current_txn_queried = false;
+                }
+                "transaction started.".to_string()
+            }
+            StatementAST::Set(ast) => {
+                self.set(&ast)?;
+                format!("{} set", ast.name)
+            }
+            StatementAST::SetTransaction(ast) => {
+                let txn_id = match self.current_txn_id {
+                    Some(txn_id) => txn_id,
+                    None => {
+                        return Err(anyhow!(
+                            "SET TRANSACTION can only be used in transaction blocks"
+                        ))
+                    }
+                };
+                if self.current_txn_queried {
+                    return Err(anyhow!(
+                        "SET TRANSACTION ISOLATION LEVEL must be called before any query"
+                    ));
+                }
+                self.instance
+                    .read()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .set_isolation_level(txn_id, ast.isolation_level)?;
+                "isolation level set".to_string()
+            }
+            StatementAST::Vacuum(ast) => {
+                // it runs in a transaction of its own, whose snapshot would keep the tuples alive
+                if self.current_txn_id.is_some() {
+                    return Err(anyhow!("VACUUM cannot run inside a transaction block"));
+                }
+                let count = self
                     .instance
                     .read()
                     .map_err(|_| anyhow!("lock error"))?
-                    .begin(self.current_txn_id)?;
-                self.current_txn_id = Some(txn_id);
-                "transaction started.".to_string()
+                    .vacuum(ast.table_name.as_deref())?;
+                format!("{} dead tuples removed", count)
+            }
+            StatementAST::Savepoint(ast) => {
+                let txn_id = self.transaction_block_txn_id("SAVEPOINT")?;
+                self.instance
+                    .read()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .savepoint(txn_id, &ast.name)?;
+                "savepoint created.".to_string()
+            }
+            StatementAST::RollbackToSavepoint(ast) => {
+                let txn_id = self.transaction_block_txn_id("ROLLBACK TO SAVEPOINT")?;
+                self.instance
+                    .read()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .rollback_to_savepoint(txn_id, &ast.name)?;
+                "rolled back to savepoint.".to_string()
+            }
+            StatementAST::ReleaseSavepoint(ast) => {
+                let txn_id = self.transaction_block_txn_id("RELEASE SAVEPOINT")?;
+                self.instance
+                    .read()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .release_savepoint(txn_id, &ast.name)?;
+                "savepoint released.".to_string()
             }
             _ => {
                 let txn_id_existed = self.current_txn_id.is_some();
@@ -150,23 +323,29 @@ impl Session {
                         self.instance
                             .read()
                             .map_err(|_| anyhow!("lock error"))?
-                            .begin(None)?,
+                            .begin_with_isolation_level(self.default_isolation_level)?,
                     );
                     self.current_txn_id = txn_id;
                 }
                 let txn_id = self.current_txn_id.unwrap();
+                self.current_txn_queried = true;
+                // applied to every statement, so that a SET inside a transaction takes effect at once
+                self.instance
+                    .read()
+                    .map_err(|_| anyhow!("lock error"))?
+                    .set_lock_timeout(txn_id, self.lock_timeout)?;
                 let response = match statement {
                     StatementAST::Commit => {
                         self.instance
-                            .write()
+                            .read()
                             .map_err(|_| anyhow!("lock error"))?
-                            .commit(txn_id)?;
+                            .commit_with_durability_window(txn_id, self.async_commit_window)?;
                         self.current_txn_id = None;
                         "transaction committed.".to_string()
                     }
                     StatementAST::Rollback => {
                         self.instance
-                            .write()
+                            .read()
                             .map_err(|_| anyhow!("lock error"))?
                             .rollback(txn_id)?;
                         self.current_txn_id = None;
@@ -174,47 +353,47 @@ impl Session {
                     }
                     StatementAST::CreateTable(ast) => {
                         self.instance
-                            .write()
+                            .read()
                             .map_err(|_| anyhow!("lock error"))?
                             .create_table(&ast, txn_id)?;
                         format!("table {} created", ast.table_name)
                     }
                     StatementAST::CreateIndex(ast) => {
                         self.instance
-                            .write()
+                            .read()
                             .map_err(|_| anyhow!("lock error"))?
                             .create_index(&ast, txn_id)?;
                         format!("index {} created", ast.index_name)
                     }
+                    StatementAST::DropIndex(ast) => {
+                        self.instance
+                            .read()
+                            .map_err(|_| anyhow!("lock error"))?
+                            .drop_index(&ast, txn_id)?;
+                        format!("index {} dropped", ast.index_name)
+                    }
+                    StatementAST::ShowIndexes(ast) => {
+                        let (rows, schema) = self
+                            .instance
+                            .read()
+                            .map_err(|_| anyhow!("lock error"))?
+                            .show_indexes(&ast, txn_id)?;
+                        format_table(rows, schema)
+                    }
                     _ => {
                         let (rows, schema) = self
                             .instance
-                            .write()
+                            .read()
                             .map_err(|_| anyhow!("lock error"))?
                             .execute(&statement, txn_id)?;
-
-                        // TODO: move to client
-                        let mut table_view = Table::new();
-                        let mut header = vec![];
-                        for column in schema.columns {
-                            header.push(Cell::new(&column.name));
-                        }
-                        table_view.set_titles(Row::new(header));
-                        for row in rows {
-                            let cells = row
-                                .iter()
-                                .map(|v| Cell::new(&v.to_string()))
-                                .collect::<Vec<_>>();
-                            table_view.add_row(Row::new(cells));
-                        }
-                        format!("{}", table_view)
+                        format_table(rows, schema)
                     }
                 };
                 if !txn_id_existed {
                     self.instance
-                        .write()
+                        .read()
                         .map_err(|_| anyhow!("lock error"))?
-                        .commit(txn_id)?;
+                        .commit_with_durability_window(txn_id, self.async_commit_window)?;
                     self.current_txn_id = None;
                 }
                 response
@@ -222,6 +401,32 @@ impl Session {
         };
         Ok(response)
     }
+    // a failure of the savepoint statement itself keeps the transaction
+    fn transaction_block_txn_id(&mut self, statement_name: &str) -> Result<TransactionID> {
+        let txn_id = self
+            .current_txn_id
+            .ok_or_else(|| anyhow!("{} can only be used in transaction blocks", statement_name))?;
+        self.current_txn_queried = true;
+        Ok(txn_id)
+    }
+}
+
+// TODO: move to client
+fn format_table(rows: Vec<Vec<Value>>, schema: Schema) -> String {
+    let mut table_view = Table::new();
+    let mut header = vec![];
+    for column in schema.columns {
+        header.push(Cell::new(&column.name));
+    }
+    table_view.set_titles(Row::new(header));
+    for row in rows {
+        let cells = row
+            .iter()
+            .map(|v| Cell::new(&v.to_string()))
+            .collect::<Vec<_>>();
+        table_view.add_row(Row::new(cells));
+    }
+    format!("{}", table_view)
 }
 
 pub fn write_to_stream(stream: &mut TcpStream, response: &str) -> Result<()> {
@@ -239,3 +444,52 @@ pub fn read_from_stream(stream: &mut TcpStream) -> Result<String> {
     let response = String::from_utf8(buffer)?;
     Ok(response)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::setup_test_database;
+
+    fn setup_session() -> Result<Session> {
+        let instance = Arc::new(RwLock::new(setup_test_database()?));
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let stream = TcpStream::connect(listener.local_addr()?)?;
+        Ok(Session::new(stream, instance))
+    }
+
+    // as the session does for each request
+    fn execute(session: &mut Session, query: &str) -> Result<()> {
+        if let Err(e) = session.execute(query) {
+            session.rollback_statement(&e)?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn count_rows(session: &Session) -> Result<usize> {
+        let instance = session.instance.read().map_err(|_| anyhow!("lock error"))?;
+        let txn_id = instance.begin(None)?;
+        let mut parser = Parser::new(tokenize(&mut "SELECT * FROM t1".chars().peekable())?);
+        let (rows, _) = instance.execute(&parser.parse()?, txn_id)?;
+        instance.commit(txn_id)?;
+        Ok(rows.len())
+    }
+
+    #[test]
+    fn test_failed_statement_in_transaction_block() -> Result<()> {
+        let mut session = setup_session()?;
+        execute(&mut session, "BEGIN")?;
+        execute(&mut session, "INSERT INTO t1 VALUES (1, 'foo')")?;
+        // the statements before a syntax error are kept
+        assert!(execute(&mut session, "INSERT INTO t1 VALUES (2,").is_err());
+        assert!(session.current_txn_id.is_some());
+        execute(&mut session, "INSERT INTO t1 VALUES (2, 'bar')")?;
+        // statements which are not queries are undone on their own too
+        assert!(execute(&mut session, "SET lock_timeout = 'foo'").is_err());
+        assert!(execute(&mut session, "VACUUM").is_err());
+        assert!(session.current_txn_id.is_some());
+        execute(&mut session, "COMMIT")?;
+        assert_eq!(count_rows(&session)?, 2);
+        Ok(())
+    }
+}
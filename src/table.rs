@@ -5,13 +5,14 @@ use anyhow::Result;
 use crate::{
     buffer::BufferPoolManager,
     common::{PageID, TransactionID, INVALID_PAGE_ID, INVALID_TRANSACTION_ID, RID},
-    concurrency::TransactionManager,
-    lock::LockManager,
+    concurrency::{DuplicateStatus, TransactionManager, TransactionRollbackError},
+    lock::{LockManager, LockMode},
     log::{
         DeleteFromTablePage, InsertToTablePage, LogManager, LogRecordBody, NewTablePage,
-        SetNextPageID,
+        PruneTablePage, SetNextPageID,
     },
     page::table_page::TABLE_PAGE_PAGE_TYPE,
+    predicate_lock::PredicateLockTarget,
     tuple::Tuple,
     value::Value,
 };
@@ -44,6 +45,15 @@ impl TableHeap {
         }
     }
     pub fn insert(&mut self, values: &[Value]) -> Result<RID> {
+        // concurrent sequential scans did not see the new tuple
+        self.transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .check_predicate_locks(
+                self.txn_id,
+                &[PredicateLockTarget::Table(self.first_page_id)],
+            )?;
+        self.lock_table()?;
         let tuple_data = Tuple::serialize(self.txn_id, INVALID_TRANSACTION_ID, values);
         let mut page_id = self.first_page_id;
         loop {
@@ -52,12 +62,18 @@ impl TableHeap {
                 .lock()
                 .map_err(|_| anyhow::anyhow!("lock error"))?
                 .fetch_page(page_id)?;
-            let result = page
-                .write()
-                .map_err(|_| anyhow::anyhow!("lock error"))?
-                .with_table_page_mut(|table_page| table_page.insert(&tuple_data));
+            // latched until the insert is logged, so that redo assigns the same slots,
+            // and until the next page is linked, so that concurrent inserts do not both append one
+            let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+            let result =
+                page_guard.with_table_page_mut(|table_page| table_page.insert(&tuple_data));
             // TODO: only free space not enough
             if let Ok(rid) = result {
+                // held until the end of the transaction, so unique checks of others can wait for it
+                self.lock_manager
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .lock(rid, self.txn_id, LockMode::Exclusive)?;
                 let lsn = self
                     .log_manager
                     .lock()
@@ -65,13 +81,12 @@ impl TableHeap {
                     .append(
                         self.txn_id,
                         LogRecordBody::InsertToTablePage(InsertToTablePage {
-                            page_id,
+                            rid,
                             data: tuple_data.clone(),
                         }),
                     )?;
-                page.write()
-                    .map_err(|_| anyhow::anyhow!("lock error"))?
-                    .with_table_page_mut(|table_page| table_page.set_lsn(lsn));
+                page_guard.with_table_page_mut(|table_page| table_page.set_lsn(lsn));
+                drop(page_guard);
                 self.buffer_pool_manager
                     .lock()
                     .map_err(|_| anyhow::anyhow!("lock error"))?
@@ -79,34 +94,32 @@ impl TableHeap {
                 return Ok(rid);
             }
 
-            let next_page_id = page
-                .read()
-                .map_err(|_| anyhow::anyhow!("lock error"))?
-                .with_table_page(|table_page| table_page.next_page_id());
+            let next_page_id = page_guard.with_table_page(|table_page| table_page.next_page_id());
             if next_page_id == INVALID_PAGE_ID {
                 let next_page = self
                     .buffer_pool_manager
                     .lock()
                     .map_err(|_| anyhow::anyhow!("lock error"))?
                     .new_page(TABLE_PAGE_PAGE_TYPE)?;
+                let next_page_id = next_page
+                    .read()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .page_id();
                 let lsn = self
                     .log_manager
                     .lock()
                     .map_err(|_| anyhow::anyhow!("lock error"))?
                     .append(
                         self.txn_id,
-                        LogRecordBody::NewTablePage(NewTablePage { page_id }),
+                        LogRecordBody::NewTablePage(NewTablePage {
+                            page_id: next_page_id,
+                        }),
                     )?;
                 next_page
                     .write()
                     .map_err(|_| anyhow::anyhow!("lock error"))?
                     .with_table_page_mut(|table_page| table_page.set_lsn(lsn));
-                let next_page_id = next_page
-                    .read()
-                    .map_err(|_| anyhow::anyhow!("lock error"))?
-                    .page_id();
-                page.write()
-                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                page_guard
                     .with_table_page_mut(|table_page| table_page.set_next_page_id(next_page_id));
                 let lsn = self
                     .log_manager
@@ -119,9 +132,8 @@ impl TableHeap {
                             next_page_id,
                         }),
                     )?;
-                page.write()
-                    .map_err(|_| anyhow::anyhow!("lock error"))?
-                    .with_table_page_mut(|table_page| table_page.set_lsn(lsn));
+                page_guard.with_table_page_mut(|table_page| table_page.set_lsn(lsn));
+                drop(page_guard);
                 self.buffer_pool_manager
                     .lock()
                     .map_err(|_| anyhow::anyhow!("lock error"))?
@@ -132,6 +144,7 @@ impl TableHeap {
                     .map_err(|_| anyhow::anyhow!("lock error"))?
                     .unpin_page(page_id, true)?;
             } else {
+                drop(page_guard);
                 self.buffer_pool_manager
                     .lock()
                     .map_err(|_| anyhow::anyhow!("lock error"))?
@@ -144,50 +157,311 @@ impl TableHeap {
     pub fn delete(&mut self, rid: RID) -> Result<()> {
         let page_id = rid.0;
         let tuple_index = rid.1;
+        self.transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .check_predicate_locks(
+                self.txn_id,
+                &[
+                    PredicateLockTarget::Table(self.first_page_id),
+                    PredicateLockTarget::Page(page_id),
+                ],
+            )?;
+        self.lock_table()?;
         self.lock_manager
             .read()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .lock(rid, self.txn_id)?;
+            .lock(rid, self.txn_id, LockMode::Exclusive)?;
         let page = self
             .buffer_pool_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
             .fetch_page(page_id)?;
-        page.write()
+        let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+        // the version may have been replaced while the lock was waited for
+        let tuple_data =
+            page_guard.with_table_page(|table_page| table_page.get_tuple(tuple_index as usize));
+        let tuple_data = match tuple_data {
+            Some(tuple_data) => tuple_data,
+            None => {
+                drop(page_guard);
+                self.buffer_pool_manager
+                    .lock()
+                    .map_err(|_| anyhow::anyhow!("lock error"))?
+                    .unpin_page(page_id, false)?;
+                return Err(anyhow::anyhow!("tuple {:?} not found", rid));
+            }
+        };
+        let x_max = Tuple::new(Some(rid), &tuple_data).xmax();
+        let write_conflict = self
+            .transaction_manager
+            .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
-            .with_table_page_mut(|table_page| table_page.delete(tuple_index, self.txn_id));
+            .is_write_conflict(self.txn_id, x_max);
+        if write_conflict {
+            drop(page_guard);
+            self.buffer_pool_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .unpin_page(page_id, false)?;
+            return Err(TransactionRollbackError(
+                "could not serialize access due to concurrent update".to_string(),
+            )
+            .into());
+        }
+        page_guard.with_table_page_mut(|table_page| table_page.delete(tuple_index, self.txn_id));
         let lsn = self
             .log_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
             .append(
                 self.txn_id,
-                LogRecordBody::DeleteFromTablePage(DeleteFromTablePage { rid }),
+                LogRecordBody::DeleteFromTablePage(DeleteFromTablePage {
+                    rid,
+                    prev_xmax: x_max,
+                }),
             )?;
-        page.write()
-            .map_err(|_| anyhow::anyhow!("lock error"))?
-            .with_table_page_mut(|table_page| table_page.set_lsn(lsn));
+        page_guard.with_table_page_mut(|table_page| table_page.set_lsn(lsn));
+        drop(page_guard);
         self.buffer_pool_manager
             .lock()
             .map_err(|_| anyhow::anyhow!("lock error"))?
             .unpin_page(page_id, true)?;
         Ok(())
     }
+    // returns the tuple only if it is visible to the transaction
+    pub fn get(&self, rid: RID) -> Result<Option<Tuple>> {
+        let tuple = match self.fetch(rid)? {
+            Some(tuple) => tuple,
+            None => return Ok(None),
+        };
+        let mut transaction_manager = self
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?;
+        transaction_manager.check_read_conflict(self.txn_id, tuple.xmin(), tuple.xmax())?;
+        let visible = transaction_manager.is_visible(self.txn_id, tuple.xmin(), tuple.xmax());
+        if visible {
+            Ok(Some(tuple))
+        } else {
+            Ok(None)
+        }
+    }
+    pub fn is_all_visible(&self, page_id: PageID) -> Result<bool> {
+        let page = self
+            .buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .fetch_page(page_id)?;
+        let all_visible = page
+            .read()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .with_table_page(|table_page| table_page.is_all_visible());
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(page_id, false)?;
+        Ok(all_visible)
+    }
+    // sets the all-visible flag of the pages whose tuples are visible to all transactions,
+    // returns the number of the flagged pages
+    pub fn mark_all_visible_pages(&self) -> Result<usize> {
+        let mut count = 0;
+        let mut page_id = self.first_page_id;
+        while page_id != INVALID_PAGE_ID {
+            let (all_visible, next_page_id) = self.mark_all_visible(page_id)?;
+            if all_visible {
+                count += 1;
+            }
+            page_id = next_page_id;
+        }
+        Ok(count)
+    }
+    // returns whether the page is flagged and the next page id
+    pub fn mark_all_visible(&self, page_id: PageID) -> Result<(bool, PageID)> {
+        let page = self
+            .buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .fetch_page(page_id)?;
+        let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+        let (tuples, next_page_id, all_visible) = page_guard.with_table_page(|table_page| {
+            (
+                table_page.get_tuples(),
+                table_page.next_page_id(),
+                table_page.is_all_visible(),
+            )
+        });
+        let mut is_dirty = false;
+        if !all_visible {
+            let transaction_manager = self
+                .transaction_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+            if tuples.iter().flatten().all(|data| {
+                let tuple = Tuple::new(None, data);
+                transaction_manager.is_visible_to_all(tuple.xmin(), tuple.xmax())
+            }) {
+                page_guard.with_table_page_mut(|table_page| table_page.set_all_visible(true));
+                is_dirty = true;
+            }
+        }
+        drop(page_guard);
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(page_id, is_dirty)?;
+        Ok((all_visible || is_dirty, next_page_id))
+    }
+    pub fn duplicate_status(&self, rid: RID) -> Result<DuplicateStatus> {
+        let tuple = match self.fetch(rid)? {
+            Some(tuple) => tuple,
+            None => return Ok(DuplicateStatus::Dead),
+        };
+        Ok(self
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .duplicate_status(self.txn_id, tuple.xmin(), tuple.xmax()))
+    }
+    // whether a committed transaction other than this one deleted or replaced the version, whatever
+    // the snapshot is. once the row is locked, it can not happen until the lock is released
+    pub fn is_updated(&self, rid: RID) -> Result<bool> {
+        let tuple = match self.fetch(rid)? {
+            Some(tuple) => tuple,
+            None => return Ok(true),
+        };
+        Ok(self
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .is_write_conflict(self.txn_id, tuple.xmax()))
+    }
+    // the versions in the page which no transaction can see any more, and the next page id
+    pub fn dead_tuples(&self, page_id: PageID) -> Result<(Vec<Tuple>, PageID)> {
+        let page = self
+            .buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .fetch_page(page_id)?;
+        let (tuples, next_page_id) = page
+            .read()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .with_table_page(|table_page| (table_page.get_tuples(), table_page.next_page_id()));
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(page_id, false)?;
+        let transaction_manager = self
+            .transaction_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?;
+        let mut dead_tuples = vec![];
+        for (index, data) in tuples.iter().enumerate() {
+            if let Some(data) = data {
+                let tuple = Tuple::new(Some(RID(page_id, index as u32)), data);
+                if transaction_manager.is_dead_to_all(tuple.xmin(), tuple.xmax()) {
+                    dead_tuples.push(tuple);
+                }
+            }
+        }
+        Ok((dead_tuples, next_page_id))
+    }
+    // removes dead tuples whose index entries are already deleted, the tuples stay dead
+    // whichever transaction crashes, so it is logged without a transaction and never undone
+    pub fn prune(&self, rids: &[RID]) -> Result<()> {
+        let mut page_ids = rids.iter().map(|rid| rid.0).collect::<Vec<_>>();
+        page_ids.sort();
+        page_ids.dedup();
+        for page_id in page_ids {
+            let indexes = rids
+                .iter()
+                .filter(|rid| rid.0 == page_id)
+                .map(|rid| rid.1)
+                .collect::<Vec<_>>();
+            let page = self
+                .buffer_pool_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .fetch_page(page_id)?;
+            let mut page_guard = page.write().map_err(|_| anyhow::anyhow!("lock error"))?;
+            page_guard.with_table_page_mut(|table_page| table_page.prune(&indexes));
+            let lsn = self
+                .log_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .append(
+                    INVALID_TRANSACTION_ID,
+                    LogRecordBody::PruneTablePage(PruneTablePage { page_id, indexes }),
+                )?;
+            page_guard.with_table_page_mut(|table_page| table_page.set_lsn(lsn));
+            drop(page_guard);
+            self.buffer_pool_manager
+                .lock()
+                .map_err(|_| anyhow::anyhow!("lock error"))?
+                .unpin_page(page_id, true)?;
+        }
+        Ok(())
+    }
+    // held until the end of the transaction, so that an index build waits for the writers of the
+    // table and the writers wait for the build
+    fn lock_table(&self) -> Result<()> {
+        self.lock_manager
+            .read()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .lock_table(self.first_page_id, self.txn_id, LockMode::Shared)
+    }
+    fn fetch(&self, rid: RID) -> Result<Option<Tuple>> {
+        let page = self
+            .buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .fetch_page(rid.0)?;
+        let tuple_data = page
+            .read()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .with_table_page(|table_page| table_page.get_tuple(rid.1 as usize));
+        self.buffer_pool_manager
+            .lock()
+            .map_err(|_| anyhow::anyhow!("lock error"))?
+            .unpin_page(rid.0, false)?;
+        Ok(tuple_data.map(|tuple_data| Tuple::new(Some(rid), &tuple_data)))
+    }
+}
+
+// the versions a table iterator returns
+enum Visibility {
+    Snapshot,
+    // the newest committed versions, whatever the snapshot of the transaction is
+    Latest,
+    // every version which a transaction can see now or later
+    NotDead,
 }
 
 pub struct TableIterator {
     heap: TableHeap,
+    visibility: Visibility,
     current_page_id: PageID,
     next_page_id: Option<PageID>,
-    tuples: Vec<Box<[u8]>>,
+    tuples: Vec<Option<Box<[u8]>>>,
     tuple_index: usize,
 }
 
 impl TableHeap {
     pub fn iter(self) -> TableIterator {
+        self.iter_with(Visibility::Snapshot)
+    }
+    pub fn iter_latest(self) -> TableIterator {
+        self.iter_with(Visibility::Latest)
+    }
+    pub fn iter_not_dead(self) -> TableIterator {
+        self.iter_with(Visibility::NotDead)
+    }
+    fn iter_with(self, visibility: Visibility) -> TableIterator {
         let page_id = self.first_page_id;
         TableIterator {
             heap: self,
+            visibility,
             current_page_id: page_id,
             next_page_id: Some(page_id),
             tuples: Vec::new(),
@@ -203,11 +477,18 @@ impl Iterator for TableIterator {
             let tuple = self.next_internal();
             match tuple {
                 Some(tuple) => {
-                    if self.heap.transaction_manager.lock().ok()?.is_visible(
-                        self.heap.txn_id,
-                        tuple.xmin(),
-                        tuple.xmax(),
-                    ) {
+                    let transaction_manager = self.heap.transaction_manager.lock().ok()?;
+                    let (txn_id, x_min, x_max) = (self.heap.txn_id, tuple.xmin(), tuple.xmax());
+                    let visible = match self.visibility {
+                        Visibility::Snapshot => {
+                            transaction_manager.is_visible(txn_id, x_min, x_max)
+                        }
+                        Visibility::Latest => {
+                            transaction_manager.is_visible_latest(txn_id, x_min, x_max)
+                        }
+                        Visibility::NotDead => !transaction_manager.is_dead_to_all(x_min, x_max),
+                    };
+                    if visible {
                         return Some(tuple);
                     }
                 }
@@ -224,44 +505,67 @@ impl TableIterator {
         self.tuples = Vec::new();
         self.tuple_index = 0;
     }
-    fn next_internal(&mut self) -> Option<Tuple> {
-        if self.tuple_index >= self.tuples.len() {
-            let next_page_id = self.next_page_id?;
-            let page = self
+    // unlike next, also records the rw-antidependencies of a serializable transaction,
+    // system tables are read by next
+    pub fn next_visible(&mut self) -> Result<Option<Tuple>> {
+        while let Some(tuple) = self.next_internal() {
+            let mut transaction_manager = self
                 .heap
-                .buffer_pool_manager
+                .transaction_manager
                 .lock()
-                .ok()?
-                .fetch_page(next_page_id)
-                .ok()?;
-            self.current_page_id = next_page_id;
-            self.next_page_id = page.read().ok()?.with_table_page(|table_page| {
-                if table_page.next_page_id() == INVALID_PAGE_ID {
-                    None
-                } else {
-                    Some(table_page.next_page_id())
-                }
-            });
-            self.tuples = page
-                .read()
-                .ok()?
-                .with_table_page(|table_page| table_page.get_tuples());
-            self.tuple_index = 0;
-            self.heap
-                .buffer_pool_manager
-                .lock()
-                .ok()?
-                .unpin_page(next_page_id, false)
-                .ok()?;
+                .map_err(|_| anyhow::anyhow!("lock error"))?;
+            transaction_manager.check_read_conflict(
+                self.heap.txn_id,
+                tuple.xmin(),
+                tuple.xmax(),
+            )?;
+            if transaction_manager.is_visible(self.heap.txn_id, tuple.xmin(), tuple.xmax()) {
+                return Ok(Some(tuple));
+            }
         }
-        if self.tuple_index >= self.tuples.len() {
-            return None;
+        Ok(None)
+    }
+    fn next_internal(&mut self) -> Option<Tuple> {
+        loop {
+            if self.tuple_index >= self.tuples.len() {
+                let next_page_id = self.next_page_id?;
+                let page = self
+                    .heap
+                    .buffer_pool_manager
+                    .lock()
+                    .ok()?
+                    .fetch_page(next_page_id)
+                    .ok()?;
+                self.current_page_id = next_page_id;
+                self.next_page_id = page.read().ok()?.with_table_page(|table_page| {
+                    if table_page.next_page_id() == INVALID_PAGE_ID {
+                        None
+                    } else {
+                        Some(table_page.next_page_id())
+                    }
+                });
+                self.tuples = page
+                    .read()
+                    .ok()?
+                    .with_table_page(|table_page| table_page.get_tuples());
+                self.tuple_index = 0;
+                self.heap
+                    .buffer_pool_manager
+                    .lock()
+                    .ok()?
+                    .unpin_page(next_page_id, false)
+                    .ok()?;
+                continue;
+            }
+            let tuple_index = self.tuple_index;
+            self.tuple_index += 1;
+            // skips the unused line pointers of removed tuples
+            if let Some(data) = &self.tuples[tuple_index] {
+                return Some(Tuple::new(
+                    Some(RID(self.current_page_id, tuple_index as u32)),
+                    data,
+                ));
+            }
         }
-        let tuple = Tuple::new(
-            Some(RID(self.current_page_id, self.tuple_index as u32)),
-            &self.tuples[self.tuple_index],
-        );
-        self.tuple_index += 1;
-        Some(tuple)
     }
 }
@@ -18,12 +18,15 @@ pub fn setup_test_database() -> Result<Instance> {
             TableElementAST {
                 column_name: "c1".to_string(),
                 data_type: DataType::Integer,
+                constraints: vec![],
             },
             TableElementAST {
                 column_name: "c2".to_string(),
                 data_type: DataType::Varchar,
+                constraints: vec![],
             },
         ],
+        constraints: vec![],
     };
     instance.create_table(&create_table_ast, txn_id)?;
     let create_table_ast = CreateTableStatementAST {
@@ -32,16 +35,20 @@ pub fn setup_test_database() -> Result<Instance> {
             TableElementAST {
                 column_name: "t1_c1".to_string(),
                 data_type: DataType::Integer,
+                constraints: vec![],
             },
             TableElementAST {
                 column_name: "c1".to_string(),
                 data_type: DataType::Integer,
+                constraints: vec![],
             },
             TableElementAST {
                 column_name: "c2".to_string(),
                 data_type: DataType::Varchar,
+                constraints: vec![],
             },
         ],
+        constraints: vec![],
     };
     instance.create_table(&create_table_ast, txn_id)?;
     let create_table_ast = CreateTableStatementAST {
@@ -50,16 +57,20 @@ pub fn setup_test_database() -> Result<Instance> {
             TableElementAST {
                 column_name: "t2_c1".to_string(),
                 data_type: DataType::Integer,
+                constraints: vec![],
             },
             TableElementAST {
                 column_name: "c1".to_string(),
                 data_type: DataType::Integer,
+                constraints: vec![],
             },
             TableElementAST {
                 column_name: "c2".to_string(),
                 data_type: DataType::Varchar,
+                constraints: vec![],
             },
         ],
+        constraints: vec![],
     };
     instance.create_table(&create_table_ast, txn_id)?;
     instance.commit(txn_id)?;
@@ -77,18 +77,16 @@ impl Tuple {
     }
 
     fn null_bitmap_size(column_count: usize) -> usize {
-        (column_count + 7) / 8
+        column_count.div_ceil(8)
     }
 
     fn null_bitmap(&self, column_count: usize) -> Vec<bool> {
         let mut res = vec![];
-        let mut offset = HEADER_SIZE;
-        for _ in 0..Self::null_bitmap_size(column_count) {
+        for offset in HEADER_SIZE..(HEADER_SIZE + Self::null_bitmap_size(column_count)) {
             let byte = self.data[offset];
             for i in 0..8 {
                 res.push((byte & (1 << i)) != 0);
             }
-            offset += 1;
         }
         res
     }